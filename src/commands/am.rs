@@ -0,0 +1,323 @@
+use crate::commands::apply::{apply_patch, ApplyOptions};
+use crate::commands::commit::advance_head;
+use crate::commands::commit_tree::commit_tree;
+use crate::commands::get_head_hash::get_head_hash;
+use crate::commands::write_tree::write_tree;
+use crate::utils::parse_rfc2822_date;
+use std::fs;
+use std::io::Read;
+
+/// A patch mail read from a mailbox.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mail {
+    pub author: String,
+    /// the unix timestamp and timezone of the `Date:` header, if it could be parsed
+    pub date: Option<(i64, String)>,
+    pub subject: String,
+    /// the full commit message: the subject, then the body if any
+    pub message: String,
+    pub patch: String,
+}
+
+pub fn am(mbox_paths: &[String], three_way: bool) {
+    if mbox_paths.is_empty() {
+        eprintln!("Usage: rgit am [--3way] <mbox>...");
+        std::process::exit(1);
+    }
+
+    for mbox_path in mbox_paths {
+        let mbox: String = if mbox_path == "-" {
+            let mut mbox: String = String::new();
+            std::io::stdin()
+                .read_to_string(&mut mbox)
+                .expect("Failed to read mailbox from standard input");
+            mbox
+        } else {
+            fs::read_to_string(mbox_path).unwrap_or_else(|_| {
+                eprintln!("Error: can't open mailbox '{}'", mbox_path);
+                std::process::exit(1);
+            })
+        };
+
+        for mail in parse_mailbox(&mbox) {
+            println!("Applying: {}", mail.subject);
+            if let Err(errors) = apply_mail(&mail, three_way) {
+                for error in errors {
+                    eprintln!("error: {}", error);
+                }
+                eprintln!("Patch failed at: {}", mail.subject);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Applies the patch of a mail to the index and the worktree, then commits it with the
+/// author, the date and the message of the mail. Returns the new commit hash.
+pub fn apply_mail(mail: &Mail, three_way: bool) -> Result<String, Vec<String>> {
+    if mail.patch.trim().is_empty() {
+        return Err(vec!["Patch is empty.".to_string()]);
+    }
+
+    let options: ApplyOptions = ApplyOptions {
+        check: false,
+        cached: false,
+        index: true,
+        three_way,
+        patch_path: String::new(),
+    };
+    let conflicted_paths: Vec<String> = apply_patch(&mail.patch, &options)?;
+    if !conflicted_paths.is_empty() {
+        return Err(conflicted_paths
+            .iter()
+            .map(|path| format!("{}: merged with conflicts", path))
+            .collect());
+    }
+
+    let tree_hash: String = write_tree();
+    let parent_hash: String = get_head_hash();
+    let parents: Vec<&str> = if parent_hash.is_empty() {
+        Vec::new()
+    } else {
+        vec![parent_hash.as_str()]
+    };
+    let author: String = match &mail.date {
+        Some((timestamp, timezone)) => format!("{} {} {}", mail.author, timestamp, timezone),
+        None => mail.author.clone(),
+    };
+    let commit_hash: String = commit_tree(&mail.message, Some(&author), tree_hash, &parents);
+    let subject: &str = mail.message.lines().next().unwrap_or_default();
+    advance_head(
+        &commit_hash,
+        Some(&parent_hash),
+        &format!("am: {}", subject),
+    );
+
+    Ok(commit_hash)
+}
+
+/// Splits a mailbox into its mails. A mail starts with a `From ` line directly followed by
+/// headers.
+pub fn parse_mailbox(mbox: &str) -> Vec<Mail> {
+    let lines: Vec<&str> = mbox.lines().collect();
+    let mut starts: Vec<usize> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let next_is_header: bool = lines
+            .get(index + 1)
+            .is_some_and(|next_line| is_header(next_line));
+        if line.starts_with("From ") && next_is_header {
+            starts.push(index);
+        }
+    }
+
+    let mut mails: Vec<Mail> = Vec::new();
+    for (position, start) in starts.iter().enumerate() {
+        let end: usize = starts.get(position + 1).copied().unwrap_or(lines.len());
+        mails.push(parse_mail(&lines[start + 1..end]));
+    }
+    mails
+}
+
+fn is_header(line: &str) -> bool {
+    match line.split_once(": ") {
+        Some((name, _)) => {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        }
+        None => false,
+    }
+}
+
+/// Parses the headers and the body of a mail. The body holds the rest of the commit message
+/// until the `---` line (or the first diff), followed by the patch.
+fn parse_mail(lines: &[&str]) -> Mail {
+    let mut author: String = String::new();
+    let mut date: Option<(i64, String)> = None;
+    let mut subject: String = String::new();
+
+    // headers, whose values may be folded on several lines
+    let mut index: usize = 0;
+    let mut headers: Vec<(String, String)> = Vec::new();
+    while index < lines.len() && !lines[index].is_empty() {
+        let line: &str = lines[index];
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.to_lowercase(), value.trim().to_string()));
+        }
+        index += 1;
+    }
+    for (name, value) in headers {
+        match name.as_str() {
+            "from" => author = value,
+            "date" => date = parse_rfc2822_date(&value),
+            "subject" => subject = strip_subject_prefix(&value).to_string(),
+            _ => {}
+        }
+    }
+
+    // the message body, up to the patch
+    let body_start: usize = (index + 1).min(lines.len());
+    let mut patch_start: usize = body_start;
+    while patch_start < lines.len() {
+        let line: &str = lines[patch_start];
+        let starts_unified_diff: bool = line.starts_with("--- ")
+            && lines
+                .get(patch_start + 1)
+                .is_some_and(|next_line| next_line.starts_with("+++ "));
+        if line == "---" || line.starts_with("diff --git ") || starts_unified_diff {
+            break;
+        }
+        patch_start += 1;
+    }
+
+    let body: String = lines[body_start..patch_start].join("\n").trim().to_string();
+    let message: String = if body.is_empty() {
+        subject.clone()
+    } else {
+        format!("{}\n\n{}", subject, body)
+    };
+    let patch: String = lines[patch_start..]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    Mail {
+        author,
+        date,
+        subject,
+        message,
+        patch,
+    }
+}
+
+/// Removes the `[PATCH n/m]`-like tags and `Re:` at the start of a subject.
+fn strip_subject_prefix(subject: &str) -> &str {
+    let mut subject: &str = subject.trim();
+    loop {
+        if subject.starts_with('[') {
+            match subject.find(']') {
+                Some(end) => subject = subject[end + 1..].trim_start(),
+                None => break,
+            }
+        } else if subject.len() >= 3 && subject[..3].eq_ignore_ascii_case("re:") {
+            subject = subject[3..].trim_start();
+        } else {
+            break;
+        }
+    }
+    subject
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::cat_file::cat_file;
+    use crate::commands::commit::commit;
+    use crate::commands::format_patch::format_mail;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::{add_index, write_index};
+    use crate::commands::update_ref::update_ref;
+    use crate::diff::DiffSettings;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+    use crate::utils::{get_commit_tree, read_tree, RepoPath};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_mailbox_reads_headers_and_message() {
+        let mbox: &str = "From 1234 Mon Sep 17 00:00:00 2001
+From: Jane Doe <jane@example.com>
+Date: Thu, 13 Apr 2023 16:30:00 +0200
+Subject: [PATCH 1/2] Fix the
+ parser
+
+Details.
+---
+ file.txt | 1 +
+
+diff --git a/file.txt b/file.txt
+From 5678 Mon Sep 17 00:00:00 2001
+From: John Doe
+Subject: Re: [PATCH] Second
+
+diff --git a/other.txt b/other.txt
+";
+        let mails: Vec<Mail> = parse_mailbox(mbox);
+        assert_eq!(mails.len(), 2);
+        assert_eq!(mails[0].author, "Jane Doe <jane@example.com>");
+        assert_eq!(mails[0].date, Some((1681396200, "+0200".to_string())));
+        assert_eq!(mails[0].subject, "Fix the parser");
+        assert_eq!(mails[0].message, "Fix the parser\n\nDetails.");
+        assert!(mails[0].patch.starts_with("---\n file.txt | 1 +\n"));
+        assert_eq!(mails[1].date, None);
+        assert_eq!(mails[1].message, "Second");
+        assert_eq!(mails[1].patch, "diff --git a/other.txt b/other.txt\n");
+    }
+
+    #[test]
+    fn test_am_applies_formatted_patch() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\ntwo\nthree\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("Initial commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+
+        fs::write("file.txt", "one\n2\nthree\n").unwrap();
+        fs::write("new.txt", "new\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        add_index("new.txt", &hash_object("new.txt"));
+        commit(
+            "Change file\n\nAnd add new.txt.",
+            Some("Jane Doe <jane@example.com> 1681396200 +0200"),
+        );
+        let second_commit: String = get_head_hash();
+        let mbox: String = format_mail(&second_commit, 1, 1, &DiffSettings::from_config());
+
+        // go back to the first commit
+        let first_files: HashMap<String, String> = read_tree(
+            &RepoPath::Local,
+            &get_commit_tree(&RepoPath::Local, &first_commit),
+        )
+        .into_iter()
+        .collect();
+        write_index(&first_files);
+        fs::write("file.txt", "one\ntwo\nthree\n").unwrap();
+        fs::remove_file("new.txt").unwrap();
+        update_ref("HEAD", &first_commit, "update-ref");
+
+        let mails: Vec<Mail> = parse_mailbox(&mbox);
+        let commit_hash: String = apply_mail(&mails[0], false).unwrap();
+        assert_eq!(get_head_hash(), commit_hash);
+        assert_eq!(fs::read_to_string("new.txt").unwrap(), "new\n");
+
+        // the new commit has the same tree, author (with its date) and message as the original
+        // one, the committer is whoever applied the mail
+        let author_line = |content: &str| -> String {
+            content
+                .lines()
+                .find(|line| line.starts_with("author "))
+                .unwrap()
+                .to_string()
+        };
+        let message =
+            |content: &str| -> String { content.split_once("\n\n").unwrap().1.to_string() };
+        let new_content: String = cat_file(&RepoPath::Local, &commit_hash);
+        let original_content: String = cat_file(&RepoPath::Local, &second_commit);
+        assert_eq!(
+            get_commit_tree(&RepoPath::Local, &commit_hash),
+            get_commit_tree(&RepoPath::Local, &second_commit)
+        );
+        assert_eq!(
+            author_line(&new_content),
+            "author Jane Doe <jane@example.com> 1681396200 +0200"
+        );
+        assert_eq!(author_line(&new_content), author_line(&original_content));
+        assert_eq!(message(&new_content), message(&original_content));
+        assert!(new_content.contains(&format!("parent {}\n", first_commit)));
+
+        remove_test_repo();
+    }
+}
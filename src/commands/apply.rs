@@ -0,0 +1,431 @@
+use crate::commands::cat_file::read_object;
+use crate::commands::update_index::{read_index, write_index};
+use crate::diff::merge::{merge3, MergeResult};
+use crate::diff::patch::{apply_hunks, parse_patch, FilePatch, RejectedHunk};
+use crate::diff::DiffSettings;
+use crate::utils::{compute_hash, find_object_by_prefix, hash_and_store, verify_path, RepoPath};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Options of the `apply` command.
+pub struct ApplyOptions {
+    /// only check that the patch applies, without writing anything
+    pub check: bool,
+    /// apply to the index only
+    pub cached: bool,
+    /// apply to both the index and the worktree
+    pub index: bool,
+    /// fall back to a three-way merge with the blob the patch was made against
+    pub three_way: bool,
+    /// path to the patch, or `-` for the standard input
+    pub patch_path: String,
+}
+
+/// The state of a file after the patch, before it is written.
+struct PatchedFile {
+    /// `None` when the file is removed
+    content: Option<String>,
+    mode: Option<String>,
+}
+
+pub fn parse_apply_options(args: &[String]) -> ApplyOptions {
+    let mut options: ApplyOptions = ApplyOptions {
+        check: false,
+        cached: false,
+        index: false,
+        three_way: false,
+        patch_path: String::new(),
+    };
+
+    for arg in args {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--cached" => options.cached = true,
+            "--index" => options.index = true,
+            "--3way" | "-3" => options.three_way = true,
+            _ if arg.starts_with('-') && arg != "-" => {
+                eprintln!("Error: unknown apply option '{}'", arg);
+                std::process::exit(1);
+            }
+            _ if options.patch_path.is_empty() => options.patch_path = arg.clone(),
+            _ => {
+                eprintln!("Error: only one patch can be applied at a time");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if options.patch_path.is_empty() {
+        eprintln!("Usage: rgit apply [--check] [--cached|--index] [--3way] <patch>");
+        std::process::exit(1);
+    }
+
+    options
+}
+
+pub fn apply(options: &ApplyOptions) {
+    let patch_text: String = if options.patch_path == "-" {
+        let mut patch_text: String = String::new();
+        std::io::stdin()
+            .read_to_string(&mut patch_text)
+            .expect("Failed to read patch from standard input");
+        patch_text
+    } else {
+        fs::read_to_string(&options.patch_path).unwrap_or_else(|_| {
+            eprintln!("Error: can't open patch '{}'", options.patch_path);
+            std::process::exit(1);
+        })
+    };
+
+    match apply_patch(&patch_text, options) {
+        Ok(conflicted_paths) => {
+            for path in &conflicted_paths {
+                eprintln!("Applied patch to '{}' with conflicts.", path);
+            }
+            if !conflicted_paths.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Err(errors) => {
+            for error in errors {
+                eprintln!("error: {}", error);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Applies a patch to the worktree and/or the index. The patch is applied entirely or not at
+/// all: on error nothing is written and every problem is returned. On success, returns the
+/// paths that were merged with conflicts (only with `--3way`).
+pub fn apply_patch(patch_text: &str, options: &ApplyOptions) -> Result<Vec<String>, Vec<String>> {
+    let file_patches: Vec<FilePatch> = parse_patch(patch_text).map_err(|error| vec![error])?;
+    if file_patches.is_empty() {
+        return Err(vec!["No valid patches in input".to_string()]);
+    }
+
+    let mut index: HashMap<String, String> = read_index();
+    let mut patched_files: BTreeMap<String, PatchedFile> = BTreeMap::new();
+    let mut conflicted_paths: Vec<String> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+
+    for file_patch in &file_patches {
+        let display_path: &str = file_patch.display_path();
+        let paths: [&Option<String>; 2] = [&file_patch.old_path, &file_patch.new_path];
+        if let Some(error) = paths
+            .into_iter()
+            .flatten()
+            .find_map(|path| verify_path(path).err())
+        {
+            errors.push(format!("invalid path {}", error));
+            continue;
+        }
+        if file_patch.is_binary {
+            errors.push(format!("{}: cannot apply binary patch", display_path));
+            continue;
+        }
+
+        let preimage: String = match &file_patch.old_path {
+            Some(old_path) => match read_preimage(old_path, options, &index, &patched_files) {
+                Ok(preimage) => preimage,
+                Err(error) => {
+                    errors.push(error);
+                    continue;
+                }
+            },
+            None => {
+                if target_exists(display_path, options, &index, &patched_files) {
+                    errors.push(format!("{}: already exists", display_path));
+                    continue;
+                }
+                String::new()
+            }
+        };
+
+        let postimage: String = match apply_hunks(&preimage, &file_patch.hunks) {
+            Ok(postimage) => postimage,
+            Err(rejected_hunks) => match three_way_merge(file_patch, &preimage, options) {
+                Some(merge_result) => {
+                    if merge_result.has_conflicts {
+                        conflicted_paths.push(display_path.to_string());
+                    }
+                    merge_result.content
+                }
+                None => {
+                    for rejected_hunk in rejected_hunks {
+                        errors.push(rejection_message(display_path, &rejected_hunk));
+                    }
+                    continue;
+                }
+            },
+        };
+
+        match &file_patch.new_path {
+            Some(new_path) => {
+                // a rename removes the old path
+                if let Some(old_path) = &file_patch.old_path {
+                    if old_path != new_path && !file_patch.is_copy {
+                        patched_files.insert(
+                            old_path.clone(),
+                            PatchedFile {
+                                content: None,
+                                mode: None,
+                            },
+                        );
+                    }
+                }
+                let mode: Option<String> = match (&file_patch.old_mode, &file_patch.new_mode) {
+                    (Some(old_mode), Some(new_mode)) if old_mode == new_mode => None,
+                    (_, new_mode) => new_mode.clone(),
+                };
+                patched_files.insert(
+                    new_path.clone(),
+                    PatchedFile {
+                        content: Some(postimage),
+                        mode,
+                    },
+                );
+            }
+            None => {
+                if !postimage.is_empty() {
+                    errors.push(format!(
+                        "{}: removal patch leaves file contents",
+                        display_path
+                    ));
+                    continue;
+                }
+                patched_files.insert(
+                    display_path.to_string(),
+                    PatchedFile {
+                        content: None,
+                        mode: None,
+                    },
+                );
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    if options.check {
+        return Ok(conflicted_paths);
+    }
+
+    for (path, patched_file) in &patched_files {
+        if !options.cached {
+            write_worktree_file(path, patched_file);
+        }
+        if options.cached || options.index {
+            match &patched_file.content {
+                Some(content) => {
+                    let blob_hash: String = hash_and_store("blob", content.as_bytes());
+                    index.insert(path.clone(), blob_hash);
+                }
+                None => {
+                    index.remove(path);
+                }
+            }
+        }
+        if !conflicted_paths.contains(path) {
+            println!("Applied patch to '{}' cleanly.", path);
+        }
+    }
+    if options.cached || options.index {
+        write_index(&index);
+    }
+
+    Ok(conflicted_paths)
+}
+
+/// Reads the content a file patch applies to, taking earlier patches to the same path into
+/// account.
+fn read_preimage(
+    path: &str,
+    options: &ApplyOptions,
+    index: &HashMap<String, String>,
+    patched_files: &BTreeMap<String, PatchedFile>,
+) -> Result<String, String> {
+    if let Some(patched_file) = patched_files.get(path) {
+        return patched_file
+            .content
+            .clone()
+            .ok_or(format!("{}: No such file or directory", path));
+    }
+
+    if options.cached {
+        return match index.get(path) {
+            Some(blob_hash) => {
+                let (_, content): (String, Vec<u8>) = read_object(&RepoPath::Local, blob_hash);
+                Ok(String::from_utf8_lossy(&content).into_owned())
+            }
+            None => Err(format!("{}: does not exist in index", path)),
+        };
+    }
+
+    let content: Vec<u8> =
+        fs::read(path).map_err(|_| format!("{}: No such file or directory", path))?;
+    if options.index && index.get(path) != Some(&compute_hash("blob", &content)) {
+        return Err(format!("{}: does not match index", path));
+    }
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+fn target_exists(
+    path: &str,
+    options: &ApplyOptions,
+    index: &HashMap<String, String>,
+    patched_files: &BTreeMap<String, PatchedFile>,
+) -> bool {
+    if let Some(patched_file) = patched_files.get(path) {
+        return patched_file.content.is_some();
+    }
+    let in_index: bool = (options.cached || options.index) && index.contains_key(path);
+    let in_worktree: bool = !options.cached && Path::new(path).exists();
+    in_index || in_worktree
+}
+
+/// Applies the patch to the blob it was made against, then merges the result with the
+/// current content. Returns `None` without `--3way` or if that blob is not available.
+fn three_way_merge(
+    file_patch: &FilePatch,
+    current: &str,
+    options: &ApplyOptions,
+) -> Option<MergeResult> {
+    if !options.three_way {
+        return None;
+    }
+
+    let base_hash: String = find_object_by_prefix(file_patch.old_hash.as_deref()?)?;
+    let (_, base_content): (String, Vec<u8>) = read_object(&RepoPath::Local, &base_hash);
+    let base: String = String::from_utf8_lossy(&base_content).into_owned();
+    let theirs: String = apply_hunks(&base, &file_patch.hunks).ok()?;
+
+    Some(merge3(
+        &base,
+        current,
+        &theirs,
+        "ours",
+        "theirs",
+        DiffSettings::from_config().algorithm,
+    ))
+}
+
+fn rejection_message(path: &str, rejected_hunk: &RejectedHunk) -> String {
+    format!(
+        "patch failed: {}:{} (hunk #{} rejected)",
+        path, rejected_hunk.line, rejected_hunk.number
+    )
+}
+
+fn write_worktree_file(path: &str, patched_file: &PatchedFile) {
+    let content: &String = match &patched_file.content {
+        Some(content) => content,
+        None => {
+            if Path::new(path).exists() {
+                fs::remove_file(path).expect("Failed to remove file");
+            }
+            return;
+        }
+    };
+
+    if let Some(parent_dir) = Path::new(path).parent() {
+        fs::create_dir_all(parent_dir).expect("Failed to create parent directories");
+    }
+    fs::write(path, content).expect("Failed to write file");
+
+    #[cfg(unix)]
+    if let Some(mode) = &patched_file.mode {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions: u32 = if mode == "100755" { 0o755 } else { 0o644 };
+        fs::set_permissions(path, fs::Permissions::from_mode(permissions))
+            .expect("Failed to set file mode");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+
+    fn options(args: &[&str]) -> ApplyOptions {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_apply_options(&args)
+    }
+
+    const PATCH: &str = "diff --git a/file.txt b/file.txt
+index 0000000..1111111 100644
+--- a/file.txt
++++ b/file.txt
+@@ -2,3 +2,3 @@
+ two
+-three
++THREE
+ four
+diff --git a/added.txt b/added.txt
+new file mode 100644
+index 0000000..2222222
+--- /dev/null
++++ b/added.txt
+@@ -0,0 +1 @@
++new file
+";
+
+    #[test]
+    fn test_apply_patch_to_worktree_with_offset() {
+        setup_test_repo();
+
+        // the file gained a line at the top since the patch was made
+        fs::write("file.txt", "zero\none\ntwo\nthree\nfour\n").unwrap();
+
+        assert_eq!(
+            apply_patch(PATCH, &options(&["--check", "p"])),
+            Ok(Vec::new())
+        );
+        assert!(
+            !Path::new("added.txt").exists(),
+            "--check should not write files."
+        );
+
+        assert_eq!(apply_patch(PATCH, &options(&["p"])), Ok(Vec::new()));
+        assert_eq!(
+            fs::read_to_string("file.txt").unwrap(),
+            "zero\none\ntwo\nTHREE\nfour\n"
+        );
+        assert_eq!(fs::read_to_string("added.txt").unwrap(), "new file\n");
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_apply_patch_to_index_and_rejections() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\ntwo\n3\nfour\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+
+        // the hunk does not match: nothing is written
+        assert_eq!(
+            apply_patch(PATCH, &options(&["--cached", "p"])),
+            Err(vec![
+                "patch failed: file.txt:2 (hunk #1 rejected)".to_string()
+            ])
+        );
+        assert!(!read_index().contains_key("added.txt"));
+
+        // the second file patch alone applies to the index, leaving the worktree alone
+        let new_file_patch: &str = &PATCH[PATCH.find("diff --git a/added.txt").unwrap()..];
+        assert_eq!(
+            apply_patch(new_file_patch, &options(&["--cached", "p"])),
+            Ok(Vec::new())
+        );
+        assert!(read_index().contains_key("added.txt"));
+        assert!(!Path::new("added.txt").exists());
+
+        remove_test_repo();
+    }
+}
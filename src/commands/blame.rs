@@ -0,0 +1,505 @@
+use crate::commands::cat_file::{cat_file, read_object};
+use crate::commands::log::{commit_date, find_rename_source};
+use crate::commands::rev_parse::commit_parents;
+use crate::diff::{diff_lines, split_lines, DiffSettings, Edit, DEFAULT_RENAME_THRESHOLD};
+use crate::utils::{
+    get_commit_tree, parse_signature, read_tree, resolve_revision, RepoPath, Signature,
+};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+/// Options of the `blame` command.
+pub struct BlameOptions {
+    /// 1-based inclusive range of lines to annotate
+    pub line_range: Option<(usize, usize)>,
+    pub porcelain: bool,
+    pub revision: String,
+    pub path: String,
+}
+
+/// The commit a line of the file comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameLine {
+    pub commit: String,
+    /// path of the file in that commit, which differs after a rename
+    pub original_path: String,
+    /// 1-based line number in that commit
+    pub original_line: usize,
+    /// 1-based line number in the annotated revision
+    pub final_line: usize,
+    pub text: String,
+}
+
+pub fn parse_blame_options(args: &[String]) -> BlameOptions {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit blame [-L <start>,<end>] [--porcelain] [<rev>] <file>");
+        std::process::exit(1);
+    };
+
+    let mut options: BlameOptions = BlameOptions {
+        line_range: None,
+        porcelain: false,
+        revision: "HEAD".to_string(),
+        path: String::new(),
+    };
+    let mut positionals: Vec<String> = Vec::new();
+
+    let mut index: usize = 0;
+    while index < args.len() {
+        let arg: &str = args[index].as_str();
+        match arg {
+            "--porcelain" => options.porcelain = true,
+            "-L" if index + 1 < args.len() => {
+                options.line_range =
+                    Some(parse_line_range(&args[index + 1]).unwrap_or_else(|| usage()));
+                index += 1;
+            }
+            "--" => {}
+            _ if arg.starts_with("-L") => {
+                options.line_range = Some(parse_line_range(&arg[2..]).unwrap_or_else(|| usage()));
+            }
+            _ if arg.starts_with('-') => usage(),
+            _ => positionals.push(arg.to_string()),
+        }
+        index += 1;
+    }
+
+    match positionals.as_slice() {
+        [path] => options.path = path.clone(),
+        [revision, path] => {
+            options.revision = revision.clone();
+            options.path = path.clone();
+        }
+        _ => usage(),
+    }
+
+    options
+}
+
+/// Parses `<start>,<end>` or `<start>,+<count>`; a missing end means the end of the file.
+fn parse_line_range(range: &str) -> Option<(usize, usize)> {
+    let (start, end): (&str, &str) = range.split_once(',').unwrap_or((range, ""));
+    let start: usize = if start.is_empty() {
+        1
+    } else {
+        start.parse().ok()?
+    };
+    let end: usize = if end.is_empty() {
+        usize::MAX
+    } else if let Some(count) = end.strip_prefix('+') {
+        start + count.parse::<usize>().ok()?.checked_sub(1)?
+    } else {
+        end.parse().ok()?
+    };
+
+    if start == 0 || end < start {
+        return None;
+    }
+    Some((start, end))
+}
+
+pub fn blame(options: &BlameOptions) {
+    let commit_hash: String = resolve_revision(&options.revision);
+    let blame_lines: Vec<BlameLine> =
+        match blame_file(&commit_hash, &options.path, options.line_range) {
+            Ok(blame_lines) => blame_lines,
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                std::process::exit(1);
+            }
+        };
+
+    if options.porcelain {
+        print!("{}", format_porcelain(&blame_lines));
+    } else {
+        print!("{}", format_blame(&blame_lines, &options.path));
+    }
+}
+
+/// Attributes each line of a file at a commit (or each line of `line_range`) to the commit
+/// that introduced it. The history is walked through all the parents, newest commits first:
+/// lines kept by the diff between a commit and one of its parents are passed on to the first
+/// such parent, the other lines are attributed to the commit.
+pub fn blame_file(
+    commit_hash: &str,
+    path: &str,
+    line_range: Option<(usize, usize)>,
+) -> Result<Vec<BlameLine>, String> {
+    let mut settings: DiffSettings = DiffSettings::from_config();
+    settings.renames = Some(settings.renames.unwrap_or(DEFAULT_RENAME_THRESHOLD));
+
+    let tree: BTreeMap<String, String> = read_tree(
+        &RepoPath::Local,
+        &get_commit_tree(&RepoPath::Local, commit_hash),
+    );
+    let final_content: String = match tree.get(path) {
+        Some(blob_hash) => read_blob(blob_hash),
+        None => return Err(format!("no such path '{}' in {}", path, commit_hash)),
+    };
+    let final_lines: Vec<&str> = split_lines(&final_content);
+
+    let (start, end): (usize, usize) = line_range.unwrap_or((1, final_lines.len()));
+    if start > final_lines.len().max(1) {
+        return Err(format!(
+            "file {} has only {} lines",
+            path,
+            final_lines.len()
+        ));
+    }
+    let end: usize = end.min(final_lines.len());
+
+    // lines still to attribute in each (commit, path), as (final line index, line index in
+    // that commit), and the order to visit them in, the most recent commit on top
+    let mut pending: HashMap<(String, String), Vec<(usize, usize)>> = HashMap::new();
+    let mut queue: BinaryHeap<(i64, Reverse<usize>, String, String)> = BinaryHeap::new();
+    let mut reached: usize = 0;
+    let mut origins: Vec<Option<(String, String, usize)>> = vec![None; final_lines.len()];
+
+    if start <= end {
+        pending.insert(
+            (commit_hash.to_string(), path.to_string()),
+            (start - 1..end).map(|index| (index, index)).collect(),
+        );
+        queue.push((
+            commit_date(commit_hash),
+            Reverse(reached),
+            commit_hash.to_string(),
+            path.to_string(),
+        ));
+    }
+
+    while let Some((_, _, current_commit, current_path)) = queue.pop() {
+        let mut remaining: Vec<(usize, usize)> = pending
+            .remove(&(current_commit.clone(), current_path.clone()))
+            .unwrap_or_default();
+        let tree: BTreeMap<String, String> = read_tree(
+            &RepoPath::Local,
+            &get_commit_tree(&RepoPath::Local, &current_commit),
+        );
+        let blob_hash: String = tree[&current_path].clone();
+        let current_content: String = read_blob(&blob_hash);
+        let current_lines: Vec<&str> = split_lines(&current_content);
+
+        for parent in commit_parents(&current_commit) {
+            if remaining.is_empty() {
+                break;
+            }
+            let parent_tree: BTreeMap<String, String> = read_tree(
+                &RepoPath::Local,
+                &get_commit_tree(&RepoPath::Local, &parent),
+            );
+
+            // the path of the file in the parent, following renames
+            let parent_path: String = if parent_tree.contains_key(&current_path) {
+                current_path.clone()
+            } else {
+                match find_rename_source(&settings, &parent_tree, &tree, &current_path) {
+                    Some(parent_path) => parent_path,
+                    // the file does not come from this parent
+                    None => continue,
+                }
+            };
+
+            // for each line of the current version, the same line in the parent, if kept
+            let parent_blob_hash: &String = &parent_tree[&parent_path];
+            let parent_indexes: Vec<Option<usize>> = if *parent_blob_hash == blob_hash {
+                (0..current_lines.len()).map(Some).collect()
+            } else {
+                let parent_content: String = read_blob(parent_blob_hash);
+                let parent_lines: Vec<&str> = split_lines(&parent_content);
+                let mut parent_indexes: Vec<Option<usize>> = vec![None; current_lines.len()];
+                for edit in diff_lines(&parent_lines, &current_lines, settings.algorithm) {
+                    if let Edit::Equal(parent_index, current_index) = edit {
+                        parent_indexes[current_index] = Some(parent_index);
+                    }
+                }
+                parent_indexes
+            };
+
+            let mut parent_pending: Vec<(usize, usize)> = Vec::new();
+            remaining.retain(
+                |(final_index, current_index)| match parent_indexes[*current_index] {
+                    Some(parent_index) => {
+                        parent_pending.push((*final_index, parent_index));
+                        false
+                    }
+                    None => true,
+                },
+            );
+            if parent_pending.is_empty() {
+                continue;
+            }
+
+            // a commit reached from several children is visited once with all their lines
+            let key: (String, String) = (parent.clone(), parent_path.clone());
+            if !pending.contains_key(&key) {
+                reached += 1;
+                queue.push((commit_date(&parent), Reverse(reached), parent, parent_path));
+            }
+            pending.entry(key).or_default().extend(parent_pending);
+        }
+
+        // the lines found in no parent were introduced by this commit
+        for (final_index, current_index) in remaining {
+            origins[final_index] =
+                Some((current_commit.clone(), current_path.clone(), current_index));
+        }
+    }
+
+    let blame_lines: Vec<BlameLine> = (start - 1..end)
+        .map(|final_index| {
+            let (commit, original_path, original_index): (String, String, usize) = origins
+                [final_index]
+                .clone()
+                .expect("Failed to attribute line");
+            BlameLine {
+                commit,
+                original_path,
+                original_line: original_index + 1,
+                final_line: final_index + 1,
+                text: final_lines[final_index].trim_end_matches('\n').to_string(),
+            }
+        })
+        .collect();
+    Ok(blame_lines)
+}
+
+fn read_blob(blob_hash: &str) -> String {
+    let (_, content): (String, Vec<u8>) = read_object(&RepoPath::Local, blob_hash);
+    String::from_utf8_lossy(&content).into_owned()
+}
+
+fn read_author(commit_hash: &str) -> Signature {
+    let commit_content: String = cat_file(&RepoPath::Local, commit_hash);
+    parse_signature(
+        commit_content
+            .lines()
+            .find(|line| line.starts_with("author "))
+            .map(|line| line.trim_start_matches("author "))
+            .unwrap_or("unknown author"),
+    )
+}
+
+fn is_root_commit(commit_hash: &str) -> bool {
+    commit_parents(commit_hash).is_empty()
+}
+
+/// Formats blame lines for people: `<commit> [<path>] (<author> <line>) <text>`. Root
+/// commits are marked with `^`, and the path is shown when some lines come from a file that
+/// was renamed since.
+pub fn format_blame(blame_lines: &[BlameLine], path: &str) -> String {
+    let mut authors: BTreeMap<String, String> = BTreeMap::new();
+    let mut roots: HashSet<String> = HashSet::new();
+    for blame_line in blame_lines {
+        if !authors.contains_key(&blame_line.commit) {
+            authors.insert(
+                blame_line.commit.clone(),
+                read_author(&blame_line.commit).name,
+            );
+            if is_root_commit(&blame_line.commit) {
+                roots.insert(blame_line.commit.clone());
+            }
+        }
+    }
+
+    let author_width: usize = authors
+        .values()
+        .map(|author| author.chars().count())
+        .max()
+        .unwrap_or(0);
+    let line_width: usize = blame_lines
+        .last()
+        .map(|blame_line| blame_line.final_line.to_string().len())
+        .unwrap_or(1);
+    let show_paths: bool = blame_lines
+        .iter()
+        .any(|blame_line| blame_line.original_path != path);
+    let path_width: usize = blame_lines
+        .iter()
+        .map(|blame_line| blame_line.original_path.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut output: String = String::new();
+    for blame_line in blame_lines {
+        let commit: String = if roots.contains(&blame_line.commit) {
+            format!("^{}", &blame_line.commit[..7])
+        } else {
+            blame_line.commit[..8].to_string()
+        };
+        let path_column: String = if show_paths {
+            format!(" {:<width$}", blame_line.original_path, width = path_width)
+        } else {
+            String::new()
+        };
+        output.push_str(&format!(
+            "{}{} ({:<author_width$} {:>line_width$}) {}\n",
+            commit,
+            path_column,
+            authors[&blame_line.commit],
+            blame_line.final_line,
+            blame_line.text,
+        ));
+    }
+    output
+}
+
+/// Formats blame lines for tools, like `git blame --porcelain`: each line starts with
+/// `<commit> <original line> <final line>` (plus the size of the group on the first line of
+/// a group of consecutive lines from the same commit), the commit details are given the
+/// first time a commit appears, and the text of the line follows on a line starting with a
+/// tab.
+pub fn format_porcelain(blame_lines: &[BlameLine]) -> String {
+    let mut output: String = String::new();
+    let mut described: HashSet<String> = HashSet::new();
+
+    let mut index: usize = 0;
+    while index < blame_lines.len() {
+        // consecutive lines coming from consecutive lines of the same commit
+        let mut group_end: usize = index + 1;
+        while group_end < blame_lines.len()
+            && blame_lines[group_end].commit == blame_lines[index].commit
+            && blame_lines[group_end].original_line == blame_lines[group_end - 1].original_line + 1
+        {
+            group_end += 1;
+        }
+
+        for (position, blame_line) in blame_lines[index..group_end].iter().enumerate() {
+            output.push_str(&format!(
+                "{} {} {}",
+                blame_line.commit, blame_line.original_line, blame_line.final_line
+            ));
+            if position == 0 {
+                output.push_str(&format!(" {}", group_end - index));
+            }
+            output.push('\n');
+
+            if described.insert(blame_line.commit.clone()) {
+                output.push_str(&describe_commit(&blame_line.commit));
+                output.push_str(&format!("filename {}\n", blame_line.original_path));
+            }
+            output.push_str(&format!("\t{}\n", blame_line.text));
+        }
+        index = group_end;
+    }
+    output
+}
+
+fn describe_commit(commit_hash: &str) -> String {
+    let commit_content: String = cat_file(&RepoPath::Local, commit_hash);
+    let author: Signature = read_author(commit_hash);
+    let summary: &str = commit_content
+        .lines()
+        .skip_while(|line| !line.is_empty())
+        .nth(1)
+        .unwrap_or("");
+
+    let mut description: String =
+        format!("author {}\nauthor-mail <{}>\n", author.name, author.email);
+    if let Some((timestamp, timezone)) = &author.date {
+        description.push_str(&format!(
+            "author-time {}\nauthor-tz {}\n",
+            timestamp, timezone
+        ));
+    }
+    description.push_str(&format!("summary {}\n", summary));
+    if is_root_commit(commit_hash) {
+        description.push_str("boundary\n");
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::{add_index, remove_index};
+    use crate::utils::tests::{commit_files, remove_test_repo, setup_test_repo};
+    use std::fs;
+
+    #[test]
+    fn test_blame_attributes_lines_through_renames() {
+        setup_test_repo();
+
+        fs::write("old.txt", "one\ntwo\nthree\nfour\n").unwrap();
+        add_index("old.txt", &hash_object("old.txt"));
+        commit(
+            "Add old.txt",
+            Some("John Doe <john@example.com> 1681392600 +0000"),
+        );
+        let first_commit: String = get_head_hash();
+
+        // rename the file and change a line
+        fs::remove_file("old.txt").unwrap();
+        fs::write("new.txt", "one\n2\nthree\nfour\n").unwrap();
+        remove_index("old.txt");
+        add_index("new.txt", &hash_object("new.txt"));
+        commit(
+            "Rename and change",
+            Some("Jane Doe <jane@example.com> 1681396200 +0200"),
+        );
+        let second_commit: String = get_head_hash();
+
+        let blame_lines: Vec<BlameLine> = blame_file(&second_commit, "new.txt", None).unwrap();
+        let commits: Vec<&str> = blame_lines
+            .iter()
+            .map(|line| line.commit.as_str())
+            .collect();
+        assert_eq!(
+            commits,
+            vec![&first_commit, &second_commit, &first_commit, &first_commit]
+        );
+        assert_eq!(blame_lines[0].original_path, "old.txt");
+        assert_eq!(blame_lines[1].original_path, "new.txt");
+
+        // only lines 2 to 3
+        let blame_lines: Vec<BlameLine> =
+            blame_file(&second_commit, "new.txt", Some((2, 3))).unwrap();
+        assert_eq!(
+            format_blame(&blame_lines, "new.txt"),
+            format!(
+                "{} new.txt (Jane Doe 2) 2\n^{} old.txt (John Doe 3) three\n",
+                &second_commit[..8],
+                &first_commit[..7]
+            )
+        );
+        assert_eq!(
+            format_porcelain(&blame_lines),
+            format!(
+                "{} 2 2 1\nauthor Jane Doe\nauthor-mail <jane@example.com>\nauthor-time 1681396200\nauthor-tz +0200\nsummary Rename and change\nfilename new.txt\n\t2\n{} 3 3 1\nauthor John Doe\nauthor-mail <john@example.com>\nauthor-time 1681392600\nauthor-tz +0000\nsummary Add old.txt\nboundary\nfilename old.txt\n\tthree\n",
+                second_commit, first_commit
+            )
+        );
+
+        assert_eq!(parse_line_range("2,+3"), Some((2, 4)));
+        assert_eq!(parse_line_range("3,1"), None);
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_blame_follows_all_parents_of_merges() {
+        setup_test_repo();
+
+        let base: String = commit_files("base", &[("file.txt", "a\nb\nc\n")], &[]);
+        let left: String = commit_files("left", &[("file.txt", "A\nb\nc\n")], &[&base]);
+        let right: String = commit_files("right", &[("file.txt", "a\nb\nC\n")], &[&base]);
+        let merge: String = commit_files(
+            "Merge right",
+            &[("file.txt", "A\nb\nC\n")],
+            &[&left, &right],
+        );
+
+        // each line comes from the branch that changed it, not from the merge
+        let commits: Vec<String> = blame_file(&merge, "file.txt", None)
+            .unwrap()
+            .into_iter()
+            .map(|line| line.commit)
+            .collect();
+        assert_eq!(commits, vec![left, base, right]);
+
+        remove_test_repo();
+    }
+}
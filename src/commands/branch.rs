@@ -0,0 +1,382 @@
+use crate::commands::cat_file::cat_file;
+use crate::commands::format_patch::split_message;
+use crate::commands::get_head_hash::get_head_hash;
+use crate::commands::reflog::rename_reflog;
+use crate::commands::rev_parse::rev_parse;
+use crate::commands::symbolic_ref::symbolic_ref;
+use crate::commands::update_ref::update_ref;
+use crate::config::get_config;
+use crate::utils::{delete_ref, list_refs, peel_to_commit, read_ref, verify_ref_name, RepoPath};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// What the `branch` command does.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BranchAction {
+    /// list the branches, with more details for each level of verbosity
+    List {
+        verbose: usize,
+    },
+    Create {
+        name: String,
+        start: String,
+    },
+    Delete {
+        names: Vec<String>,
+        force: bool,
+    },
+    /// rename a branch, the current one by default
+    Rename {
+        old: Option<String>,
+        new: String,
+    },
+}
+
+pub fn parse_branch_options(args: &[String]) -> BranchAction {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit branch [-v|-vv]");
+        eprintln!("       rgit branch <name> [<start>]");
+        eprintln!("       rgit branch (-d|-D) <name>...");
+        eprintln!("       rgit branch -m [<old>] <new>");
+        std::process::exit(1);
+    };
+
+    let mut verbose: usize = 0;
+    let mut delete: Option<bool> = None;
+    let mut rename: bool = false;
+    let mut names: Vec<String> = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose += 1,
+            "-vv" => verbose += 2,
+            "-d" | "--delete" => delete = Some(delete.unwrap_or(false)),
+            "-D" => delete = Some(true),
+            "-m" | "--move" => rename = true,
+            _ if arg.starts_with('-') => usage(),
+            _ => names.push(arg.clone()),
+        }
+    }
+
+    match (delete, rename, names.as_slice()) {
+        (None, false, []) => BranchAction::List { verbose },
+        (None, false, [name]) => BranchAction::Create {
+            name: name.clone(),
+            start: "HEAD".to_string(),
+        },
+        (None, false, [name, start]) => BranchAction::Create {
+            name: name.clone(),
+            start: start.clone(),
+        },
+        (Some(force), false, names) if !names.is_empty() => BranchAction::Delete {
+            names: names.to_vec(),
+            force,
+        },
+        (None, true, [new]) => BranchAction::Rename {
+            old: None,
+            new: new.clone(),
+        },
+        (None, true, [old, new]) => BranchAction::Rename {
+            old: Some(old.clone()),
+            new: new.clone(),
+        },
+        _ => usage(),
+    }
+}
+
+pub fn branch(action: &BranchAction) {
+    let result: Result<(), String> = match action {
+        BranchAction::List { verbose } => {
+            print!("{}", format_branches(*verbose));
+            Ok(())
+        }
+        BranchAction::Create { name, start } => create_branch(name, start).map(|_| ()),
+        BranchAction::Delete { names, force } => names.iter().try_for_each(|name| {
+            let commit_hash: String = delete_branch(name, *force)?;
+            println!("Deleted branch {} (was {}).", name, &commit_hash[..7]);
+            Ok(())
+        }),
+        BranchAction::Rename { old, new } => match old.clone().or_else(current_branch) {
+            Some(old) => rename_branch(&old, new),
+            None => Err("fatal: cannot rename the current branch while not on any".to_string()),
+        },
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Returns the name of the branch HEAD points to, or `None` if HEAD is detached.
+pub fn current_branch() -> Option<String> {
+    let head: String = fs::read_to_string(Path::new(".rgit").join("HEAD")).ok()?;
+    head.strip_prefix("ref: refs/heads/")
+        .map(|name| name.trim().to_string())
+}
+
+/// Returns the names of all branches, sorted.
+pub fn list_branches() -> Vec<String> {
+    list_refs("refs/heads/")
+        .into_iter()
+        .map(|ref_name| ref_name.trim_start_matches("refs/heads/").to_string())
+        .collect()
+}
+
+/// Checks that a branch name can be stored under `refs/heads`.
+fn check_branch_name(name: &str) -> Result<(), String> {
+    verify_ref_name(name).map_err(|_| format!("fatal: '{}' is not a valid branch name", name))
+}
+
+fn branch_ref(name: &str) -> String {
+    format!("refs/heads/{}", name)
+}
+
+/// Creates a branch pointing to `start`. Returns the commit hash of the branch.
+pub fn create_branch(name: &str, start: &str) -> Result<String, String> {
+    check_branch_name(name)?;
+    if read_ref(&branch_ref(name)).is_some() {
+        return Err(format!("fatal: a branch named '{}' already exists", name));
+    }
+    let commit_hash: String = rev_parse(start)
+        .map(|hash| peel_to_commit(&hash))
+        .map_err(|_| format!("fatal: not a valid object name: '{}'", start))?;
+    update_ref(
+        &branch_ref(name),
+        &commit_hash,
+        &format!("branch: Created from {}", start),
+    );
+    Ok(commit_hash)
+}
+
+/// Deletes a branch. Unless `force` is set, the branch must be merged into HEAD. Returns the
+/// commit hash the branch pointed to.
+pub fn delete_branch(name: &str, force: bool) -> Result<String, String> {
+    let commit_hash: String =
+        read_ref(&branch_ref(name)).ok_or(format!("error: branch '{}' not found", name))?;
+    if current_branch().as_deref() == Some(name) {
+        return Err(format!(
+            "error: Cannot delete branch '{}' checked out",
+            name
+        ));
+    }
+    if !force && !reachable_commits(&get_head_hash()).contains(&commit_hash) {
+        return Err(format!(
+            "error: The branch '{}' is not fully merged.\nIf you are sure you want to delete it, run 'rgit branch -D {}'.",
+            name, name
+        ));
+    }
+
+    delete_ref(&branch_ref(name));
+    Ok(commit_hash)
+}
+
+/// Renames a branch, and makes HEAD follow it if it is the current branch. The reflog of the
+/// branch moves with it.
+pub fn rename_branch(old: &str, new: &str) -> Result<(), String> {
+    let commit_hash: String =
+        read_ref(&branch_ref(old)).ok_or(format!("error: branch '{}' not found", old))?;
+    check_branch_name(new)?;
+    if read_ref(&branch_ref(new)).is_some() {
+        return Err(format!("fatal: a branch named '{}' already exists", new));
+    }
+
+    let reason: String = format!("Branch: renamed {} to {}", branch_ref(old), branch_ref(new));
+    rename_reflog(&branch_ref(old), &branch_ref(new));
+    delete_ref(&branch_ref(old));
+    update_ref(&branch_ref(new), &commit_hash, &reason);
+    if current_branch().as_deref() == Some(old) {
+        symbolic_ref("HEAD", &branch_ref(new), &reason);
+    }
+    Ok(())
+}
+
+/// Formats the branch list: the current branch is marked with `*`. With `verbose`, each
+/// branch is followed by its commit and subject, and with `verbose >= 2` by its upstream
+/// branch and how far it is ahead of and behind it.
+pub fn format_branches(verbose: usize) -> String {
+    let current: Option<String> = current_branch();
+    let mut rows: Vec<(bool, String, String)> = list_branches()
+        .into_iter()
+        .map(|name| {
+            let commit_hash: String = read_ref(&branch_ref(&name)).unwrap_or_default();
+            (current.as_ref() == Some(&name), name, commit_hash)
+        })
+        .collect();
+    let head_hash: String = get_head_hash();
+    if current.is_none() && !head_hash.is_empty() {
+        let label: String = format!("(HEAD detached at {})", &head_hash[..7]);
+        rows.insert(0, (true, label, head_hash));
+    }
+
+    let width: usize = rows
+        .iter()
+        .map(|(_, name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let mut output: String = String::new();
+    for (is_current, name, commit_hash) in rows {
+        let marker: &str = if is_current { "*" } else { " " };
+        if verbose == 0 {
+            output.push_str(&format!("{} {}\n", marker, name));
+            continue;
+        }
+
+        let mut details: String = format!("{} ", &commit_hash[..7]);
+        if verbose >= 2 {
+            if let Some(tracking) = tracking_info(&name, &commit_hash) {
+                details.push_str(&format!("[{}] ", tracking));
+            }
+        }
+        let (subject, _): (String, String) = split_message(
+            cat_file(&RepoPath::Local, &commit_hash)
+                .split_once("\n\n")
+                .map_or("", |(_, message)| message),
+        );
+        details.push_str(&subject);
+        output.push_str(&format!(
+            "{} {:width$} {}\n",
+            marker,
+            name,
+            details,
+            width = width
+        ));
+    }
+    output
+}
+
+/// Returns the full name of the upstream ref of a branch, set by `branch.<name>.remote` and
+/// `branch.<name>.merge` in the config: `refs/remotes/<remote>/<branch>`, or a local branch
+/// for the remote `.`.
+pub fn upstream_ref(name: &str) -> Option<String> {
+    let remote: String = get_config(&format!("branch.{}.remote", name))?;
+    let merge: String = get_config(&format!("branch.{}.merge", name))?;
+    let upstream_branch: &str = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+
+    // "." is the local repository
+    if remote == "." {
+        Some(branch_ref(upstream_branch))
+    } else {
+        Some(format!("refs/remotes/{}/{}", remote, upstream_branch))
+    }
+}
+
+/// Describes the upstream of a branch: `origin/main: ahead 1, behind 2`.
+fn tracking_info(name: &str, commit_hash: &str) -> Option<String> {
+    let upstream_ref: String = upstream_ref(name)?;
+    let label: String = upstream_ref
+        .strip_prefix("refs/remotes/")
+        .or(upstream_ref.strip_prefix("refs/heads/"))
+        .unwrap_or(&upstream_ref)
+        .to_string();
+    let upstream_hash: String = match read_ref(&upstream_ref) {
+        Some(upstream_hash) => upstream_hash,
+        None => return Some(format!("{}: gone", label)),
+    };
+
+    let local_commits: HashSet<String> = reachable_commits(commit_hash);
+    let upstream_commits: HashSet<String> = reachable_commits(&upstream_hash);
+    let ahead: usize = local_commits.difference(&upstream_commits).count();
+    let behind: usize = upstream_commits.difference(&local_commits).count();
+    let counts: Vec<String> = [("ahead", ahead), ("behind", behind)]
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(direction, count)| format!("{} {}", direction, count))
+        .collect();
+
+    if counts.is_empty() {
+        Some(label)
+    } else {
+        Some(format!("{}: {}", label, counts.join(", ")))
+    }
+}
+
+/// Returns a commit and all its ancestors, following every parent.
+pub fn reachable_commits(commit_hash: &str) -> HashSet<String> {
+    let mut commits: HashSet<String> = HashSet::new();
+    let mut to_visit: Vec<String> = vec![commit_hash.to_string()];
+    while let Some(current_commit) = to_visit.pop() {
+        if current_commit.is_empty() || !commits.insert(current_commit.clone()) {
+            continue;
+        }
+        let commit_content: String = cat_file(&RepoPath::Local, &current_commit);
+        for parent_line in commit_content
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .filter(|line| line.starts_with("parent "))
+        {
+            to_visit.push(parent_line.trim_start_matches("parent ").to_string());
+        }
+    }
+    commits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::{commit_files_on_head, remove_test_repo, setup_test_repo};
+
+    #[test]
+    fn test_create_list_rename_and_delete_branches() {
+        setup_test_repo();
+
+        let first_commit: String = commit_files_on_head("First commit", &[("file.txt", "one\n")]);
+        let second_commit: String = commit_files_on_head("Second commit", &[("file.txt", "two\n")]);
+
+        create_branch("feature/old", &first_commit).unwrap();
+        create_branch("topic", "HEAD").unwrap();
+        assert!(create_branch("topic", "HEAD").is_err());
+        assert!(create_branch("../escape", "HEAD").is_err());
+        assert_eq!(
+            fs::read_to_string(".rgit/refs/heads/feature/old").unwrap(),
+            first_commit
+        );
+        assert_eq!(format_branches(0), "  feature/old\n* main\n  topic\n");
+        assert_eq!(
+            format_branches(1),
+            format!(
+                "  feature/old {} First commit\n* main        {} Second commit\n  topic       {} Second commit\n",
+                &first_commit[..7],
+                &second_commit[..7],
+                &second_commit[..7]
+            )
+        );
+
+        // renaming the current branch moves HEAD along
+        rename_branch("main", "trunk").unwrap();
+        assert_eq!(current_branch(), Some("trunk".to_string()));
+        assert_eq!(get_head_hash(), second_commit);
+
+        // only merged branches are deleted without --force
+        assert!(delete_branch("trunk", true).is_err());
+        assert_eq!(delete_branch("feature/old", false), Ok(first_commit));
+        assert!(!Path::new(".rgit/refs/heads/feature").exists());
+        symbolic_ref("HEAD", "refs/heads/topic", "checkout");
+        commit_files_on_head("Third commit", &[("file.txt", "three\n")]);
+        symbolic_ref("HEAD", "refs/heads/trunk", "checkout");
+        assert!(delete_branch("topic", false).is_err());
+        assert!(delete_branch("topic", true).is_ok());
+        assert_eq!(list_branches(), vec!["trunk"]);
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_format_branches_shows_tracking_info() {
+        setup_test_repo();
+
+        let first_commit: String = commit_files_on_head("First commit", &[("file.txt", "one\n")]);
+        commit_files_on_head("Second commit", &[("file.txt", "two\n")]);
+        update_ref("refs/remotes/origin/main", &first_commit, "fetch");
+        fs::write(
+            ".rgit/config",
+            "[branch \"main\"]\n    remote = origin\n    merge = refs/heads/main\n",
+        )
+        .unwrap();
+
+        assert!(format_branches(2).contains(" [origin/main: ahead 1] Second commit\n"));
+        fs::remove_file(".rgit/refs/remotes/origin/main").unwrap();
+        assert!(format_branches(2).contains(" [origin/main: gone] Second commit\n"));
+
+        remove_test_repo();
+    }
+}
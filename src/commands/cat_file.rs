@@ -2,6 +2,12 @@ use crate::utils::{create_object_path, decompress_object, RepoPath};
 use std::fs;
 
 pub fn cat_file(repo_path: &RepoPath, hash: &str) -> String {
+    let (_, contents): (String, Vec<u8>) = read_object(repo_path, hash);
+    String::from_utf8_lossy(&contents).into_owned()
+}
+
+/// Reads an object from the database and returns its type and raw contents.
+pub fn read_object(repo_path: &RepoPath, hash: &str) -> (String, Vec<u8>) {
     let object_path: std::path::PathBuf = create_object_path(repo_path, hash);
     if !object_path.exists() {
         eprintln!("Object {} not found.", hash);
@@ -12,9 +18,16 @@ pub fn cat_file(repo_path: &RepoPath, hash: &str) -> String {
     let decompressed_data: Vec<u8> = decompress_object(&compressed_data);
 
     let null_byte_pos: usize = decompressed_data.iter().position(|&b| b == 0).unwrap();
-    let (_, contents): (&[u8], &[u8]) = decompressed_data.split_at(null_byte_pos + 1);
+    let (header, contents): (&[u8], &[u8]) = decompressed_data.split_at(null_byte_pos + 1);
+
+    // the header is "<type> <size>\0"
+    let object_type: String = String::from_utf8_lossy(header)
+        .split(' ')
+        .next()
+        .unwrap_or("")
+        .to_string();
 
-    String::from_utf8_lossy(contents).into_owned()
+    (object_type, contents.to_vec())
 }
 
 #[cfg(test)]
@@ -0,0 +1,132 @@
+use crate::commands::reflog::previous_branch;
+use crate::utils::check_ref_format;
+
+/// Options of the `check-ref-format` command.
+pub struct CheckRefFormatOptions {
+    /// accept names without `/`
+    pub allow_onelevel: bool,
+    /// print the name with leading and repeated slashes removed
+    pub normalize: bool,
+    /// check a branch name (`@{-<n>}` is expanded) and print it
+    pub branch: bool,
+    pub name: String,
+}
+
+pub fn parse_check_ref_format_options(args: &[String]) -> CheckRefFormatOptions {
+    let mut options: CheckRefFormatOptions = CheckRefFormatOptions {
+        allow_onelevel: false,
+        normalize: false,
+        branch: false,
+        name: String::new(),
+    };
+    let mut names: Vec<String> = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--allow-onelevel" => options.allow_onelevel = true,
+            "--no-allow-onelevel" => options.allow_onelevel = false,
+            "--normalize" | "--print" => options.normalize = true,
+            "--branch" => options.branch = true,
+            _ => names.push(arg.clone()),
+        }
+    }
+    if names.len() != 1 {
+        eprintln!(
+            "Usage: rgit check-ref-format [--allow-onelevel] [--normalize] [--branch] <refname>"
+        );
+        std::process::exit(1);
+    }
+    options.name = names.remove(0);
+    options
+}
+
+/// Exits with status 1 if the name is not a valid ref name.
+pub fn check_ref_format_command(options: &CheckRefFormatOptions) {
+    match checked_ref_name(options) {
+        Ok(Some(name)) => println!("{}", name),
+        Ok(None) => {}
+        Err(error) => {
+            eprintln!("fatal: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Validates the name of the options, returning the name to print for `--normalize` and
+/// `--branch`.
+pub fn checked_ref_name(options: &CheckRefFormatOptions) -> Result<Option<String>, String> {
+    if options.branch {
+        let branch: String = match options
+            .name
+            .strip_prefix("@{-")
+            .and_then(|n| n.strip_suffix('}'))
+        {
+            Some(n) => n
+                .parse()
+                .ok()
+                .and_then(previous_branch)
+                .ok_or(format!("no previous branch for '{}'", options.name))?,
+            None => options.name.clone(),
+        };
+        if branch == "HEAD" || branch.starts_with('-') {
+            return Err(format!("'{}' is not a valid branch name", branch));
+        }
+        check_ref_format(&format!("refs/heads/{}", branch), false)?;
+        return Ok(Some(branch));
+    }
+
+    let name: String = if options.normalize {
+        normalize_ref_name(&options.name)
+    } else {
+        options.name.clone()
+    };
+    check_ref_format(&name, options.allow_onelevel)?;
+    Ok(options.normalize.then_some(name))
+}
+
+/// Removes the leading slash and collapses repeated slashes, e.g. `/refs//heads/x`.
+fn normalize_ref_name(name: &str) -> String {
+    name.split('/')
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(name: &str, allow_onelevel: bool, normalize: bool) -> CheckRefFormatOptions {
+        CheckRefFormatOptions {
+            allow_onelevel,
+            normalize,
+            branch: false,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_check_ref_format_options() {
+        assert_eq!(
+            checked_ref_name(&options("refs/heads/main", false, false)),
+            Ok(None)
+        );
+        assert!(checked_ref_name(&options("main", false, false)).is_err());
+        assert_eq!(checked_ref_name(&options("main", true, false)), Ok(None));
+        assert_eq!(
+            checked_ref_name(&options("/refs//heads/main", false, true)),
+            Ok(Some("refs/heads/main".to_string()))
+        );
+        assert!(checked_ref_name(&options("refs/heads/a..b", false, true)).is_err());
+
+        let branch: CheckRefFormatOptions = CheckRefFormatOptions {
+            branch: true,
+            ..options("feature/x", false, false)
+        };
+        assert_eq!(checked_ref_name(&branch), Ok(Some("feature/x".to_string())));
+        let branch: CheckRefFormatOptions = CheckRefFormatOptions {
+            branch: true,
+            ..options("HEAD.lock", false, false)
+        };
+        assert!(checked_ref_name(&branch).is_err());
+    }
+}
@@ -1,92 +1,460 @@
-use crate::commands::cat_file::cat_file;
+use crate::commands::cat_file::read_object;
+use crate::commands::get_head_hash::get_head_hash;
+use crate::commands::reflog::previous_branch;
+use crate::commands::rev_parse::rev_parse;
 use crate::commands::symbolic_ref::*;
+use crate::commands::update_index::{read_index, write_index};
 use crate::commands::update_ref::*;
-use crate::utils::RepoPath;
+use crate::diff::merge::{merge3, MergeResult};
+use crate::diff::{DiffAlgorithm, DiffSettings};
+use crate::utils::{
+    compute_hash, get_commit_tree, peel_to_commit, read_ref, read_tree, verify_path,
+    verify_ref_name, RepoPath,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
-use std::path::{Path, PathBuf};
-
-pub fn checkout(target: &str) {
-    // check if the target is a branch
-    let branch_ref_path: String = format!(".rgit/refs/{}", target);
-    let commit_hash: String = if Path::new(&branch_ref_path).exists() {
-        // read the commit hash from the branch reference file
-        fs::read_to_string(&branch_ref_path)
-            .expect("Failed to read branch reference")
-            .trim()
-            .to_string()
-    } else {
-        // assume the target is a commit hash
-        target.to_string()
+use std::path::Path;
+
+/// How checkout treats local changes to the files it has to update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckoutMode {
+    /// refuse to overwrite local changes
+    Safe,
+    /// discard local changes to tracked files
+    Force,
+    /// merge local changes into the target version of the files
+    Merge,
+}
+
+pub fn checkout(target: &str, mode: CheckoutMode) {
+    // `-` and `@{-<n>}` name the branches checked out before
+    let previous_branch: Option<String> = match target {
+        "-" => previous_branch(1),
+        _ => target
+            .strip_prefix("@{-")
+            .and_then(|n| n.strip_suffix('}'))
+            .and_then(|n| n.parse().ok())
+            .and_then(previous_branch),
+    };
+    if (target == "-" || target.starts_with("@{-")) && previous_branch.is_none() {
+        eprintln!("error: no previous branch for '{}'", target);
+        std::process::exit(1);
+    }
+    let target: &str = previous_branch.as_deref().unwrap_or(target);
+
+    // where HEAD comes from, for the reflog
+    let moving_from: String = match head_target() {
+        Some(head_target) => head_target.trim_start_matches("refs/heads/").to_string(),
+        None => get_head_hash(),
     };
+    let reason: String = format!("checkout: moving from {} to {}", moving_from, target);
 
-    // obtain the tree hash from the commit
-    let commit_content: String = cat_file(&RepoPath::Local, &commit_hash);
-    let tree_hash: &str = commit_content
-        .lines()
-        .find(|line| line.starts_with("tree "))
-        .map(|line| line.split_whitespace().nth(1).unwrap())
-        .expect("Commit does not contain a tree");
+    // a branch wins over a tag or any other revision of the same name, as HEAD is attached
+    // to it
+    let branch_ref: String = format!("refs/heads/{}", target);
+    let branch_hash: Option<String> = if verify_ref_name(target).is_ok() {
+        read_ref(&branch_ref)
+    } else {
+        None
+    };
+    let commit_hash: String = match &branch_hash {
+        Some(hash) => peel_to_commit(hash),
+        None => match rev_parse(target) {
+            Ok(hash) => peel_to_commit(&hash),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        },
+    };
 
-    // clear the current working directory
-    clear_working_directory();
+    // obtain the trees to switch between
+    let current_files: BTreeMap<String, String> = head_files();
+    let target_files: BTreeMap<String, String> = read_tree(
+        &RepoPath::Local,
+        &get_commit_tree(&RepoPath::Local, &commit_hash),
+    );
 
-    // restore the tree associated with the commit
-    restore_tree(tree_hash, Path::new("."));
+    // update the tracked files that differ between both trees
+    let conflicted_paths: Vec<String> =
+        match switch_trees(&current_files, &target_files, mode, target) {
+            Ok(conflicted_paths) => conflicted_paths,
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        };
     println!("Checked out to {}", target);
 
     // update HEAD
-    if Path::new(&branch_ref_path).exists() {
+    if branch_hash.is_some() {
         // If the target is a branch, make HEAD point to this branch
-        symbolic_ref("HEAD", &format!("refs/{}", target));
+        symbolic_ref("HEAD", &branch_ref, &reason);
         println!("Checked out to branch '{}'", target);
     } else {
         // If the target is a commit hash, set HEAD directly to this commit
-        update_ref("HEAD", &commit_hash);
+        update_ref("HEAD", &commit_hash, &reason);
         println!("Checked out to commit '{}'", commit_hash);
     }
+
+    if !conflicted_paths.is_empty() {
+        for path in &conflicted_paths {
+            eprintln!("CONFLICT: {}", path);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Returns the files of the HEAD commit, or nothing before the first commit.
+pub fn head_files() -> BTreeMap<String, String> {
+    let head_hash: String = get_head_hash();
+    if head_hash.is_empty() {
+        return BTreeMap::new();
+    }
+    read_tree(
+        &RepoPath::Local,
+        &get_commit_tree(&RepoPath::Local, &head_hash),
+    )
 }
 
-fn clear_working_directory() {
-    let current_dir: &Path = Path::new(".");
-    for entry in fs::read_dir(current_dir).expect("Failed to read current directory") {
-        let entry: fs::DirEntry = entry.expect("Failed to get directory entry");
-        let path: PathBuf = entry.path();
+/// Updates the worktree and the index from the `current` tree to the `target` tree. Only the
+/// paths that differ between both trees are touched (all tracked paths with `Force`), so
+/// untracked files and changes to other files are kept. Nothing is written if local changes
+/// (staged or not) or untracked files would be overwritten, unless `mode` allows it. Returns
+/// the paths merged with conflicts.
+pub fn switch_trees(
+    current: &BTreeMap<String, String>,
+    target: &BTreeMap<String, String>,
+    mode: CheckoutMode,
+    target_label: &str,
+) -> Result<Vec<String>, String> {
+    let mut index: HashMap<String, String> = read_index();
+    let paths: BTreeSet<&String> = current.keys().chain(target.keys()).collect();
+
+    // never write outside of the worktree or into the repository
+    for path in &paths {
+        verify_path(path).map_err(|error| format!("error: invalid path {}\nAborting", error))?;
+    }
+
+    let mut updates: Vec<(&str, Option<&String>)> = Vec::new();
+    let mut merges: Vec<&str> = Vec::new();
+    let mut modified_paths: Vec<&str> = Vec::new();
+    let mut untracked_paths: Vec<&str> = Vec::new();
+
+    for path in paths {
+        let current_hash: Option<&String> = current.get(path);
+        let target_hash: Option<&String> = target.get(path);
+        let worktree_hash: Option<String> = worktree_hash(path);
 
-        // do not remove .rgit directory
-        if path.ends_with(".rgit") {
+        if current_hash == target_hash {
+            // with --force, local changes are discarded everywhere
+            if mode == CheckoutMode::Force && worktree_hash.as_ref() != current_hash {
+                updates.push((path, target_hash));
+            }
             continue;
         }
 
-        // remove files and folders
-        if path.is_dir() {
-            fs::remove_dir_all(&path).expect("Failed to remove directory");
+        // the worktree already holds the target version
+        if worktree_hash.as_ref() == target_hash {
+            set_index_entry(&mut index, path, target_hash);
+            continue;
+        }
+
+        let has_local_changes: bool = match current_hash {
+            Some(current_hash) => {
+                worktree_hash.as_ref() != Some(current_hash)
+                    || index.get(path.as_str()) != Some(current_hash)
+            }
+            // an untracked file is in the way
+            None => worktree_hash.is_some(),
+        };
+
+        if !has_local_changes || mode == CheckoutMode::Force {
+            updates.push((path, target_hash));
+        } else if current_hash.is_none() {
+            untracked_paths.push(path);
+        } else if mode == CheckoutMode::Merge && target_hash.is_some() && worktree_hash.is_some() {
+            merges.push(path);
         } else {
-            fs::remove_file(&path).expect("Failed to remove file");
+            modified_paths.push(path);
         }
     }
-    println!("Cleared working directory.");
+
+    let mut errors: Vec<String> = Vec::new();
+    if !modified_paths.is_empty() {
+        errors.push(format!(
+            "error: Your local changes to the following files would be overwritten by checkout:\n\t{}\nPlease commit your changes or stash them before you switch branches.",
+            modified_paths.join("\n\t")
+        ));
+    }
+    if !untracked_paths.is_empty() {
+        errors.push(format!(
+            "error: The following untracked working tree files would be overwritten by checkout:\n\t{}\nPlease move or remove them before you switch branches.",
+            untracked_paths.join("\n\t")
+        ));
+    }
+    if !errors.is_empty() {
+        errors.push("Aborting".to_string());
+        return Err(errors.join("\n"));
+    }
+
+    for (path, blob_hash) in updates {
+        set_index_entry(&mut index, path, blob_hash);
+        match blob_hash {
+            Some(blob_hash) => {
+                let (_, content): (String, Vec<u8>) = read_object(&RepoPath::Local, blob_hash);
+                write_worktree_file(path, &content);
+                println!("Restored file: {}", path);
+            }
+            None => {
+                remove_worktree_file(path);
+                println!("Removed file: {}", path);
+            }
+        }
+    }
+
+    let algorithm: DiffAlgorithm = DiffSettings::from_config().algorithm;
+    let mut conflicted_paths: Vec<String> = Vec::new();
+    for path in merges {
+        let read_blob = |hash: &String| -> String {
+            String::from_utf8_lossy(&read_object(&RepoPath::Local, hash).1).into_owned()
+        };
+        let base: String = read_blob(&current[path]);
+        let theirs: String = read_blob(&target[path]);
+        let ours: String =
+            String::from_utf8_lossy(&fs::read(path).expect("Failed to read file")).into_owned();
+
+        let merge_result: MergeResult =
+            merge3(&base, &ours, &theirs, "local", target_label, algorithm);
+        write_worktree_file(path, merge_result.content.as_bytes());
+        set_index_entry(&mut index, path, target.get(path));
+        if merge_result.has_conflicts {
+            conflicted_paths.push(path.to_string());
+        } else {
+            println!("Merged local changes into: {}", path);
+        }
+    }
+
+    // with --force, the index is reset to the target tree
+    if mode == CheckoutMode::Force {
+        index = target.clone().into_iter().collect();
+    }
+    write_index(&index);
+
+    Ok(conflicted_paths)
 }
 
-fn restore_tree(tree_hash: &str, base_path: &Path) {
-    let tree_content: String = cat_file(&RepoPath::Local, tree_hash);
+pub fn set_index_entry(
+    index: &mut HashMap<String, String>,
+    path: &str,
+    blob_hash: Option<&String>,
+) {
+    match blob_hash {
+        Some(blob_hash) => index.insert(path.to_string(), blob_hash.clone()),
+        None => index.remove(path),
+    };
+}
 
-    for line in tree_content.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 4 {
-            continue;
+/// Hashes a file of the worktree, if it exists.
+pub fn worktree_hash(path: &str) -> Option<String> {
+    if !Path::new(path).is_file() {
+        return None;
+    }
+    let content: Vec<u8> = fs::read(path).expect("Failed to read file");
+    Some(compute_hash("blob", &content))
+}
+
+pub fn write_worktree_file(path: &str, content: &[u8]) {
+    if let Some(parent_dir) = Path::new(path).parent() {
+        fs::create_dir_all(parent_dir).expect("Failed to create parent directories");
+    }
+    fs::write(path, content).expect("Failed to write file");
+}
+
+/// Removes a file, then the directories it leaves empty.
+pub fn remove_worktree_file(path: &str) {
+    if Path::new(path).is_file() {
+        fs::remove_file(path).expect("Failed to remove file");
+    }
+
+    let mut parent_dir: Option<&Path> = Path::new(path).parent();
+    while let Some(dir) = parent_dir {
+        if dir.as_os_str().is_empty() || fs::remove_dir(dir).is_err() {
+            break;
         }
+        parent_dir = dir.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::branch::create_branch;
+    use crate::commands::commit::commit;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::tag::create_tag;
+    use crate::commands::update_index::{add_index, remove_index};
+    use crate::utils::hash_and_store;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+
+    /// Commits `a.txt` and `dir/b.txt`, then a second commit changing `a.txt` and removing
+    /// `dir/b.txt`. Returns the files of both commits.
+    fn two_commits() -> (BTreeMap<String, String>, BTreeMap<String, String>) {
+        fs::write("a.txt", "one\ntwo\nthree\n").unwrap();
+        fs::create_dir("dir").unwrap();
+        fs::write("dir/b.txt", "b\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        add_index("dir/b.txt", &hash_object("dir/b.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_files: BTreeMap<String, String> = head_files();
+
+        fs::write("a.txt", "one\ntwo\nTHREE\n").unwrap();
+        fs::remove_file("dir/b.txt").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        remove_index("dir/b.txt");
+        commit("Second commit", Some("John Doe"));
+        (first_files, head_files())
+    }
+
+    fn index_files() -> BTreeMap<String, String> {
+        read_index().into_iter().collect()
+    }
 
-        let _file_mode: &str = parts[0];
-        let object_type: &str = parts[1];
-        let hash: &str = parts[2];
-        let file_name: &str = parts[3];
+    #[test]
+    fn test_switch_trees_keeps_untracked_files_and_local_changes() {
+        setup_test_repo();
+        let (first_files, second_files) = two_commits();
 
-        let file_path: PathBuf = base_path.join(file_name);
+        // untracked files are kept
+        fs::write("untracked.txt", "keep me\n").unwrap();
+        assert_eq!(
+            switch_trees(&second_files, &first_files, CheckoutMode::Safe, "first"),
+            Ok(Vec::new())
+        );
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "one\ntwo\nthree\n");
+        assert_eq!(fs::read_to_string("dir/b.txt").unwrap(), "b\n");
+        assert_eq!(fs::read_to_string("untracked.txt").unwrap(), "keep me\n");
+        assert_eq!(
+            index_files(),
+            first_files,
+            "the index should match the target tree."
+        );
 
-        if object_type == "blob" {
-            let blob_content: String = cat_file(&RepoPath::Local, hash);
-            fs::write(&file_path, blob_content).expect("Failed to write file");
-            println!("Restored file: {}", file_name);
+        // going back removes dir/b.txt and its directory
+        assert_eq!(
+            switch_trees(&first_files, &second_files, CheckoutMode::Safe, "second"),
+            Ok(Vec::new())
+        );
+        assert!(!Path::new("dir").exists());
+        assert_eq!(index_files(), second_files);
+
+        // a staged change to a file that differs between both trees is not overwritten
+        fs::write("a.txt", "staged\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        fs::write("a.txt", "one\ntwo\nTHREE\n").unwrap();
+        assert!(switch_trees(&second_files, &first_files, CheckoutMode::Safe, "first").is_err());
+        add_index("a.txt", &second_files["a.txt"]);
+
+        // a local change to a file that differs between both trees is not overwritten
+        fs::write("a.txt", "one\ntwo\nTHREE\nfour\n").unwrap();
+        let error: String =
+            switch_trees(&second_files, &first_files, CheckoutMode::Safe, "first").unwrap_err();
+        assert!(error.contains("would be overwritten by checkout:\n\ta.txt\n"));
+        assert_eq!(
+            fs::read_to_string("a.txt").unwrap(),
+            "one\ntwo\nTHREE\nfour\n"
+        );
+
+        // an untracked file in the way is not overwritten either
+        fs::write("a.txt", "one\ntwo\nTHREE\n").unwrap();
+        fs::create_dir("dir").unwrap();
+        fs::write("dir/b.txt", "mine\n").unwrap();
+        let error: String =
+            switch_trees(&second_files, &first_files, CheckoutMode::Safe, "first").unwrap_err();
+        assert!(error.contains(
+            "untracked working tree files would be overwritten by checkout:\n\tdir/b.txt\n"
+        ));
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_switch_trees_refuses_unsafe_paths() {
+        setup_test_repo();
+
+        let blob_hash: String = hash_and_store("blob", b"echo owned\n");
+        for path in [
+            "../evil.txt",
+            "dir/../../evil.txt",
+            ".rgit/config",
+            "dir/.RGIT/config",
+        ] {
+            let target: BTreeMap<String, String> =
+                BTreeMap::from([(path.to_string(), blob_hash.clone())]);
+            let error: String =
+                switch_trees(&BTreeMap::new(), &target, CheckoutMode::Force, "evil").unwrap_err();
+            assert!(
+                error.starts_with("error: invalid path"),
+                "{} should be refused.",
+                path
+            );
         }
+        assert!(!Path::new("../evil.txt").exists());
+        assert!(!fs::read_to_string(".rgit/config")
+            .unwrap()
+            .contains("echo owned"));
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_switch_trees_force_and_merge() {
+        setup_test_repo();
+        let (first_files, second_files) = two_commits();
+
+        // --merge carries the local change over to the target version
+        fs::write("a.txt", "zero\none\ntwo\nTHREE\n").unwrap();
+        assert_eq!(
+            switch_trees(&second_files, &first_files, CheckoutMode::Merge, "first"),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            fs::read_to_string("a.txt").unwrap(),
+            "zero\none\ntwo\nthree\n"
+        );
+
+        // --force discards local changes
+        fs::write("dir/b.txt", "changed\n").unwrap();
+        assert_eq!(
+            switch_trees(&first_files, &first_files, CheckoutMode::Force, "first"),
+            Ok(Vec::new())
+        );
+        assert_eq!(fs::read_to_string("dir/b.txt").unwrap(), "b\n");
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_checkout_prefers_branch_over_tag_of_same_name() {
+        setup_test_repo();
+
+        let (_, second_files): (BTreeMap<String, String>, BTreeMap<String, String>) = two_commits();
+        let second_commit: String = get_head_hash();
+        create_tag("dev", "HEAD~1", None, false).unwrap();
+        create_branch("dev", "HEAD").unwrap();
+
+        // the branch is checked out, and the worktree and the index match it
+        checkout("dev", CheckoutMode::Safe);
+        assert_eq!(head_target(), Some("refs/heads/dev".to_string()));
+        assert_eq!(get_head_hash(), second_commit);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "one\ntwo\nTHREE\n");
+        assert_eq!(index_files(), second_files);
+
+        // the tag can still be checked out by its full name, detaching HEAD
+        checkout("refs/tags/dev", CheckoutMode::Safe);
+        assert_eq!(head_target(), None);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "one\ntwo\nthree\n");
+
+        remove_test_repo();
     }
 }
@@ -1,11 +1,259 @@
+use crate::commands::branch::current_branch;
 use crate::commands::commit_tree::commit_tree;
 use crate::commands::get_head_hash::get_head_hash;
-use crate::commands::update_ref::update_ref;
+use crate::commands::update_ref::{update_refs, RefChange, RefUpdate};
 use crate::commands::write_tree::write_tree;
+use crate::config::get_config;
+use std::env;
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
 
-pub fn commit(commit_message: &str, author: &str) {
+/// How the message of a commit is cleaned up before committing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupMode {
+    /// like `Whitespace`, and remove the lines starting with `#`
+    Strip,
+    /// remove trailing whitespace, leading and trailing empty lines, and repeated empty lines
+    Whitespace,
+    /// keep the message as it is
+    Verbatim,
+}
+
+/// Options of the `commit` command.
+pub struct CommitOptions {
+    /// the paragraphs of the message
+    pub messages: Vec<String>,
+    /// a file to read the message from, `-` for the standard input
+    pub file: Option<String>,
+    /// a file whose content starts the message in the editor, `commit.template` by default
+    pub template: Option<String>,
+    /// open the editor even with `-m` or `-F`
+    pub edit: bool,
+    /// `Strip` when the editor is opened, `Whitespace` otherwise, by default
+    pub cleanup: Option<CleanupMode>,
+    pub author: Option<String>,
+}
+
+pub fn parse_commit_options(args: &[String]) -> CommitOptions {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit commit [-m <message>]... [-F <file>] [-t <template>] [-e] [--cleanup=<mode>] [--author <author>]");
+        eprintln!("       rgit commit <message> [<author>]");
+        std::process::exit(1);
+    };
+
+    let mut options: CommitOptions = CommitOptions {
+        messages: Vec::new(),
+        file: None,
+        template: None,
+        edit: false,
+        cleanup: None,
+        author: None,
+    };
+    let mut positional_args: Vec<String> = Vec::new();
+    let mut index: usize = 0;
+    while index < args.len() {
+        let arg: &str = args[index].as_str();
+        // "--option value" is the same as "--option=value"
+        let (option, value): (&str, Option<String>) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value.to_string())),
+            _ if matches!(
+                arg,
+                "-m" | "--message"
+                    | "-F"
+                    | "--file"
+                    | "-t"
+                    | "--template"
+                    | "--cleanup"
+                    | "--author"
+            ) && index + 1 < args.len() =>
+            {
+                index += 1;
+                (arg, Some(args[index].clone()))
+            }
+            _ => (arg, None),
+        };
+        match (option, value) {
+            ("-m" | "--message", Some(message)) => options.messages.push(message),
+            ("-F" | "--file", Some(file)) => options.file = Some(file),
+            ("-t" | "--template", Some(template)) => options.template = Some(template),
+            ("--author", Some(author)) => options.author = Some(author),
+            ("--cleanup", Some(mode)) => {
+                options.cleanup = match mode.as_str() {
+                    "strip" => Some(CleanupMode::Strip),
+                    "whitespace" => Some(CleanupMode::Whitespace),
+                    "verbatim" => Some(CleanupMode::Verbatim),
+                    "default" => None,
+                    _ => {
+                        eprintln!("fatal: invalid cleanup mode {}", mode);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            ("-e" | "--edit", None) => options.edit = true,
+            _ if !arg.starts_with('-') => positional_args.push(arg.to_string()),
+            _ => usage(),
+        }
+        index += 1;
+    }
+
+    // the older form: commit <message> [<author>]
+    match positional_args.as_slice() {
+        [] => {}
+        [message] => options.messages.push(message.clone()),
+        [message, author] if options.author.is_none() => {
+            options.messages.push(message.clone());
+            options.author = Some(author.clone());
+        }
+        _ => usage(),
+    }
+    if !options.messages.is_empty() && options.file.is_some() {
+        eprintln!("fatal: options -m and -F cannot be used together");
+        std::process::exit(1);
+    }
+    options
+}
+
+pub fn commit_command(options: &CommitOptions) {
+    verify_commit_possible();
+    match commit_message(options) {
+        Ok(message) => commit(&message, options.author.as_deref()),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Builds the message of a commit: the `-m` paragraphs or the content of the `-F` file. When
+/// there are none (or with `--edit`), the editor is opened on `.rgit/COMMIT_EDITMSG`, starting
+/// from them or from the template. The message is then cleaned up. Fails if it is empty, or
+/// if the template was not edited.
+pub fn commit_message(options: &CommitOptions) -> Result<String, String> {
+    let edit: bool = options.edit || (options.messages.is_empty() && options.file.is_none());
+    let mode: CleanupMode = options.cleanup.unwrap_or(if edit {
+        CleanupMode::Strip
+    } else {
+        CleanupMode::Whitespace
+    });
+
+    let template: Option<String> = match options
+        .template
+        .clone()
+        .or_else(|| get_config("commit.template"))
+    {
+        Some(path) => Some(
+            fs::read_to_string(&path)
+                .map_err(|error| format!("fatal: could not read template '{}': {}", path, error))?,
+        ),
+        None => None,
+    };
+    let message: String = if !options.messages.is_empty() {
+        options.messages.join("\n\n")
+    } else if let Some(path) = &options.file {
+        let mut message: String = String::new();
+        if path == "-" {
+            std::io::stdin()
+                .read_to_string(&mut message)
+                .map_err(|error| {
+                    format!("fatal: could not read log from standard input: {}", error)
+                })?;
+        } else {
+            message = fs::read_to_string(path)
+                .map_err(|error| format!("fatal: could not read log file '{}': {}", path, error))?;
+        }
+        message
+    } else {
+        template.clone().unwrap_or_default()
+    };
+
+    // the message is always left in COMMIT_EDITMSG, e.g. to recover it after a failed commit
+    let edit_path: PathBuf = Path::new(".rgit").join("COMMIT_EDITMSG");
+    let message: String = if edit {
+        let mut content: String = message;
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        if mode == CleanupMode::Strip {
+            content.push_str(&format!(
+                "\n# Please enter the commit message for your changes. Lines starting\n# with '#' will be ignored, and an empty message aborts the commit.\n#\n# On branch {}\n",
+                current_branch().unwrap_or("HEAD (detached)".to_string())
+            ));
+        }
+        fs::write(&edit_path, content).expect("Failed to write COMMIT_EDITMSG");
+        launch_editor(&edit_path)?;
+        fs::read_to_string(&edit_path).expect("Failed to read COMMIT_EDITMSG")
+    } else {
+        fs::write(&edit_path, &message).expect("Failed to write COMMIT_EDITMSG");
+        message
+    };
+
+    let message: String = cleanup_message(&message, mode);
+    if message.trim().is_empty() {
+        return Err("Aborting commit due to empty commit message.".to_string());
+    }
+    let from_template: bool = options.messages.is_empty() && options.file.is_none();
+    let unedited: bool = template
+        .as_deref()
+        .is_some_and(|template| cleanup_message(template, mode) == message);
+    if from_template && unedited {
+        return Err("Aborting commit; you did not edit the message.".to_string());
+    }
+    Ok(message)
+}
+
+/// Cleans up a commit message. The result has no final newline, which `commit_tree` adds.
+pub fn cleanup_message(message: &str, mode: CleanupMode) -> String {
+    if mode == CleanupMode::Verbatim {
+        return message.strip_suffix('\n').unwrap_or(message).to_string();
+    }
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in message.lines() {
+        if mode == CleanupMode::Strip && line.starts_with('#') {
+            continue;
+        }
+        let line: &str = line.trim_end();
+        // keep a single empty line between paragraphs, and none at the start
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Opens a file in the editor of the user: `$RGIT_EDITOR`, `core.editor`, `$EDITOR`, or
+/// `vi`, run by the shell so that it may have arguments.
+fn launch_editor(path: &Path) -> Result<(), String> {
+    let editor: String = env::var("RGIT_EDITOR")
+        .ok()
+        .filter(|editor| !editor.is_empty())
+        .or_else(|| get_config("core.editor"))
+        .or_else(|| env::var("EDITOR").ok().filter(|editor| !editor.is_empty()))
+        .unwrap_or("vi".to_string());
+    let status: ExitStatus = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status()
+        .map_err(|error| format!("error: cannot run {}: {}", editor, error))?;
+    if !status.success() {
+        return Err(format!(
+            "error: there was a problem with the editor '{}'.\nPlease supply the message using either -m or -F option.",
+            editor
+        ));
+    }
+    Ok(())
+}
+
+/// Exits if there is no repository, or nothing staged to commit.
+fn verify_commit_possible() {
     // check if the .rgit directory exists
     let rgit_path: &Path = Path::new(".rgit");
     if !rgit_path.exists() {
@@ -20,48 +268,165 @@ pub fn commit(commit_message: &str, author: &str) {
         eprintln!("Error: Nothing to commit. The index is empty.");
         std::process::exit(1);
     }
+}
+
+pub fn commit(commit_message: &str, author: Option<&str>) {
+    verify_commit_possible();
 
     // write the current index to a tree object
     let tree_hash: String = write_tree();
 
-    // get the current HEAD hash or reference
-    let head_path: PathBuf = rgit_path.join("HEAD");
-    let head_content: String = fs::read_to_string(&head_path).expect("Failed to read .rgit/HEAD");
-
-    // check if HEAD points to a symbolic reference or is a direct commit hash
-    let (head_target, is_symbolic_ref): (String, bool) = if head_content.starts_with("ref: ") {
-        (
-            head_content.trim_start_matches("ref: ").trim().to_string(),
-            true,
-        )
-    } else {
-        (head_content.trim().to_string(), false)
-    };
-
     // get the current HEAD hash, if available
     let parent_hash: String = get_head_hash();
 
-    // convert `String` to `Option<&str>`
-    let parent_option: Option<&str> = if parent_hash.is_empty() {
-        None
+    // the first commit has no parent
+    let parents: Vec<&str> = if parent_hash.is_empty() {
+        Vec::new()
     } else {
-        Some(parent_hash.as_str())
+        vec![parent_hash.as_str()]
     };
 
     // create the new commit
-    let commit_hash: String = commit_tree(commit_message, author, tree_hash, parent_option);
+    let commit_hash: String = commit_tree(commit_message, author, tree_hash, &parents);
 
-    // update the reference
-    if is_symbolic_ref {
-        // If HEAD points to a symbolic reference (e.g., a branch), update the branch itself
-        update_ref(&head_target, &commit_hash);
-        println!(
+    // update the reference, noting in the reflog whether this is the first commit
+    let subject: &str = commit_message.lines().next().unwrap_or_default();
+    let reason: String = if parents.is_empty() {
+        format!("commit (initial): {}", subject)
+    } else {
+        format!("commit: {}", subject)
+    };
+    match advance_head(&commit_hash, Some(&parent_hash), &reason) {
+        Some(head_target) => println!(
             "Committed as commit {} and updated branch '{}'",
             commit_hash, head_target
-        );
+        ),
+        None => println!("Committed as commit {} in detached HEAD state", commit_hash),
+    }
+}
+
+/// Moves HEAD to a new commit. Returns the branch that was updated if HEAD points to a
+/// branch, or `None` if HEAD is detached and was updated itself. The reason is recorded in
+/// the reflog.
+///
+/// With `old_hash`, the update only happens if HEAD still points to it (empty: no commit
+/// yet), so that a concurrent commit is not lost; otherwise the process exits.
+pub fn advance_head(commit_hash: &str, old_hash: Option<&str>, reason: &str) -> Option<String> {
+    let head_path: PathBuf = Path::new(".rgit").join("HEAD");
+    let head_content: String = fs::read_to_string(&head_path).expect("Failed to read .rgit/HEAD");
+
+    // check if HEAD points to a symbolic reference or is a direct commit hash
+    if head_content.starts_with("ref: ") {
+        // If HEAD points to a symbolic reference (e.g., a branch), update the branch itself
+        let head_target: String = head_content.trim_start_matches("ref: ").trim().to_string();
+        move_ref(&head_target, commit_hash, old_hash, reason);
+        Some(head_target)
     } else {
         // If HEAD is a direct commit hash (detached HEAD), update HEAD only
-        update_ref("HEAD", &commit_hash);
-        println!("Committed as commit {} in detached HEAD state", commit_hash);
+        move_ref("HEAD", commit_hash, old_hash, reason);
+        None
+    }
+}
+
+fn move_ref(ref_name: &str, commit_hash: &str, old_hash: Option<&str>, reason: &str) {
+    let update: RefUpdate = RefUpdate {
+        ref_name: ref_name.to_string(),
+        change: RefChange::Update(commit_hash.to_string()),
+        old: old_hash.map(|old_hash| old_hash.to_string()),
+    };
+    if let Err(error) = update_refs(&[update], reason) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+
+    fn options(args: &[&str]) -> CommitOptions {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_commit_options(&args)
+    }
+
+    #[test]
+    fn test_cleanup_message_modes() {
+        let message: &str = "\n\nSubject  \n\n\n# a comment\nBody line\t\n\n";
+        assert_eq!(
+            cleanup_message(message, CleanupMode::Strip),
+            "Subject\n\nBody line"
+        );
+        assert_eq!(
+            cleanup_message(message, CleanupMode::Whitespace),
+            "Subject\n\n# a comment\nBody line"
+        );
+        assert_eq!(
+            cleanup_message(message, CleanupMode::Verbatim),
+            "\n\nSubject  \n\n\n# a comment\nBody line\t\n"
+        );
+    }
+
+    #[test]
+    fn test_commit_message_from_options_and_editor() {
+        setup_test_repo();
+
+        // -m paragraphs, and the older positional form
+        assert_eq!(
+            commit_message(&options(&["-m", "Subject", "-m", "Body  "])),
+            Ok("Subject\n\nBody".to_string())
+        );
+        let positional: CommitOptions = options(&["Subject", "John Doe"]);
+        assert_eq!(positional.author.as_deref(), Some("John Doe"));
+        assert_eq!(commit_message(&positional), Ok("Subject".to_string()));
+
+        // -F keeps comments unless told otherwise
+        fs::write("message.txt", "From a file\n# not a comment\n").unwrap();
+        assert_eq!(
+            commit_message(&options(&["-F", "message.txt"])),
+            Ok("From a file\n# not a comment".to_string())
+        );
+        assert_eq!(
+            commit_message(&options(&["-F", "message.txt", "--cleanup=strip"])),
+            Ok("From a file".to_string())
+        );
+
+        // the editor gets the template and the help comments, which are removed
+        fs::write("template.txt", "Subject\n\n# explain why\n").unwrap();
+        fs::write(
+            "editor.sh",
+            "grep -q 'On branch main' \"$1\" && sed -i 's/^Subject$/Edited subject/' \"$1\"\n",
+        )
+        .unwrap();
+        let config: String = fs::read_to_string(".rgit/config").unwrap();
+        fs::write(
+            ".rgit/config",
+            format!("{}[core]\n    editor = sh editor.sh\n", config),
+        )
+        .unwrap();
+        assert_eq!(
+            commit_message(&options(&["--template", "template.txt"])),
+            Ok("Edited subject".to_string())
+        );
+        assert!(fs::read_to_string(".rgit/COMMIT_EDITMSG")
+            .unwrap()
+            .starts_with("Edited subject\n\n# explain why\n"));
+
+        // an empty or unedited message aborts
+        fs::write(
+            ".rgit/config",
+            format!("{}[core]\n    editor = true\n", config),
+        )
+        .unwrap();
+        assert_eq!(
+            commit_message(&options(&[])),
+            Err("Aborting commit due to empty commit message.".to_string())
+        );
+        assert_eq!(
+            commit_message(&options(&["-t", "template.txt"])),
+            Err("Aborting commit; you did not edit the message.".to_string())
+        );
+
+        remove_test_repo();
     }
 }
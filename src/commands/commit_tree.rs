@@ -1,23 +1,120 @@
-use crate::utils::hash_and_store;
+use crate::commands::rev_parse::rev_parse;
+use crate::utils::{committer_signature, hash_and_store, signature_line};
+use std::io::Read;
+
+/// Options of the `commit-tree` command.
+pub struct CommitTreeOptions {
+    pub tree: String,
+    /// the parents of the commit, in order: several make a merge commit
+    pub parents: Vec<String>,
+    /// the paragraphs of the message, read from the standard input when there are none
+    pub messages: Vec<String>,
+    pub author: Option<String>,
+}
+
+pub fn parse_commit_tree_options(args: &[String]) -> CommitTreeOptions {
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: rgit commit-tree <tree> [-p <parent>]... [-m <message>]... [--author <author>]"
+        );
+        std::process::exit(1);
+    };
+
+    let mut tree: Option<String> = None;
+    let mut options: CommitTreeOptions = CommitTreeOptions {
+        tree: String::new(),
+        parents: Vec::new(),
+        messages: Vec::new(),
+        author: None,
+    };
+    let mut index: usize = 0;
+    while index < args.len() {
+        let arg: &str = args[index].as_str();
+        match arg {
+            "-p" | "-m" | "--author" if index + 1 < args.len() => {
+                let value: String = args[index + 1].clone();
+                match arg {
+                    "-p" => options.parents.push(value),
+                    "-m" => options.messages.push(value),
+                    _ => options.author = Some(value),
+                }
+                index += 1;
+            }
+            _ if arg.starts_with("--author=") => {
+                options.author = Some(arg["--author=".len()..].to_string());
+            }
+            _ if !arg.starts_with('-') && tree.is_none() => tree = Some(arg.to_string()),
+            _ => usage(),
+        }
+        index += 1;
+    }
+    options.tree = tree.unwrap_or_else(|| usage());
+    options
+}
+
+/// Resolves the tree and the parents, then prints the hash of the new commit.
+pub fn commit_tree_command(options: &CommitTreeOptions) {
+    let resolve = |spec: &str, object_type: &str| -> String {
+        rev_parse(&format!("{}^{{{}}}", spec, object_type)).unwrap_or_else(|error| {
+            eprintln!("fatal: {}", error);
+            std::process::exit(1);
+        })
+    };
 
-/// Creates a new commit object in the database.
+    let tree_hash: String = resolve(&options.tree, "tree");
+    let mut parent_hashes: Vec<String> = Vec::new();
+    for parent in &options.parents {
+        let parent_hash: String = resolve(parent, "commit");
+        if parent_hashes.contains(&parent_hash) {
+            eprintln!("error: duplicate parent {} ignored", parent_hash);
+        } else {
+            parent_hashes.push(parent_hash);
+        }
+    }
+
+    let message: String = if options.messages.is_empty() {
+        let mut message: String = String::new();
+        std::io::stdin()
+            .read_to_string(&mut message)
+            .expect("Failed to read the commit message from the standard input");
+        message.trim_end().to_string()
+    } else {
+        options.messages.join("\n\n")
+    };
+
+    let parents: Vec<&str> = parent_hashes.iter().map(|hash| hash.as_str()).collect();
+    let commit_hash: String = commit_tree(&message, options.author.as_deref(), tree_hash, &parents);
+    println!("{}", commit_hash);
+}
+
+/// Creates a new commit object in the database. The author is `author` when given (`Name
+/// <email>`, possibly followed by a date), otherwise it comes from the environment or the
+/// config, like the committer (see `signature_line`). Without a committer identity, the
+/// author's is used. A commit has no parent (a root commit), one, or several (a merge).
 pub fn commit_tree(
     commit_name: &str,
-    author: &str,
+    author: Option<&str>,
     tree_hash: String,
-    parent_hash: Option<&str>,
+    parent_hashes: &[&str],
 ) -> String {
-    let parent_line: String = if let Some(parent) = parent_hash {
-        format!("parent {}\n", parent)
-    } else {
-        String::new()
+    let exit_with = |error: String| -> String {
+        eprintln!("{}", error);
+        std::process::exit(1);
     };
+    let author_line: String = signature_line("author", author).unwrap_or_else(exit_with);
+    let committer_line: String = committer_signature(Some(&author_line)).unwrap_or_else(exit_with);
+
+    let parent_lines: String = parent_hashes
+        .iter()
+        .map(|parent| format!("parent {}\n", parent))
+        .collect();
 
     let commit_content: String = format!(
-        "tree {}\n{}author {}\n\n{}\n",
+        "tree {}\n{}author {}\ncommitter {}\n\n{}\n",
         tree_hash.as_str(),
-        parent_line,
-        author,
+        parent_lines,
+        author_line,
+        committer_line,
         commit_name
     );
     let commit_hash: String = hash_and_store("commit", &commit_content.into_bytes());
@@ -29,8 +126,8 @@ pub fn commit_tree(
 mod tests {
     use super::*;
     use crate::commands::cat_file::cat_file;
-    use crate::utils::tests::{remove_test_repo, setup_test_repo}; // import test utilities
-    use crate::utils::RepoPath;
+    use crate::utils::tests::{remove_test_repo, set_test_identity, setup_test_repo}; // import test utilities
+    use crate::utils::{local_timezone, RepoPath};
     use std::path::Path;
 
     #[test]
@@ -42,8 +139,8 @@ mod tests {
 
         // call the `commit_tree` function to create the commit
         let commit_message: &str = "Initial commit";
-        let author: &str = "John Doe <john.doe@example.com>";
-        let commit_hash: String = commit_tree(commit_message, author, tree_hash.to_string(), None);
+        let author: Option<&str> = Some("John Doe <john.doe@example.com>");
+        let commit_hash: String = commit_tree(commit_message, author, tree_hash.to_string(), &[]);
 
         // verify that the commit was created in the `.rgit/objects` directory
         let object_path: String =
@@ -81,9 +178,8 @@ mod tests {
         // simulate two commits using a `parent_hash`
         let tree_hash: &str = "dummy_tree_hash";
         let commit_message1: &str = "First commit";
-        let author: &str = "John Doe <john.doe@example.com>";
-        let commit_hash1: String =
-            commit_tree(commit_message1, author, tree_hash.to_string(), None);
+        let author: Option<&str> = Some("John Doe <john.doe@example.com>");
+        let commit_hash1: String = commit_tree(commit_message1, author, tree_hash.to_string(), &[]);
 
         // create a second commit with `commit_hash1` as the parent
         let commit_message2: &str = "Second commit";
@@ -91,7 +187,7 @@ mod tests {
             commit_message2,
             author,
             tree_hash.to_string(),
-            Some(&commit_hash1),
+            &[&commit_hash1],
         );
 
         // verify that the second commit has a parent
@@ -133,4 +229,20 @@ mod tests {
 
         remove_test_repo();
     }
+
+    #[test]
+    fn test_commit_tree_records_author_and_committer_identities() {
+        setup_test_repo();
+        set_test_identity();
+
+        // both identities come from the config, dated now
+        let commit_hash: String = commit_tree("Message", None, "tree_hash".to_string(), &[]);
+        let commit_content: String = cat_file(&RepoPath::Local, &commit_hash);
+        let lines: Vec<&str> = commit_content.lines().collect();
+        assert!(lines[1].starts_with("author Config User <config@example.com> "));
+        assert!(lines[1].ends_with(&format!(" {}", local_timezone())));
+        assert!(lines[2].starts_with("committer Config User <config@example.com> "));
+
+        remove_test_repo();
+    }
 }
@@ -0,0 +1,274 @@
+use crate::commands::cat_file::read_object;
+use crate::commands::get_head_hash::get_head_hash;
+use crate::commands::rev_parse::commit_parents;
+use crate::commands::update_index::read_index;
+use crate::diff::{diff_snapshots, format_diffs, DiffFormat, DiffSettings, FileDiff};
+use crate::utils::{compute_hash, get_commit_tree, read_tree, resolve_revision, RepoPath};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Options of the `diff` command.
+pub struct DiffOptions {
+    pub cached: bool,
+    pub revisions: Vec<String>,
+    pub settings: DiffSettings,
+    pub format: DiffFormat,
+}
+
+pub fn parse_diff_options(args: &[String]) -> DiffOptions {
+    let mut options: DiffOptions = DiffOptions {
+        cached: false,
+        revisions: Vec::new(),
+        settings: DiffSettings::from_config(),
+        format: DiffFormat::Patch,
+    };
+
+    for arg in args {
+        match arg.as_str() {
+            "--cached" | "--staged" => options.cached = true,
+            "--stat" => options.format = DiffFormat::Stat,
+            "--name-only" => options.format = DiffFormat::NameOnly,
+            "--name-status" => options.format = DiffFormat::NameStatus,
+            _ if options.settings.parse_option(arg) => {}
+            _ if arg.starts_with('-') => {
+                eprintln!("Error: unknown diff option '{}'", arg);
+                std::process::exit(1);
+            }
+            _ => options.revisions.push(arg.clone()),
+        }
+    }
+
+    if options.revisions.len() > 2 || (options.cached && options.revisions.len() > 1) {
+        eprintln!("Usage: rgit diff [--cached] [<rev> [<rev>]] [-U<n>] [--diff-algorithm=<algorithm>] [-M[<n>%]] [-C] [--stat|--name-only|--name-status]");
+        std::process::exit(1);
+    }
+
+    options
+}
+
+pub fn diff(options: &DiffOptions) {
+    print!("{}", diff_output(options));
+}
+
+/// Computes the output of `diff` for the given options.
+pub fn diff_output(options: &DiffOptions) -> String {
+    let read_stored =
+        |_path: &str, hash: &str| -> Vec<u8> { read_object(&RepoPath::Local, hash).1 };
+    let read_worktree =
+        |path: &str, _hash: &str| -> Vec<u8> { fs::read(path).expect("Failed to read file") };
+
+    let file_diffs: Vec<FileDiff> = match options.revisions.as_slice() {
+        // tree vs tree
+        [old_revision, new_revision] => diff_snapshots(
+            &revision_snapshot(old_revision),
+            &revision_snapshot(new_revision),
+            &read_stored,
+            &read_stored,
+        ),
+        // tree vs index
+        [revision] if options.cached => diff_snapshots(
+            &revision_snapshot(revision),
+            &index_snapshot(),
+            &read_stored,
+            &read_stored,
+        ),
+        // tree vs worktree
+        [revision] => {
+            let old: BTreeMap<String, String> = revision_snapshot(revision);
+            let tracked: Vec<String> = old.keys().chain(index_snapshot().keys()).cloned().collect();
+            diff_snapshots(
+                &old,
+                &worktree_snapshot(&tracked),
+                &read_stored,
+                &read_worktree,
+            )
+        }
+        // HEAD vs index
+        _ if options.cached => diff_snapshots(
+            &head_snapshot(),
+            &index_snapshot(),
+            &read_stored,
+            &read_stored,
+        ),
+        // index vs worktree
+        _ => {
+            let index: BTreeMap<String, String> = index_snapshot();
+            let tracked: Vec<String> = index.keys().cloned().collect();
+            diff_snapshots(
+                &index,
+                &worktree_snapshot(&tracked),
+                &read_stored,
+                &read_worktree,
+            )
+        }
+    };
+
+    let file_diffs: Vec<FileDiff> = options.settings.detect_renames(file_diffs);
+    format_diffs(&file_diffs, options.format, &options.settings)
+}
+
+/// Returns the changes introduced by a commit compared to its first parent, or to an empty
+/// tree for a root commit. A merge is only compared to its first parent, the branch it was
+/// merged into, so that its diff shows everything the merge brought (and a stash commit, whose
+/// second parent is the index, shows the changes since HEAD).
+pub fn commit_diffs(commit_hash: &str, settings: &DiffSettings) -> Vec<FileDiff> {
+    let read_stored =
+        |_path: &str, hash: &str| -> Vec<u8> { read_object(&RepoPath::Local, hash).1 };
+
+    let old: BTreeMap<String, String> = match commit_parents(commit_hash).first() {
+        Some(parent) => revision_snapshot(parent),
+        None => BTreeMap::new(),
+    };
+    let file_diffs: Vec<FileDiff> = diff_snapshots(
+        &old,
+        &revision_snapshot(commit_hash),
+        &read_stored,
+        &read_stored,
+    );
+    settings.detect_renames(file_diffs)
+}
+
+/// Returns the files of the tree recorded in a revision.
+fn revision_snapshot(revision: &str) -> BTreeMap<String, String> {
+    let commit_hash: String = resolve_revision(revision);
+    let tree_hash: String = get_commit_tree(&RepoPath::Local, &commit_hash);
+    read_tree(&RepoPath::Local, &tree_hash)
+}
+
+/// Returns the files of the HEAD commit, or nothing before the first commit.
+fn head_snapshot() -> BTreeMap<String, String> {
+    let head_hash: String = get_head_hash();
+    if head_hash.is_empty() {
+        return BTreeMap::new();
+    }
+    revision_snapshot(&head_hash)
+}
+
+fn index_snapshot() -> BTreeMap<String, String> {
+    let index: HashMap<String, String> = read_index();
+    index.into_iter().collect()
+}
+
+/// Hashes the given tracked paths as they are in the worktree; missing files are left out.
+fn worktree_snapshot(paths: &[String]) -> BTreeMap<String, String> {
+    let mut snapshot: BTreeMap<String, String> = BTreeMap::new();
+    for path in paths {
+        if !Path::new(path).is_file() {
+            continue;
+        }
+        let content: Vec<u8> = fs::read(path).expect("Failed to read file");
+        snapshot.insert(path.clone(), compute_hash("blob", &content));
+    }
+    snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{commit_files, remove_test_repo, setup_test_repo};
+
+    fn options(args: &[&str]) -> DiffOptions {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_diff_options(&args)
+    }
+
+    #[test]
+    fn test_diff_shows_worktree_and_staged_changes() {
+        setup_test_repo();
+
+        fs::write("file.txt", "first line\nsecond line\n").unwrap();
+        let hash: String = hash_object("file.txt");
+        add_index("file.txt", &hash);
+
+        // the staged file is new compared to the (empty) HEAD
+        let output: String = diff_output(&options(&["--cached", "--name-status"]));
+        assert_eq!(output, "A\tfile.txt\n");
+
+        commit("Initial commit", Some("John Doe"));
+        fs::write("file.txt", "first line\nsecond line changed\n").unwrap();
+
+        // the worktree differs from the index
+        let output: String = diff_output(&options(&[]));
+        assert!(output.starts_with("diff --git a/file.txt b/file.txt\n"));
+        assert!(
+            output.contains("@@ -1,2 +1,2 @@\n first line\n-second line\n+second line changed\n"),
+            "diff should contain the changed lines."
+        );
+
+        // nothing is staged yet
+        assert_eq!(diff_output(&options(&["--cached"])), "");
+
+        let output: String = diff_output(&options(&["--stat"]));
+        assert_eq!(
+            output,
+            " file.txt | 2 +-\n 1 file changed, 1 insertion(+), 1 deletion(-)\n"
+        );
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_diff_between_two_revisions() {
+        setup_test_repo();
+
+        fs::write("a.txt", "a\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+
+        fs::write("b.bin", b"\0binary").unwrap();
+        add_index("b.bin", &hash_object("b.bin"));
+        crate::commands::update_index::remove_index("a.txt");
+        commit("Second commit", Some("John Doe"));
+        let second_commit: String = get_head_hash();
+
+        let output: String = diff_output(&options(&[&first_commit, &second_commit]));
+        assert!(output.contains("diff --git a/a.txt b/a.txt\ndeleted file mode 100644\n"));
+        assert!(output.contains("--- a/a.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-a\n"));
+        assert!(output.contains("Binary files /dev/null and b/b.bin differ\n"));
+
+        let output: String = diff_output(&options(&["--name-only", &first_commit, &second_commit]));
+        assert_eq!(output, "a.txt\nb.bin\n");
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_commit_diffs_compare_merges_to_their_first_parent() {
+        setup_test_repo();
+
+        let base: String = commit_files(
+            "base",
+            &[("file.txt", "a\n"), ("other.txt", "other\n")],
+            &[],
+        );
+        let left: String = commit_files(
+            "left",
+            &[("file.txt", "A\n"), ("other.txt", "other\n")],
+            &[&base],
+        );
+        let right: String = commit_files(
+            "right",
+            &[("file.txt", "a\n"), ("other.txt", "OTHER\n")],
+            &[&base],
+        );
+        let merge: String = commit_files(
+            "Merge right",
+            &[("file.txt", "A\n"), ("other.txt", "OTHER\n")],
+            &[&left, &right],
+        );
+
+        // the merge brought the changes of the right branch into the left one
+        let paths: Vec<String> = commit_diffs(&merge, &DiffSettings::from_config())
+            .into_iter()
+            .map(|file_diff| file_diff.new_path)
+            .collect();
+        assert_eq!(paths, vec!["other.txt"]);
+
+        remove_test_repo();
+    }
+}
@@ -1,28 +1,45 @@
+use crate::commands::update_ref::update_ref;
 use crate::utils::RepoPath;
 use crate::utils::*;
 use std::collections::HashSet;
 use std::fs;
 use std::process::Command;
 
-pub fn fetch(remote_repo_path: &str, branch: &str) {
-    println!("Fetching from remote repository: {}", remote_repo_path);
+/// Name of the remote the fetched branches are stored under when none is given.
+pub const DEFAULT_REMOTE_NAME: &str = "origin";
+
+pub fn fetch(remote_name: &str, remote_repo_path: &str, branch: &str) {
+    if let Err(error) = fetch_branch(remote_name, remote_repo_path, branch) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
 
-    // determine the remote branch reference (e.g., "refs/heads/main")
-    let remote_branch_ref: String = format!("{}/refs/{}", remote_repo_path, branch);
+/// Copies the objects of a remote branch that are missing locally, then points
+/// `refs/remotes/<remote_name>/<branch>` to it. Returns the fetched commit hash.
+pub fn fetch_branch(
+    remote_name: &str,
+    remote_repo_path: &str,
+    branch: &str,
+) -> Result<String, String> {
+    verify_ref_name(remote_name)
+        .map_err(|_| format!("fatal: '{}' is not a valid remote name", remote_name))?;
+    verify_ref_name(branch)
+        .map_err(|_| format!("fatal: '{}' is not a valid branch name", branch))?;
+    println!("Fetching from remote repository: {}", remote_repo_path);
 
-    // read the remote branch reference to get the latest commit hash
-    let remote_commit_hash: String = fs::read_to_string(&remote_branch_ref)
-        .expect("Failed to read remote branch reference")
-        .trim()
-        .to_string();
+    // read the remote branch, loose or packed: "refs/heads/<branch>", or "refs/<branch>" in
+    // repositories created before branches were moved to refs/heads
+    let repo_path: RepoPath = RepoPath::Remote(remote_repo_path.to_string());
+    let remote_commit_hash: String = read_repo_ref(&repo_path, &format!("refs/heads/{}", branch))
+        .or_else(|| read_repo_ref(&repo_path, &format!("refs/{}", branch)))
+        .ok_or(format!("fatal: couldn't find remote ref '{}'", branch))?;
 
     println!(
         "Remote branch {} points to commit {}",
         branch, remote_commit_hash
     );
 
-    let repo_path: RepoPath = RepoPath::Remote(remote_repo_path.to_string());
-
     // collect all necessary objects
     let objects: HashSet<String> = collect_objects(&repo_path, &remote_commit_hash);
 
@@ -55,12 +72,138 @@ pub fn fetch(remote_repo_path: &str, branch: &str) {
     }
 
     // update the local reference to point to the fetched commit
-    let local_branch_ref: String = format!(".rgit/refs/remotes/{}", branch);
-    fs::write(&local_branch_ref, remote_commit_hash)
-        .expect("Failed to update local branch reference");
+    let reason: String = format!("fetch: storing {} from {}", branch, remote_repo_path);
+    update_ref(
+        &format!("refs/remotes/{}/{}", remote_name, branch),
+        &remote_commit_hash,
+        &reason,
+    );
 
     println!(
         "Successfully updated local reference for branch '{}'",
         branch
     );
+    Ok(remote_commit_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::branch::format_branches;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::init::{init_repository, parse_init_options};
+    use crate::commands::pack_refs::pack_loose_refs;
+    use crate::commands::reflog::read_reflog;
+    use crate::commands::rev_parse::rev_parse;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+    use std::env;
+    use std::path::Path;
+
+    #[test]
+    fn test_fetch_into_fresh_repository() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let commit_hash: String = get_head_hash();
+
+        // a new repository has no refs/remotes yet
+        init_repository(&parse_init_options(&["local".to_string()])).unwrap();
+        env::set_current_dir("local").unwrap();
+        assert_eq!(
+            fetch_branch("origin", "../.rgit", "main"),
+            Ok(commit_hash.clone())
+        );
+        assert_eq!(
+            read_ref("refs/remotes/origin/main"),
+            Some(commit_hash.clone())
+        );
+        assert!(object_exists(&get_commit_tree(
+            &RepoPath::Local,
+            &commit_hash
+        )));
+        assert!(read_reflog("refs/remotes/origin/main")[0]
+            .message
+            .starts_with("fetch: storing main"));
+
+        // branch names cannot leave the refs
+        assert!(fetch_branch("origin", "../.rgit", "../../HEAD").is_err());
+        assert!(fetch_branch("origin", "../.rgit", "missing").is_err());
+        env::set_current_dir("..").unwrap();
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_fetch_from_packed_remote() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let commit_hash: String = get_head_hash();
+
+        // the remote branch is only in packed-refs
+        pack_loose_refs(true, true).unwrap();
+        assert!(!Path::new(".rgit/refs/heads/main").exists());
+
+        init_repository(&parse_init_options(&["local".to_string()])).unwrap();
+        env::set_current_dir("local").unwrap();
+        assert_eq!(
+            fetch_branch("origin", "../.rgit", "main"),
+            Ok(commit_hash.clone())
+        );
+        assert_eq!(read_ref("refs/remotes/origin/main"), Some(commit_hash));
+        env::set_current_dir("..").unwrap();
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_fetched_branch_is_the_upstream() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+
+        init_repository(&parse_init_options(&["local".to_string()])).unwrap();
+        env::set_current_dir("local").unwrap();
+        fetch_branch("origin", "../.rgit", "main").unwrap();
+        update_ref(
+            "refs/heads/main",
+            &first_commit,
+            "branch: Created from origin/main",
+        );
+        fs::write(
+            ".rgit/config",
+            "[branch \"main\"]\n    remote = origin\n    merge = refs/heads/main\n",
+        )
+        .unwrap();
+        assert_eq!(rev_parse("main@{upstream}"), Ok(first_commit.clone()));
+
+        fs::write("local.txt", "local\n").unwrap();
+        add_index("local.txt", &hash_object("local.txt"));
+        commit("Local commit", Some("John Doe"));
+        assert!(format_branches(2).contains(" [origin/main: ahead 1] Local commit\n"));
+
+        // a new remote commit is only seen once fetched
+        env::set_current_dir("..").unwrap();
+        fs::write("file.txt", "two\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("Second commit", Some("John Doe"));
+        let second_commit: String = get_head_hash();
+        env::set_current_dir("local").unwrap();
+        fetch_branch("origin", "../.rgit", "main").unwrap();
+        assert_eq!(rev_parse("main@{u}"), Ok(second_commit));
+        assert!(format_branches(2).contains(" [origin/main: ahead 1, behind 1] Local commit\n"));
+        env::set_current_dir("..").unwrap();
+
+        remove_test_repo();
+    }
 }
@@ -0,0 +1,366 @@
+use crate::commands::cat_file::read_object;
+use crate::commands::rev_parse::abbreviate_ref;
+use crate::utils::{
+    format_rfc2822_date, glob_match, list_refs, parse_signature, peel_to_commit, read_ref,
+    RepoPath, Signature,
+};
+use std::cmp::Ordering;
+
+const DEFAULT_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+
+/// Options of the `for-each-ref` command.
+pub struct ForEachRefOptions {
+    /// the fields to sort by, the last one first; `-<field>` sorts in descending order
+    pub sort: Vec<String>,
+    pub format: String,
+    /// stop after this many refs
+    pub count: Option<usize>,
+    /// only show the refs under these prefixes (e.g. `refs/heads`) or matching these globs
+    pub patterns: Vec<String>,
+}
+
+pub fn parse_for_each_ref_options(args: &[String]) -> ForEachRefOptions {
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: rgit for-each-ref [--sort=<key>]... [--format=<format>] [--count=<n>] [<pattern>...]"
+        );
+        std::process::exit(1);
+    };
+
+    let mut options: ForEachRefOptions = ForEachRefOptions {
+        sort: Vec::new(),
+        format: DEFAULT_FORMAT.to_string(),
+        count: None,
+        patterns: Vec::new(),
+    };
+    let mut index: usize = 0;
+    while index < args.len() {
+        let arg: &str = args[index].as_str();
+        // "--option value" is the same as "--option=value"
+        let (option, value): (&str, Option<String>) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value.to_string())),
+            _ if matches!(arg, "--sort" | "--format" | "--count") && index + 1 < args.len() => {
+                index += 1;
+                (arg, Some(args[index].clone()))
+            }
+            _ => (arg, None),
+        };
+        match (option, value) {
+            ("--sort", Some(key)) => options.sort.push(key),
+            ("--format", Some(format)) => options.format = format,
+            ("--count", Some(count)) => match count.parse() {
+                Ok(count) => options.count = Some(count),
+                Err(_) => usage(),
+            },
+            _ if !arg.starts_with('-') => options.patterns.push(arg.to_string()),
+            _ => usage(),
+        }
+        index += 1;
+    }
+    options
+}
+
+pub fn for_each_ref(options: &ForEachRefOptions) {
+    match format_refs(options) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Err(error) => {
+            eprintln!("fatal: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// A ref and the object it points to, from which the fields of the format are taken.
+struct RefObject {
+    ref_name: String,
+    hash: String,
+    object_type: String,
+    content: String,
+}
+
+/// Returns a line per ref matching the patterns, loose or packed, with the `%(<field>)` of
+/// the format replaced by the values of the ref.
+pub fn format_refs(options: &ForEachRefOptions) -> Result<Vec<String>, String> {
+    let mut refs: Vec<RefObject> = list_refs("refs/")
+        .into_iter()
+        .filter(|ref_name| {
+            options.patterns.is_empty()
+                || options
+                    .patterns
+                    .iter()
+                    .any(|pattern| matches_pattern(pattern, ref_name))
+        })
+        .filter_map(|ref_name| {
+            let hash: String = read_ref(&ref_name)?;
+            let (object_type, content): (String, Vec<u8>) = read_object(&RepoPath::Local, &hash);
+            Some(RefObject {
+                ref_name,
+                hash,
+                object_type,
+                content: String::from_utf8_lossy(&content).into_owned(),
+            })
+        })
+        .collect();
+
+    // the refs are sorted by name, then by each key in turn, so that the last key comes first
+    for key in &options.sort {
+        let (field, descending): (&str, bool) = match key.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (key.as_str(), false),
+        };
+        let mut keyed_refs: Vec<(String, RefObject)> = Vec::new();
+        for ref_object in refs {
+            keyed_refs.push((sort_value(&ref_object, field)?, ref_object));
+        }
+        keyed_refs.sort_by(|(a, _), (b, _)| {
+            let ordering: Ordering = match (a.parse::<i64>(), b.parse::<i64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        refs = keyed_refs
+            .into_iter()
+            .map(|(_, ref_object)| ref_object)
+            .collect();
+    }
+
+    refs.iter()
+        .take(options.count.unwrap_or(usize::MAX))
+        .map(|ref_object| expand_format(&options.format, ref_object))
+        .collect()
+}
+
+/// A pattern matches the refs under it (`refs/heads` matches `refs/heads/main` but not
+/// `refs/headsup`), or the refs it matches as a glob when it has wildcards.
+fn matches_pattern(pattern: &str, ref_name: &str) -> bool {
+    if pattern.contains(['*', '?']) {
+        return glob_match(pattern, ref_name);
+    }
+    let prefix: &str = pattern.trim_end_matches('/');
+    ref_name == prefix || ref_name.starts_with(&format!("{}/", prefix))
+}
+
+/// Dates are sorted by timestamp rather than by their formatted text.
+fn sort_value(ref_object: &RefObject, field: &str) -> Result<String, String> {
+    if field.ends_with("date") {
+        return field_value(ref_object, &format!("{}:unix", field));
+    }
+    field_value(ref_object, field)
+}
+
+/// Replaces the `%(<field>)` of a format with their values, and `%%` with `%`.
+fn expand_format(format: &str, ref_object: &RefObject) -> Result<String, String> {
+    let mut output: String = String::new();
+    let mut rest: &str = format;
+    while let Some(start) = rest.find('%') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("%%") {
+            output.push('%');
+            rest = after;
+        } else if let Some((field, after)) = rest
+            .strip_prefix("%(")
+            .and_then(|field| field.split_once(')'))
+        {
+            output.push_str(&field_value(ref_object, field)?);
+            rest = after;
+        } else {
+            output.push('%');
+            rest = &rest[1..];
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Returns the value of a field for a ref:
+///
+/// - `refname`, `refname:short`: the name of the ref, full or abbreviated.
+/// - `objectname`, `objectname:short`, `objecttype`: the object the ref points to.
+/// - `*<field>`: a field of the object an annotated tag points to (empty for other refs).
+/// - `subject`, `body`, `contents`: the first line of the message, the rest, or both.
+/// - `<role>name`, `<role>email`, `<role>date` for `author`, `committer`, `tagger` and
+///   `creator` (the tagger of a tag, the committer or the author of a commit). Dates are
+///   RFC 2822 dates, or timestamps with `:unix`.
+fn field_value(ref_object: &RefObject, field: &str) -> Result<String, String> {
+    let (name, modifier): (&str, Option<&str>) = match field.split_once(':') {
+        Some((name, modifier)) => (name, Some(modifier)),
+        None => (field, None),
+    };
+    let unknown_field =
+        || -> Result<String, String> { Err(format!("unknown field name: {}", field)) };
+
+    if let Some(peeled_field) = field.strip_prefix('*') {
+        if ref_object.object_type != "tag" || peeled_field.starts_with('*') {
+            // check the field anyway, so that typos are reported for every ref
+            return field_value(ref_object, peeled_field).map(|_| String::new());
+        }
+        let peeled_hash: String = peel_to_commit(&ref_object.hash);
+        let (object_type, content): (String, Vec<u8>) = read_object(&RepoPath::Local, &peeled_hash);
+        let peeled: RefObject = RefObject {
+            ref_name: ref_object.ref_name.clone(),
+            hash: peeled_hash,
+            object_type,
+            content: String::from_utf8_lossy(&content).into_owned(),
+        };
+        return field_value(&peeled, peeled_field);
+    }
+
+    let header = |header_name: &str| -> Option<String> {
+        ref_object
+            .content
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .find_map(|line| line.strip_prefix(&format!("{} ", header_name)))
+            .map(|value| value.to_string())
+    };
+    let message: &str = ref_object
+        .content
+        .split_once("\n\n")
+        .map(|(_, message)| message)
+        .unwrap_or("");
+
+    match (name, modifier) {
+        ("refname", None) => Ok(ref_object.ref_name.clone()),
+        ("refname", Some("short")) => Ok(abbreviate_ref(&ref_object.ref_name)),
+        ("objectname", None) => Ok(ref_object.hash.clone()),
+        ("objectname", Some("short")) => Ok(ref_object.hash[..7].to_string()),
+        ("objecttype", None) => Ok(ref_object.object_type.clone()),
+        ("subject", None) => Ok(message.lines().next().unwrap_or("").to_string()),
+        ("body", None) => Ok(message
+            .split_once('\n')
+            .map(|(_, body)| body.trim_start_matches('\n'))
+            .unwrap_or("")
+            .to_string()),
+        ("contents", None) => Ok(message.to_string()),
+        _ => {
+            let Some((role, part)) = ["name", "email", "date"]
+                .iter()
+                .find_map(|part| Some((name.strip_suffix(part)?, *part)))
+            else {
+                return unknown_field();
+            };
+            let signature_line: Option<String> = match role {
+                "author" | "committer" | "tagger" => header(role),
+                "creator" => header("tagger")
+                    .or_else(|| header("committer"))
+                    .or_else(|| header("author")),
+                _ => return unknown_field(),
+            };
+            let Some(signature_line) = signature_line else {
+                return Ok(String::new());
+            };
+            let signature: Signature = parse_signature(&signature_line);
+            match (part, modifier) {
+                ("name", None) => Ok(signature.name),
+                ("email", None) => Ok(format!("<{}>", signature.email)),
+                ("date", None) | ("date", Some("rfc2822")) => Ok(signature
+                    .date
+                    .map(|(timestamp, timezone)| format_rfc2822_date(timestamp, &timezone))
+                    .unwrap_or_default()),
+                ("date", Some("unix")) => Ok(signature
+                    .date
+                    .map(|(timestamp, _)| timestamp.to_string())
+                    .unwrap_or_default()),
+                _ => unknown_field(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::branch::create_branch;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::pack_refs::pack_loose_refs;
+    use crate::commands::update_index::add_index;
+    use crate::utils::hash_and_store;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+    use std::fs;
+
+    fn for_each_ref_lines(args: &[&str]) -> Result<Vec<String>, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        format_refs(&parse_for_each_ref_options(&args))
+    }
+
+    #[test]
+    fn test_for_each_ref_formats_sorts_and_filters() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit\n\nWith a body", Some("John Doe"));
+        let commit_hash: String = get_head_hash();
+        create_branch("topic", "HEAD").unwrap();
+
+        // two tags with different dates, the older one created last
+        for (name, timestamp) in [("v2.0", 1700000000), ("v1.0", 1600000000)] {
+            let tag_hash: String = hash_and_store(
+                "tag",
+                format!(
+                    "object {}\ntype commit\ntag {}\ntagger Jane <jane@example.com> {} +0000\n\nRelease {}\n",
+                    commit_hash, name, timestamp, name
+                )
+                .as_bytes(),
+            );
+            fs::write(format!(".rgit/refs/tags/{}", name), &tag_hash).unwrap();
+        }
+        pack_loose_refs(false, true).unwrap();
+
+        assert_eq!(
+            for_each_ref_lines(&[]).unwrap()[0],
+            format!("{} commit\trefs/heads/main", commit_hash)
+        );
+        assert_eq!(
+            for_each_ref_lines(&[
+                "--format=%(refname:short) %(objectname:short)",
+                "refs/heads"
+            ])
+            .unwrap(),
+            vec![
+                format!("main {}", &commit_hash[..7]),
+                format!("topic {}", &commit_hash[..7]),
+            ]
+        );
+
+        // tags sorted by date, newest first, with their tagged commit
+        assert_eq!(
+            for_each_ref_lines(&[
+                "--sort=-taggerdate",
+                "--format",
+                "%(refname) %(taggerdate) %(*objectname) %(subject)",
+                "refs/tags/v*",
+            ])
+            .unwrap(),
+            vec![
+                format!(
+                    "refs/tags/v2.0 Tue, 14 Nov 2023 22:13:20 +0000 {} Release v2.0",
+                    commit_hash
+                ),
+                format!(
+                    "refs/tags/v1.0 Sun, 13 Sep 2020 12:26:40 +0000 {} Release v1.0",
+                    commit_hash
+                ),
+            ]
+        );
+        assert_eq!(
+            for_each_ref_lines(&["--count=1", "--format=%(body)%%", "refs/heads/main"]).unwrap(),
+            vec!["With a body\n%"]
+        );
+        assert!(for_each_ref_lines(&["--format=%(nope)"]).is_err());
+
+        remove_test_repo();
+    }
+}
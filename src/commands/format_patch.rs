@@ -0,0 +1,335 @@
+use crate::commands::cat_file::cat_file;
+use crate::commands::diff::commit_diffs;
+use crate::commands::log::log_history;
+use crate::commands::rev_parse::commit_parents;
+use crate::diff::{format_diffs, format_stat, DiffFormat, DiffSettings, FileDiff};
+use crate::utils::{format_rfc2822_date, parse_signature, resolve_revision, RepoPath, Signature};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Maximum length of the subject part of a patch file name, like git.
+const MAX_FILE_NAME_SUBJECT: usize = 52;
+
+/// Options of the `format-patch` command.
+pub struct FormatPatchOptions {
+    /// `<since>` (commits after it up to HEAD), `<since>..<until>`, or the last commit of
+    /// the series when `max_count` is set
+    pub range: String,
+    pub max_count: Option<usize>,
+    pub output_dir: String,
+    pub stdout: bool,
+    pub settings: DiffSettings,
+}
+
+pub fn parse_format_patch_options(args: &[String]) -> FormatPatchOptions {
+    let mut options: FormatPatchOptions = FormatPatchOptions {
+        range: String::new(),
+        max_count: None,
+        output_dir: ".".to_string(),
+        stdout: false,
+        settings: DiffSettings::from_config(),
+    };
+
+    let mut index: usize = 0;
+    while index < args.len() {
+        let arg: &str = args[index].as_str();
+        match arg {
+            "--stdout" => options.stdout = true,
+            "-o" | "--output-directory" if index + 1 < args.len() => {
+                options.output_dir = args[index + 1].clone();
+                index += 1;
+            }
+            _ if arg.len() > 1
+                && arg.starts_with('-')
+                && arg[1..].chars().all(|c| c.is_ascii_digit()) =>
+            {
+                options.max_count = Some(arg[1..].parse().expect("Failed to parse count"));
+            }
+            _ if options.settings.parse_option(arg) => {}
+            _ if !arg.starts_with('-') && options.range.is_empty() => {
+                options.range = arg.to_string();
+            }
+            _ => {
+                eprintln!("Usage: rgit format-patch [-o <dir>] [--stdout] [-<n>] [<since>|<since>..<until>]");
+                std::process::exit(1);
+            }
+        }
+        index += 1;
+    }
+
+    if options.range.is_empty() {
+        if options.max_count.is_none() {
+            eprintln!(
+                "Usage: rgit format-patch [-o <dir>] [--stdout] [-<n>] [<since>|<since>..<until>]"
+            );
+            std::process::exit(1);
+        }
+        options.range = "HEAD".to_string();
+    }
+
+    options
+}
+
+pub fn format_patch(options: &FormatPatchOptions) {
+    let commits: Vec<String> = select_commits(&options.range, options.max_count);
+    let total: usize = commits.len();
+
+    if !options.stdout {
+        fs::create_dir_all(&options.output_dir).expect("Failed to create output directory");
+    }
+
+    for (number, commit_hash) in commits.iter().enumerate() {
+        let mail: String = format_mail(commit_hash, number + 1, total, &options.settings);
+        if options.stdout {
+            print!("{}", mail);
+            continue;
+        }
+
+        let (subject, _): (String, String) =
+            split_message(&commit_message(&cat_file(&RepoPath::Local, commit_hash)));
+        let patch_path: PathBuf =
+            Path::new(&options.output_dir).join(patch_file_name(number + 1, &subject));
+        fs::write(&patch_path, mail).expect("Failed to write patch file");
+        println!("{}", patch_path.display());
+    }
+}
+
+/// Returns the commits of a range, oldest first: the ones reachable from `<until>` through
+/// all the parents and not from `<since>`. Merges are left out, like git, since they have no
+/// single patch to send.
+pub fn select_commits(range: &str, max_count: Option<usize>) -> Vec<String> {
+    let (since, until): (Option<&str>, &str) = match range.split_once("..") {
+        Some((since, until)) => (Some(since), until),
+        None if max_count.is_some() => (None, range),
+        None => (Some(range), "HEAD"),
+    };
+    let excluded: HashSet<String> = match since {
+        Some(since) => log_history(&resolve_revision(or_head(since)))
+            .into_iter()
+            .collect(),
+        None => HashSet::new(),
+    };
+
+    let mut commits: Vec<String> = log_history(&resolve_revision(or_head(until)))
+        .into_iter()
+        .filter(|commit| !excluded.contains(commit) && commit_parents(commit).len() <= 1)
+        .take(max_count.unwrap_or(usize::MAX))
+        .collect();
+
+    commits.reverse();
+    commits
+}
+
+/// An empty side of a range means HEAD, like in `origin..`.
+fn or_head(revision: &str) -> &str {
+    if revision.is_empty() {
+        "HEAD"
+    } else {
+        revision
+    }
+}
+
+/// Formats a commit as a mail in mbox format: headers built from the commit, the message,
+/// a diffstat and the patch.
+pub fn format_mail(
+    commit_hash: &str,
+    number: usize,
+    total: usize,
+    settings: &DiffSettings,
+) -> String {
+    let commit_content: String = cat_file(&RepoPath::Local, commit_hash);
+    let author: Signature = parse_signature(
+        commit_content
+            .lines()
+            .find(|line| line.starts_with("author "))
+            .map(|line| line.trim_start_matches("author "))
+            .unwrap_or("unknown author"),
+    );
+    let (subject, body): (String, String) = split_message(&commit_message(&commit_content));
+
+    let prefix: String = if total > 1 {
+        format!("[PATCH {}/{}]", number, total)
+    } else {
+        "[PATCH]".to_string()
+    };
+    let body: String = if body.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", body)
+    };
+
+    let file_diffs: Vec<FileDiff> = commit_diffs(commit_hash, settings);
+    format!(
+        "From {} Mon Sep 17 00:00:00 2001\nFrom: {}\nDate: {}\nSubject: {} {}\n\n{}---\n{}\n{}-- \nrgit {}\n\n",
+        commit_hash,
+        author.identity(),
+        format_author_date(&author),
+        prefix,
+        subject,
+        body,
+        format_stat(&file_diffs, settings.algorithm),
+        format_diffs(&file_diffs, DiffFormat::Patch, settings),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+fn commit_message(commit_content: &str) -> String {
+    commit_content
+        .lines()
+        .skip_while(|line| !line.is_empty())
+        .skip(1)
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Splits a commit message into its subject (the first paragraph, on one line) and its body.
+pub fn split_message(message: &str) -> (String, String) {
+    let message: &str = message.trim();
+    let (subject, body): (&str, &str) = message.split_once("\n\n").unwrap_or((message, ""));
+    let subject: String = subject
+        .lines()
+        .map(|line| line.trim())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    (subject, body.trim().to_string())
+}
+
+/// Formats the date of an author for the `Date:` header. Authors recorded without a date
+/// are dated now.
+fn format_author_date(author: &Signature) -> String {
+    match &author.date {
+        Some((timestamp, timezone)) => format_rfc2822_date(*timestamp, timezone),
+        None => {
+            let now: i64 = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Failed to read the system time")
+                .as_secs() as i64;
+            format_rfc2822_date(now, "+0000")
+        }
+    }
+}
+
+/// Builds the name of a patch file, e.g. `0001-Fix-the-parser.patch`.
+fn patch_file_name(number: usize, subject: &str) -> String {
+    let mut slug: String = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= MAX_FILE_NAME_SUBJECT {
+            break;
+        }
+    }
+    let slug: &str = slug.trim_end_matches(['-', '.']);
+    format!("{:04}-{}.patch", number, slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{commit_files, remove_test_repo, setup_test_repo};
+
+    #[test]
+    fn test_format_mail_for_commit_range() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\ntwo\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit(
+            "Initial commit",
+            Some("John Doe <john@example.com> 0 +0000"),
+        );
+        let first_commit: String = get_head_hash();
+
+        fs::write("file.txt", "one\n2\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit(
+            "Change the second line\n\nIt reads better.",
+            Some("Jane Doe <jane@example.com> 1681396200 +0200"),
+        );
+        let second_commit: String = get_head_hash();
+
+        assert_eq!(
+            select_commits(&first_commit, None),
+            vec![second_commit.clone()]
+        );
+        assert_eq!(
+            select_commits("HEAD", Some(5)),
+            vec![first_commit.clone(), second_commit.clone()]
+        );
+
+        let mail: String = format_mail(&second_commit, 2, 2, &DiffSettings::from_config());
+        assert_eq!(
+            mail,
+            format!(
+                "From {} Mon Sep 17 00:00:00 2001
+From: Jane Doe <jane@example.com>
+Date: Thu, 13 Apr 2023 16:30:00 +0200
+Subject: [PATCH 2/2] Change the second line
+
+It reads better.
+---
+ file.txt | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/file.txt b/file.txt
+index 814f4a4..99b356d 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++2
+--\x20
+rgit {}
+
+",
+                second_commit,
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        assert_eq!(
+            patch_file_name(1, "Fix the parser: don't crash!"),
+            "0001-Fix-the-parser-don-t-crash.patch"
+        );
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_select_commits_goes_through_merges_and_skips_them() {
+        setup_test_repo();
+
+        let base: String = commit_files("base", &[("file.txt", "a\n")], &[]);
+        let left: String = commit_files("left", &[("file.txt", "left\n")], &[&base]);
+        let right: String = commit_files("right", &[("file.txt", "right\n")], &[&base]);
+        let merge: String =
+            commit_files("Merge right", &[("file.txt", "both\n")], &[&left, &right]);
+        let after: String = commit_files("after", &[("file.txt", "after\n")], &[&merge]);
+
+        // both branches are exported, without the merge itself
+        let mut commits: Vec<String> = select_commits(&format!("{}..{}", base, after), None);
+        assert_eq!(commits.pop(), Some(after.clone()));
+        commits.sort();
+        let mut expected: Vec<String> = vec![left.clone(), right];
+        expected.sort();
+        assert_eq!(commits, expected);
+
+        // commits reachable from <since> through any parent are left out
+        assert_eq!(
+            select_commits(&format!("{}..{}", left, after), None).len(),
+            2
+        );
+
+        remove_test_repo();
+    }
+}
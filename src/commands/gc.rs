@@ -0,0 +1,193 @@
+use crate::commands::cat_file::read_object;
+use crate::commands::pack_refs::pack_loose_refs;
+use crate::commands::reflog::{logged_refs, parse_expire_time, read_reflog, NULL_HASH};
+use crate::commands::update_index::read_index;
+use crate::utils::{list_refs, object_exists, parse_tree_entries, read_ref, RepoPath};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Unreachable objects younger than this are kept by default, as they may belong to a
+/// command still running.
+const DEFAULT_PRUNE_EXPIRE: &str = "2.weeks.ago";
+
+pub fn gc(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit gc [--prune=<time>|--no-prune]");
+        std::process::exit(1);
+    };
+
+    let mut prune: Option<String> = Some(DEFAULT_PRUNE_EXPIRE.to_string());
+    for arg in args {
+        match arg.as_str() {
+            "--no-prune" => prune = None,
+            "--prune" => prune = Some(DEFAULT_PRUNE_EXPIRE.to_string()),
+            _ if arg.starts_with("--prune=") => {
+                prune = Some(arg["--prune=".len()..].to_string());
+            }
+            _ => usage(),
+        }
+    }
+    let cutoff: Option<u64> = prune.map(|time| {
+        parse_expire_time(&time).unwrap_or_else(|| {
+            eprintln!("fatal: malformed prune time '{}'", time);
+            std::process::exit(1);
+        })
+    });
+
+    if let Err(error) = pack_loose_refs(true, true) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    if let Some(cutoff) = cutoff {
+        let removed: usize = prune_objects(cutoff);
+        println!("Removed {} unreachable objects", removed);
+    }
+}
+
+/// Returns the objects reachable from the refs, HEAD, the old and new values of every reflog
+/// entry, and the index. Objects missing from the database are skipped.
+pub fn reachable_objects() -> HashSet<String> {
+    let mut to_visit: Vec<String> = Vec::new();
+    to_visit.extend(read_ref("HEAD"));
+    for ref_name in list_refs("refs/") {
+        to_visit.extend(read_ref(&ref_name));
+    }
+    for ref_name in logged_refs() {
+        for entry in read_reflog(&ref_name) {
+            to_visit.push(entry.old);
+            to_visit.push(entry.new);
+        }
+    }
+    to_visit.extend(read_index().into_values());
+
+    let mut reachable: HashSet<String> = HashSet::new();
+    while let Some(hash) = to_visit.pop() {
+        if hash == NULL_HASH || reachable.contains(&hash) || !object_exists(&hash) {
+            continue;
+        }
+        reachable.insert(hash.clone());
+
+        // commits, tags and trees point to other objects
+        let (object_type, content): (String, Vec<u8>) = read_object(&RepoPath::Local, &hash);
+        let content: String = String::from_utf8_lossy(&content).into_owned();
+        match object_type.as_str() {
+            "commit" | "tag" => {
+                let header_lines = content.lines().take_while(|line| !line.is_empty());
+                for line in header_lines {
+                    if let Some((name, value)) = line.split_once(' ') {
+                        if matches!(name, "tree" | "parent" | "object") {
+                            to_visit.push(value.trim().to_string());
+                        }
+                    }
+                }
+            }
+            "tree" => {
+                // a tree with unsafe names is still kept, it is only never checked out
+                for entry in parse_tree_entries(&content).unwrap_or_default() {
+                    to_visit.push(entry.hash);
+                }
+            }
+            _ => {}
+        }
+    }
+    reachable
+}
+
+/// Removes the loose objects that are not reachable (see `reachable_objects`) and were last
+/// written before `cutoff`, in seconds since the epoch. Returns the number of objects removed.
+pub fn prune_objects(cutoff: u64) -> usize {
+    let reachable: HashSet<String> = reachable_objects();
+    let objects_dir: PathBuf = Path::new(".rgit").join("objects");
+    let Ok(fanout_dirs) = fs::read_dir(&objects_dir) else {
+        return 0;
+    };
+
+    let mut removed: usize = 0;
+    for fanout_dir in fanout_dirs.filter_map(|entry| entry.ok()) {
+        let dir_name: String = fanout_dir.file_name().to_string_lossy().into_owned();
+        if dir_name.len() != 2 || !dir_name.chars().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        let Ok(object_files) = fs::read_dir(fanout_dir.path()) else {
+            continue;
+        };
+
+        for object_file in object_files.filter_map(|entry| entry.ok()) {
+            let hash: String = format!("{}{}", dir_name, object_file.file_name().to_string_lossy());
+            if reachable.contains(&hash) {
+                continue;
+            }
+            let modified: u64 = object_file
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+                .unwrap_or(u64::MAX);
+            if cutoff == u64::MAX || modified < cutoff {
+                fs::remove_file(object_file.path()).expect("Failed to remove object");
+                removed += 1;
+            }
+        }
+
+        // the directory is only removed once empty
+        let _ = fs::remove_dir(fanout_dir.path());
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::reflog::expire_reflog;
+    use crate::commands::update_index::add_index;
+    use crate::commands::update_ref::update_ref;
+    use crate::utils::hash_and_store;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+
+    #[test]
+    fn test_prune_keeps_objects_reachable_from_reflogs() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+        fs::write("file.txt", "two\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("Second commit", Some("John Doe"));
+        let second_commit: String = get_head_hash();
+        let dangling_blob: String = hash_and_store("blob", b"dangling\n");
+
+        // the second commit is only reachable from the reflogs once the branch moves back
+        update_ref("refs/heads/main", &first_commit, "reset: moving to HEAD~1");
+        let staged_blob: String = hash_and_store("blob", b"staged\n");
+        add_index("staged.txt", &staged_blob);
+
+        // nothing is old enough to be pruned by default, and "never" keeps everything
+        assert_eq!(prune_objects(parse_expire_time("2.weeks.ago").unwrap()), 0);
+        assert_eq!(prune_objects(parse_expire_time("never").unwrap()), 0);
+
+        assert_eq!(prune_objects(parse_expire_time("now").unwrap()), 1);
+        assert!(!object_exists(&dangling_blob));
+        assert!(object_exists(&second_commit));
+        assert!(object_exists(&staged_blob));
+
+        // once the reflogs forget it, the second commit goes with its tree, but not with its
+        // blob, which is still in the index
+        for ref_name in logged_refs() {
+            expire_reflog(&ref_name, u64::MAX);
+        }
+        assert_eq!(prune_objects(u64::MAX), 2);
+        assert!(!object_exists(&second_commit));
+        assert!(object_exists(&first_commit));
+        assert!(object_exists(&staged_blob));
+
+        remove_test_repo();
+    }
+}
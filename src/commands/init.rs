@@ -1,33 +1,168 @@
+use crate::config::get_config;
+use crate::utils::verify_ref_name;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn init() {
-    let rgit_path: &Path = Path::new(".rgit");
-    let objects_path: PathBuf = rgit_path.join("objects");
-    let refs_path: PathBuf = rgit_path.join("refs");
+/// The branch HEAD points to in a new repository, unless `init.defaultBranch` is set.
+const DEFAULT_BRANCH: &str = "main";
 
-    if rgit_path.exists() {
-        eprintln!("Error: .rgit already exists.");
+/// Options of the `init` command.
+pub struct InitOptions {
+    /// the directory to create the repository in, the current directory by default
+    pub directory: Option<String>,
+    /// create the repository directly in the directory, without worktree nor `.rgit`
+    pub bare: bool,
+    pub initial_branch: Option<String>,
+    /// a directory whose files are copied into the new repository
+    pub template: Option<String>,
+}
+
+pub fn parse_init_options(args: &[String]) -> InitOptions {
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: rgit init [--bare] [-b|--initial-branch=<name>] [--template=<dir>] [<directory>]"
+        );
         std::process::exit(1);
+    };
+
+    let mut options: InitOptions = InitOptions {
+        directory: None,
+        bare: false,
+        initial_branch: None,
+        template: None,
+    };
+    let mut index: usize = 0;
+    while index < args.len() {
+        let arg: &str = args[index].as_str();
+        match arg {
+            "--bare" => options.bare = true,
+            "-b" | "--initial-branch" | "--template" if index + 1 < args.len() => {
+                let value: String = args[index + 1].clone();
+                if arg == "--template" {
+                    options.template = Some(value);
+                } else {
+                    options.initial_branch = Some(value);
+                }
+                index += 1;
+            }
+            _ if arg.starts_with("--initial-branch=") => {
+                options.initial_branch = Some(arg["--initial-branch=".len()..].to_string());
+            }
+            _ if arg.starts_with("--template=") => {
+                options.template = Some(arg["--template=".len()..].to_string());
+            }
+            _ if !arg.starts_with('-') && options.directory.is_none() => {
+                options.directory = Some(arg.to_string());
+            }
+            _ => usage(),
+        }
+        index += 1;
     }
+    options
+}
+
+pub fn init(options: &InitOptions) {
+    if let Err(error) = init_repository(options) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
 
-    fs::create_dir(rgit_path).expect("Failed to create .rgit directory");
-    fs::create_dir(&objects_path).expect("Failed to create .rgit/objects directory");
-    fs::create_dir(&refs_path).expect("Failed to create .rgit/refs directory");
+/// Creates a repository: `<directory>/.rgit`, or `<directory>` itself when bare (e.g. a push
+/// target). HEAD points to the initial branch (`--initial-branch`, else `init.defaultBranch`,
+/// else `main`), which is created by the first commit.
+///
+/// Running it again on an existing repository is safe: the missing directories and template
+/// files are added, and nothing else is touched. Returns the path of the repository.
+pub fn init_repository(options: &InitOptions) -> Result<PathBuf, String> {
+    let directory: &Path = Path::new(options.directory.as_deref().unwrap_or("."));
+    let rgit_path: PathBuf = if options.bare {
+        directory.to_path_buf()
+    } else {
+        directory.join(".rgit")
+    };
+    let reinitialized: bool = rgit_path.join("HEAD").is_file();
 
-    let index_path: PathBuf = rgit_path.join("index");
-    let head_path: PathBuf = rgit_path.join("HEAD");
-    fs::write(index_path, "").expect("Failed to write index file");
-    fs::write(head_path, "").expect("Failed to write HEAD file");
+    let branch: String = options
+        .initial_branch
+        .clone()
+        .or_else(|| get_config("init.defaultBranch"))
+        .unwrap_or(DEFAULT_BRANCH.to_string());
+    verify_ref_name(&branch)
+        .map_err(|_| format!("fatal: invalid initial branch name: '{}'", branch))?;
+
+    for subdirectory in ["objects", "refs/heads", "refs/tags"] {
+        fs::create_dir_all(rgit_path.join(subdirectory))
+            .map_err(|error| format!("fatal: cannot create {}: {}", rgit_path.display(), error))?;
+    }
+
+    // existing files are kept, so that HEAD, the index and the config survive
+    let write_new_file = |name: &str, content: &str| {
+        let path: PathBuf = rgit_path.join(name);
+        if !path.exists() {
+            fs::write(&path, content).expect("Failed to write repository file");
+        }
+    };
+    write_new_file("HEAD", &format!("ref: refs/heads/{}", branch));
+    write_new_file("config", &format!("[core]\n    bare = {}\n", options.bare));
+    if !options.bare {
+        write_new_file("index", "");
+    }
+
+    let template: Option<String> = options
+        .template
+        .clone()
+        .or_else(|| get_config("init.templateDir"));
+    match template {
+        Some(template) => copy_template(Path::new(&template), &rgit_path)?,
+        None => {
+            fs::create_dir_all(rgit_path.join("hooks")).expect("Failed to create hooks directory");
+            write_new_file(
+                "description",
+                "Unnamed repository; edit this file 'description' to name the repository.\n",
+            );
+        }
+    }
 
     println!(
-        "Initialized empty rgit repository in {}",
+        "{} {}rgit repository in {}",
+        if reinitialized {
+            "Reinitialized existing"
+        } else {
+            "Initialized empty"
+        },
+        if options.bare { "bare " } else { "" },
         rgit_path.display()
     );
+    Ok(rgit_path)
+}
+
+/// Copies the files of a template directory (hooks, description...) into a repository,
+/// keeping the files that already exist there.
+fn copy_template(template: &Path, rgit_path: &Path) -> Result<(), String> {
+    let dir_entries: fs::ReadDir = fs::read_dir(template).map_err(|error| {
+        format!(
+            "fatal: cannot read template directory {}: {}",
+            template.display(),
+            error
+        )
+    })?;
+    for dir_entry in dir_entries {
+        let dir_entry: fs::DirEntry = dir_entry.expect("Failed to read directory entry");
+        let target: PathBuf = rgit_path.join(dir_entry.file_name());
+        if dir_entry.path().is_dir() {
+            fs::create_dir_all(&target).expect("Failed to create template directory");
+            copy_template(&dir_entry.path(), &target)?;
+        } else if !target.exists() {
+            fs::copy(dir_entry.path(), &target).expect("Failed to copy template file");
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::utils::tests::*;
     use std::path::Path;
 
@@ -58,4 +193,73 @@ mod tests {
 
         remove_test_repo();
     }
+
+    #[test]
+    fn test_init_bare_branch_template_and_reinit() {
+        setup_test_repo();
+
+        fs::create_dir_all("template/hooks").unwrap();
+        fs::write("template/hooks/pre-commit", "#!/bin/sh\n").unwrap();
+        fs::write("template/description", "My project\n").unwrap();
+        let options: InitOptions = InitOptions {
+            directory: Some("project".to_string()),
+            bare: false,
+            initial_branch: Some("trunk".to_string()),
+            template: Some("template".to_string()),
+        };
+        assert_eq!(
+            init_repository(&options),
+            Ok(PathBuf::from("project/.rgit"))
+        );
+        assert_eq!(
+            fs::read_to_string("project/.rgit/HEAD").unwrap(),
+            "ref: refs/heads/trunk"
+        );
+        assert!(Path::new("project/.rgit/hooks/pre-commit").is_file());
+
+        // re-running keeps HEAD, the index and the edited files
+        fs::write("project/.rgit/index", "file.txt 1234\n").unwrap();
+        fs::write("project/.rgit/description", "Edited\n").unwrap();
+        let options: InitOptions = InitOptions {
+            initial_branch: None,
+            ..options
+        };
+        init_repository(&options).unwrap();
+        assert_eq!(
+            fs::read_to_string("project/.rgit/HEAD").unwrap(),
+            "ref: refs/heads/trunk"
+        );
+        assert_eq!(
+            fs::read_to_string("project/.rgit/index").unwrap(),
+            "file.txt 1234\n"
+        );
+        assert_eq!(
+            fs::read_to_string("project/.rgit/description").unwrap(),
+            "Edited\n"
+        );
+
+        // a bare repository has no worktree files
+        let options: InitOptions = InitOptions {
+            directory: Some("remote.rgit".to_string()),
+            bare: true,
+            initial_branch: None,
+            template: None,
+        };
+        init_repository(&options).unwrap();
+        assert!(Path::new("remote.rgit/refs/heads").is_dir());
+        assert!(!Path::new("remote.rgit/index").exists());
+        assert!(fs::read_to_string("remote.rgit/config")
+            .unwrap()
+            .contains("bare = true"));
+
+        let options: InitOptions = InitOptions {
+            directory: Some("bad".to_string()),
+            bare: false,
+            initial_branch: Some("../escape".to_string()),
+            template: None,
+        };
+        assert!(init_repository(&options).is_err());
+
+        remove_test_repo();
+    }
 }
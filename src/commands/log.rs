@@ -1,59 +1,352 @@
-use crate::commands::cat_file::cat_file;
-use crate::commands::get_head_hash::*;
-use crate::utils::RepoPath;
-use std::fs;
-use std::path::Path;
+use crate::commands::cat_file::{cat_file, read_object};
+use crate::commands::rev_parse::commit_parents;
+use crate::diff::{diff_snapshots, ChangeKind, DiffSettings, FileDiff, DEFAULT_RENAME_THRESHOLD};
+use crate::utils::{
+    format_rfc2822_date, get_commit_tree, parse_signature, read_tree, resolve_revision, RepoPath,
+    Signature,
+};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
 
-pub fn log(target: &str) {
+pub fn log(target: &str, follow_path: Option<&str>) {
     // determine the commit hash to start from
-    let commit_hash: String = if target == "HEAD" {
-        // if target is HEAD, use get_head_hash to retrieve the current commit hash
-        get_head_hash()
-    } else {
-        // check if the target is a branch
-        let branch_ref_path: String = format!(".rgit/refs/{}", target);
-        if Path::new(&branch_ref_path).exists() {
-            // read the commit hash from the branch reference file
-            fs::read_to_string(&branch_ref_path)
-                .expect("Failed to read branch reference")
-                .trim()
-                .to_string()
-        } else {
-            // assume the target is a commit hash
-            target.to_string()
-        }
+    let commit_hash: String = resolve_revision(target);
+
+    // only show the commits that changed the followed file
+    let commits: Vec<String> = match follow_path {
+        Some(path) => follow_history(&commit_hash, path),
+        None => log_history(&commit_hash),
     };
+    for commit in commits {
+        print_commit(&commit, &cat_file(&RepoPath::Local, &commit));
+    }
+}
 
-    // start from the given commit
-    let mut current_commit: String = commit_hash;
+/// Returns the commits reachable from `commit_hash` through all the parents of merges, each
+/// once, newest first like `git log`: by commit date, in the order they were reached for
+/// commits with the same date (or recorded without one).
+pub fn log_history(commit_hash: &str) -> Vec<String> {
+    let mut history: Vec<String> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    // the commits to show next, the most recent on top
+    let mut pending: BinaryHeap<(i64, Reverse<usize>, String)> = BinaryHeap::new();
+    let mut reached: usize = 0;
 
-    // traverse all commits until there is no more parent
-    while !current_commit.is_empty() {
-        // read the content of the current commit
-        let commit_content: String = cat_file(&RepoPath::Local, &current_commit);
+    if !commit_hash.is_empty() {
+        seen.insert(commit_hash.to_string());
+        pending.push((
+            commit_date(commit_hash),
+            Reverse(reached),
+            commit_hash.to_string(),
+        ));
+    }
+    while let Some((_, _, current_commit)) = pending.pop() {
+        for parent in commit_parents(&current_commit) {
+            if seen.insert(parent.clone()) {
+                reached += 1;
+                pending.push((commit_date(&parent), Reverse(reached), parent));
+            }
+        }
+        history.push(current_commit);
+    }
+    history
+}
+
+/// Returns the timestamp of the committer of a commit, else of its author, or 0 for commits
+/// recorded without a date.
+pub fn commit_date(commit_hash: &str) -> i64 {
+    let commit_content: String = cat_file(&RepoPath::Local, commit_hash);
+    let signature_date = |role: &str| -> Option<i64> {
+        let line: &str = commit_content
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .find_map(|line| line.strip_prefix(&format!("{} ", role)))?;
+        parse_signature(line).date.map(|(timestamp, _)| timestamp)
+    };
+    signature_date("committer")
+        .or_else(|| signature_date("author"))
+        .unwrap_or(0)
+}
+
+fn print_commit(commit_hash: &str, commit_content: &str) {
+    println!("{}", format_commit(commit_hash, commit_content));
+}
 
-        let author: &str = commit_content
+/// Formats the header of a commit as shown by `log`. Commits recorded without a date (by
+/// older versions) have no `Date:` line.
+pub fn format_commit(commit_hash: &str, commit_content: &str) -> String {
+    let author: Signature = parse_signature(
+        commit_content
             .lines()
             .find(|line| line.starts_with("author "))
             .map(|line| line.trim_start_matches("author "))
-            .unwrap_or("unknown author");
+            .unwrap_or("unknown author"),
+    );
+    let date_line: String = match &author.date {
+        Some((timestamp, timezone)) => {
+            format!("Date: {}\n", format_rfc2822_date(*timestamp, timezone))
+        }
+        None => String::new(),
+    };
 
-        let message: String = commit_content
-            .lines()
-            .skip_while(|line| !line.is_empty())
-            .skip(1)
-            .collect::<Vec<&str>>()
-            .join("\n");
+    let message: String = commit_content
+        .lines()
+        .skip_while(|line| !line.is_empty())
+        .skip(1)
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    // a merge lists its parents
+    let parents: Vec<&str> = commit_content
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.strip_prefix("parent "))
+        .map(|parent| &parent[..7.min(parent.len())])
+        .collect();
+    let merge_line: String = if parents.len() > 1 {
+        format!("Merge: {}\n", parents.join(" "))
+    } else {
+        String::new()
+    };
 
-        println!("Commit: {}", current_commit);
-        println!("Author: {}", author);
-        println!("Message: {}\n", message);
+    format!(
+        "Commit: {}\n{}Author: {}\n{}Message: {}\n",
+        commit_hash,
+        merge_line,
+        author.identity(),
+        date_line,
+        message
+    )
+}
 
-        // move to the parent commit (if present)
-        current_commit = commit_content
-            .lines()
-            .find(|line| line.starts_with("parent "))
-            .map(|line| line.split_whitespace().nth(1).unwrap().to_string())
-            .unwrap_or(String::new());
+/// Returns the commits of the history starting at `commit_hash` that changed `path`,
+/// following the file through renames, newest first like `log_history`. A merge that kept
+/// the file of one of its parents is not shown, and only that parent is followed; otherwise
+/// every parent the file comes from is followed.
+pub fn follow_history(commit_hash: &str, path: &str) -> Vec<String> {
+    let mut settings: DiffSettings = DiffSettings::from_config();
+    settings.renames = Some(settings.renames.unwrap_or(DEFAULT_RENAME_THRESHOLD));
+
+    let mut history: Vec<String> = Vec::new();
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    // the commits to look at next with the path of the file in each, the most recent on top
+    let mut pending: BinaryHeap<(i64, Reverse<usize>, String, String)> = BinaryHeap::new();
+    let mut reached: usize = 0;
+
+    if !commit_hash.is_empty() {
+        seen.insert((commit_hash.to_string(), path.to_string()));
+        pending.push((
+            commit_date(commit_hash),
+            Reverse(reached),
+            commit_hash.to_string(),
+            path.to_string(),
+        ));
+    }
+    while let Some((_, _, current_commit, current_path)) = pending.pop() {
+        let tree: BTreeMap<String, String> = read_tree(
+            &RepoPath::Local,
+            &get_commit_tree(&RepoPath::Local, &current_commit),
+        );
+
+        // the file does not exist yet at this point of the history
+        let blob_hash: &String = match tree.get(&current_path) {
+            Some(blob_hash) => blob_hash,
+            None => continue,
+        };
+
+        // the parents to follow, with the path of the file in each
+        let mut parents: Vec<(String, String)> = Vec::new();
+        let mut unchanged: bool = false;
+        for parent in commit_parents(&current_commit) {
+            let parent_tree: BTreeMap<String, String> = read_tree(
+                &RepoPath::Local,
+                &get_commit_tree(&RepoPath::Local, &parent),
+            );
+            match parent_tree.get(&current_path) {
+                Some(parent_blob_hash) if parent_blob_hash == blob_hash => {
+                    parents = vec![(parent, current_path.clone())];
+                    unchanged = true;
+                    break;
+                }
+                Some(_) => parents.push((parent, current_path.clone())),
+                // the file appeared in this commit: look for the deleted file it comes from
+                None => {
+                    if let Some(old_path) =
+                        find_rename_source(&settings, &parent_tree, &tree, &current_path)
+                    {
+                        parents.push((parent, old_path));
+                    }
+                }
+            }
+        }
+        if !unchanged {
+            history.push(current_commit);
+        }
+
+        for (parent, parent_path) in parents {
+            if seen.insert((parent.clone(), parent_path.clone())) {
+                reached += 1;
+                pending.push((commit_date(&parent), Reverse(reached), parent, parent_path));
+            }
+        }
+    }
+
+    history
+}
+
+/// Returns the path of the file of `parent_tree`, deleted in `tree`, that `path` was renamed
+/// from, if any.
+pub fn find_rename_source(
+    settings: &DiffSettings,
+    parent_tree: &BTreeMap<String, String>,
+    tree: &BTreeMap<String, String>,
+    path: &str,
+) -> Option<String> {
+    let read_stored =
+        |_path: &str, hash: &str| -> Vec<u8> { read_object(&RepoPath::Local, hash).1 };
+
+    let deleted: BTreeMap<String, String> = parent_tree
+        .iter()
+        .filter(|(parent_path, _)| !tree.contains_key(*parent_path))
+        .map(|(parent_path, hash)| (parent_path.clone(), hash.clone()))
+        .collect();
+    let added: BTreeMap<String, String> =
+        BTreeMap::from([(path.to_string(), tree.get(path)?.clone())]);
+    let file_diffs: Vec<FileDiff> =
+        settings.detect_renames(diff_snapshots(&deleted, &added, &read_stored, &read_stored));
+
+    file_diffs
+        .into_iter()
+        .find(|file_diff| matches!(file_diff.kind, ChangeKind::Renamed(_)))
+        .map(|rename| rename.old_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::{add_index, remove_index};
+    use crate::commands::write_tree::write_tree;
+    use crate::utils::collect_objects;
+    use crate::utils::hash_and_store;
+    use crate::utils::tests::{commit_files, remove_test_repo, setup_test_repo};
+    use std::fs;
+
+    #[test]
+    fn test_follow_history_tracks_renames() {
+        setup_test_repo();
+
+        fs::write("old.txt", "line 1\nline 2\nline 3\nline 4\n").unwrap();
+        add_index("old.txt", &hash_object("old.txt"));
+        commit("Add old.txt", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+
+        fs::write("other.txt", "unrelated\n").unwrap();
+        add_index("other.txt", &hash_object("other.txt"));
+        commit("Add other.txt", Some("John Doe"));
+
+        // rename old.txt to new.txt with a small change
+        fs::rename("old.txt", "new.txt").unwrap();
+        fs::write("new.txt", "line 1\nline 2\nline 3\nline 4 changed\n").unwrap();
+        remove_index("old.txt");
+        add_index("new.txt", &hash_object("new.txt"));
+        commit("Rename old.txt to new.txt", Some("John Doe"));
+        let rename_commit: String = get_head_hash();
+
+        let history: Vec<String> = follow_history(&rename_commit, "new.txt");
+        assert_eq!(
+            history,
+            vec![rename_commit, first_commit],
+            "history should go through the rename and skip unrelated commits."
+        );
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_log_history_follows_all_parents_of_merges() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        let tree: String = write_tree();
+        let commit_at = |message: &str, parents: &[&str], timestamp: i64| -> String {
+            let parent_lines: String = parents
+                .iter()
+                .map(|parent| format!("parent {}\n", parent))
+                .collect();
+            let commit_content: String = format!(
+                "tree {}\n{}author John Doe <john@example.com> 1000 +0000\ncommitter John Doe <john@example.com> {} +0000\n\n{}\n",
+                tree, parent_lines, timestamp, message
+            );
+            hash_and_store("commit", commit_content.as_bytes())
+        };
+
+        // base - left and base - right, merged with both as parents
+        let base: String = commit_at("base", &[], 1000);
+        let left: String = commit_at("left", &[&base], 2000);
+        let right: String = commit_at("right", &[&base], 3000);
+        let merge: String = commit_at("Merge right", &[&left, &right], 4000);
+
+        let content: String = cat_file(&RepoPath::Local, &merge);
+        assert!(content.contains(&format!("parent {}\nparent {}\n", left, right)));
+        assert!(format_commit(&merge, &content).starts_with(&format!(
+            "Commit: {}\nMerge: {} {}\n",
+            merge,
+            &left[..7],
+            &right[..7]
+        )));
+
+        // each commit is listed once, the most recently committed first
+        assert_eq!(
+            log_history(&merge),
+            vec![merge.clone(), right.clone(), left.clone(), base.clone()]
+        );
+
+        // the objects of both branches are sent by push and fetch
+        let objects: HashSet<String> = collect_objects(&RepoPath::Local, &merge);
+        for commit_hash in [&merge, &left, &right, &base] {
+            assert!(objects.contains(commit_hash));
+        }
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_follow_history_goes_through_merges() {
+        setup_test_repo();
+
+        let base: String = commit_files(
+            "base",
+            &[("file.txt", "a\nb\nc\n"), ("other.txt", "other\n")],
+            &[],
+        );
+        let left: String = commit_files(
+            "left",
+            &[("file.txt", "A\nb\nc\n"), ("other.txt", "other\n")],
+            &[&base],
+        );
+        let right: String = commit_files(
+            "right",
+            &[("file.txt", "a\nb\nC\n"), ("other.txt", "OTHER\n")],
+            &[&base],
+        );
+        let merge: String = commit_files(
+            "Merge right",
+            &[("file.txt", "A\nb\nC\n"), ("other.txt", "OTHER\n")],
+            &[&left, &right],
+        );
+
+        // the merge changed file.txt compared to both parents, which are both followed
+        assert_eq!(
+            follow_history(&merge, "file.txt"),
+            vec![merge.clone(), left.clone(), right.clone(), base.clone()]
+        );
+        // the merge kept other.txt from the right parent: only that side is followed
+        assert_eq!(follow_history(&merge, "other.txt"), vec![right, base]);
+
+        remove_test_repo();
     }
 }
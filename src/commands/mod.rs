@@ -1,14 +1,32 @@
+pub mod am;
+pub mod apply;
+pub mod blame;
+pub mod branch;
 pub mod cat_file;
+pub mod check_ref_format;
 pub mod checkout;
 pub mod commit;
 pub mod commit_tree;
+pub mod diff;
 pub mod fetch;
+pub mod for_each_ref;
+pub mod format_patch;
+pub mod gc;
 pub mod get_head_hash;
 pub mod hash_object;
 pub mod init;
 pub mod log;
+pub mod pack_refs;
 pub mod push;
+pub mod reflog;
+pub mod reset;
+pub mod restore;
+pub mod rev_parse;
+pub mod show;
+pub mod show_ref;
+pub mod stash;
 pub mod symbolic_ref;
+pub mod tag;
 pub mod update_index;
 pub mod update_ref;
 pub mod write_tree;
@@ -0,0 +1,196 @@
+use crate::commands::update_ref::lock_ref;
+use crate::utils::{list_refs, read_packed_refs, write_packed_refs};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn pack_refs(args: &[String]) {
+    let mut all: bool = false;
+    let mut prune: bool = true;
+    for arg in args {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--no-prune" => prune = false,
+            _ => {
+                eprintln!("Usage: rgit pack-refs [--all] [--no-prune]");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match pack_loose_refs(all, prune) {
+        Ok(count) => println!("Packed {} refs", count),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Moves the loose tags (all the refs under `refs/` with `all`) into `.rgit/packed-refs`,
+/// and removes their files unless `prune` is false. Refs already packed stay packed, and
+/// symbolic refs stay loose. Each loose ref is locked while it is packed, so that a concurrent
+/// update is neither lost nor overwritten. Returns the number of refs packed.
+pub fn pack_loose_refs(all: bool, prune: bool) -> Result<usize, String> {
+    let namespace: &str = if all { "refs/" } else { "refs/tags/" };
+    let mut packed_refs: BTreeMap<String, String> = read_packed_refs();
+
+    // lock the loose refs, then read them: they can not change until they are packed
+    let mut locked_refs: Vec<(String, PathBuf)> = Vec::new();
+    let release = |locked_refs: &[(String, PathBuf)]| {
+        for (_, lock_path) in locked_refs {
+            let _ = fs::remove_file(lock_path);
+        }
+    };
+    for ref_name in list_refs(namespace) {
+        let ref_path: PathBuf = Path::new(".rgit").join(&ref_name);
+        if !ref_path.is_file() {
+            continue;
+        }
+        match lock_ref(&ref_name) {
+            Ok(lock_path) => locked_refs.push((ref_name, lock_path)),
+            Err(error) => {
+                release(&locked_refs);
+                return Err(error);
+            }
+        }
+    }
+
+    let mut loose_refs: Vec<(String, PathBuf)> = Vec::new();
+    for (ref_name, lock_path) in locked_refs {
+        let content: String = fs::read_to_string(Path::new(".rgit").join(&ref_name))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if content.is_empty() || content.starts_with("ref: ") {
+            // symbolic refs point to another ref, they have no hash to pack
+            fs::remove_file(&lock_path).expect("Failed to release reference lock");
+            continue;
+        }
+        packed_refs.insert(ref_name.clone(), content);
+        loose_refs.push((ref_name, lock_path));
+    }
+    if let Err(error) = write_packed_refs(&packed_refs) {
+        release(&loose_refs);
+        return Err(error);
+    }
+
+    for (ref_name, lock_path) in &loose_refs {
+        let ref_path: PathBuf = Path::new(".rgit").join(ref_name);
+        if prune {
+            fs::remove_file(&ref_path).expect("Failed to remove reference");
+        }
+        fs::remove_file(lock_path).expect("Failed to release reference lock");
+        if !prune {
+            continue;
+        }
+
+        // remove the directories left empty, keeping ".rgit/refs/<namespace>"
+        let mut parent_dir: Option<&Path> = ref_path.parent();
+        while let Some(dir) = parent_dir {
+            if dir.components().count() <= 3 || fs::remove_dir(dir).is_err() {
+                break;
+            }
+            parent_dir = dir.parent();
+        }
+    }
+    Ok(loose_refs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::branch::{create_branch, delete_branch, list_branches};
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::rev_parse::rev_parse;
+    use crate::commands::tag::{create_tag, delete_tag, list_tags};
+    use crate::commands::update_index::add_index;
+    use crate::commands::update_ref::update_ref;
+    use crate::utils::read_ref;
+    use crate::utils::tests::{remove_test_repo, set_test_identity, setup_test_repo};
+
+    #[test]
+    fn test_packed_refs_are_read_and_deleted() {
+        setup_test_repo();
+        set_test_identity();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+        create_branch("feature/x", "HEAD").unwrap();
+        let tag_hash: String = create_tag("v1.0", "HEAD", Some("Release"), false).unwrap();
+        create_tag("v0.9", "HEAD", None, false).unwrap();
+
+        // without --all, only tags are packed
+        assert_eq!(pack_loose_refs(false, true), Ok(2));
+        assert!(!Path::new(".rgit/refs/tags/v1.0").exists());
+        assert!(Path::new(".rgit/refs/heads/main").exists());
+        assert_eq!(pack_loose_refs(true, true), Ok(2));
+        assert!(!Path::new(".rgit/refs/heads/feature").exists());
+        let packed: String = fs::read_to_string(".rgit/packed-refs").unwrap();
+        assert!(packed.contains(&format!("{} refs/tags/v1.0\n^{}\n", tag_hash, first_commit)));
+
+        // lookups fall back to packed-refs
+        assert_eq!(get_head_hash(), first_commit);
+        assert_eq!(rev_parse("v1.0"), Ok(tag_hash.clone()));
+        assert_eq!(rev_parse("feature/x"), Ok(first_commit.clone()));
+        assert_eq!(list_tags(None), vec!["v0.9", "v1.0"]);
+        assert_eq!(list_branches(), vec!["feature/x", "main"]);
+
+        // a loose ref overrides its packed value, and deleting removes both
+        update_ref("refs/tags/v0.9", &tag_hash, "test");
+        assert_eq!(read_ref("refs/tags/v0.9"), Some(tag_hash.clone()));
+        assert_eq!(delete_tag("v0.9"), Ok(tag_hash));
+        assert_eq!(read_ref("refs/tags/v0.9"), None);
+        assert!(delete_branch("feature/x", false).is_ok());
+        assert_eq!(list_branches(), vec!["main"]);
+        assert!(!fs::read_to_string(".rgit/packed-refs")
+            .unwrap()
+            .contains("feature/x"));
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_pack_refs_locks_refs_and_keeps_symrefs() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+        create_branch("feature", "HEAD").unwrap();
+        fs::create_dir_all(".rgit/refs/remotes/origin").unwrap();
+        fs::write(
+            ".rgit/refs/remotes/origin/HEAD",
+            "ref: refs/remotes/origin/main\n",
+        )
+        .unwrap();
+
+        // a ref being updated by another process can not be packed
+        fs::write(".rgit/refs/heads/feature.lock", "").unwrap();
+        assert!(pack_loose_refs(true, true).is_err());
+        assert!(Path::new(".rgit/refs/heads/main").exists());
+        assert!(!Path::new(".rgit/refs/heads/main.lock").exists());
+        assert!(!Path::new(".rgit/packed-refs").exists());
+        fs::remove_file(".rgit/refs/heads/feature.lock").unwrap();
+
+        // the symbolic ref stays loose and untouched
+        assert_eq!(pack_loose_refs(true, true), Ok(2));
+        let packed: String = fs::read_to_string(".rgit/packed-refs").unwrap();
+        assert!(packed.contains(&format!("{} refs/heads/feature\n", first_commit)));
+        assert!(!packed.contains("origin/HEAD"));
+        assert_eq!(
+            fs::read_to_string(".rgit/refs/remotes/origin/HEAD").unwrap(),
+            "ref: refs/remotes/origin/main\n"
+        );
+        assert!(!Path::new(".rgit/refs/heads/feature").exists());
+        assert!(!Path::new(".rgit/refs/heads/feature.lock").exists());
+        assert_eq!(read_ref("refs/heads/main"), Some(first_commit));
+
+        remove_test_repo();
+    }
+}
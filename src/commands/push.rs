@@ -1,17 +1,21 @@
-use crate::update_ref;
 use crate::utils::RepoPath;
 use crate::utils::*;
 use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus};
 
 pub fn push(remote_path: &str, branch: &str) {
-    let local_branch_ref: String = format!(".rgit/refs/{}", branch);
-    let commit_hash: String = fs::read_to_string(&local_branch_ref)
-        .expect("Failed to read local branch reference")
-        .trim()
-        .to_string();
+    // find the full name of the local ref (e.g., "refs/heads/main")
+    let local_branch_ref: String = match resolve_ref(branch) {
+        Some(local_branch_ref) => local_branch_ref,
+        None => {
+            eprintln!("Error: src refspec {} does not match any", branch);
+            std::process::exit(1);
+        }
+    };
+    let commit_hash: String =
+        read_ref(&local_branch_ref).expect("Failed to read local branch reference");
 
     let repo_path: RepoPath = RepoPath::Local;
 
@@ -71,7 +75,30 @@ pub fn push(remote_path: &str, branch: &str) {
     }
 
     println!("update_ref");
-    // update the remote reference for the branch
-    let remote_ref: String = format!("{}/refs/{}", remote_path, branch);
-    update_ref(&remote_ref, &commit_hash);
+    // update the remote reference for the branch, in the remote repository (a bare
+    // repository or the `.rgit` directory of another one), through a lock file
+    let remote_ref_path: PathBuf = Path::new(remote_path).join(&local_branch_ref);
+    let lock_path: PathBuf = Path::new(remote_path).join(format!("{}.lock", local_branch_ref));
+    if let Some(parent_dir) = remote_ref_path.parent() {
+        fs::create_dir_all(parent_dir).expect("Failed to create remote reference directory");
+    }
+    if let Err(error) = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock_path)
+    {
+        eprintln!(
+            "Error: unable to lock remote reference {}: {}",
+            remote_ref_path.display(),
+            error
+        );
+        std::process::exit(1);
+    }
+    fs::write(&lock_path, &commit_hash).expect("Failed to update remote reference");
+    fs::rename(&lock_path, &remote_ref_path).expect("Failed to update remote reference");
+    println!(
+        "Updated remote reference '{}' to point to commit {}",
+        remote_ref_path.display(),
+        commit_hash
+    );
 }
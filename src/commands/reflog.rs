@@ -0,0 +1,463 @@
+use crate::utils::{current_identity, list_refs, read_ref};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The hash recorded for a ref that did not exist before, or that was deleted.
+pub const NULL_HASH: &str = "0000000000000000000000000000000000000000";
+
+/// Entries older than this are removed by `reflog expire` by default.
+const DEFAULT_EXPIRE_DAYS: u64 = 90;
+
+/// A line of a reflog: `<old> <new> <name> <<email>> <timestamp> <timezone>\t<message>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    /// `<name> <<email>> <timestamp> <timezone>`
+    pub identity: String,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    /// Returns the time of the entry, in seconds since the epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.identity
+            .rsplit(' ')
+            .nth(1)
+            .and_then(|timestamp| timestamp.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+pub fn reflog(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit reflog [show] [<ref>]");
+        eprintln!("       rgit reflog expire [--expire=<time>] (--all | <ref>...)");
+        eprintln!("       rgit reflog delete <ref>@{{<n>}}...");
+        std::process::exit(1);
+    };
+
+    let (command, args): (&str, &[String]) = match args.first().map(|arg| arg.as_str()) {
+        Some("show") | Some("expire") | Some("delete") => (args[0].as_str(), &args[1..]),
+        _ => ("show", args),
+    };
+
+    match command {
+        "show" => {
+            let ref_name: String = match args {
+                [] => "HEAD".to_string(),
+                [name] => full_ref_name(name).unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                }),
+                _ => usage(),
+            };
+            print!(
+                "{}",
+                format_reflog(&ref_name, args.first().map(|name| name.as_str()))
+            );
+        }
+        "expire" => {
+            let mut cutoff: u64 = now().saturating_sub(DEFAULT_EXPIRE_DAYS * 24 * 60 * 60);
+            let mut ref_names: Vec<String> = Vec::new();
+            for arg in args {
+                if let Some(time) = arg.strip_prefix("--expire=") {
+                    cutoff = parse_expire_time(time).unwrap_or_else(|| usage());
+                } else if arg == "--all" {
+                    ref_names.extend(logged_refs());
+                } else if arg.starts_with('-') {
+                    usage();
+                } else {
+                    ref_names.push(full_ref_name(arg).unwrap_or_else(|error| {
+                        eprintln!("{}", error);
+                        std::process::exit(1);
+                    }));
+                }
+            }
+            if ref_names.is_empty() {
+                usage();
+            }
+            for ref_name in ref_names {
+                let removed: usize = expire_reflog(&ref_name, cutoff);
+                if removed > 0 {
+                    println!("Expired {} entries of {}", removed, ref_name);
+                }
+            }
+        }
+        "delete" => {
+            if args.is_empty() {
+                usage();
+            }
+            for spec in args {
+                let result: Result<(), String> = match parse_reflog_spec(spec) {
+                    Some((name, index)) => full_ref_name(name)
+                        .and_then(|ref_name| delete_reflog_entry(&ref_name, index)),
+                    None => Err(format!("error: not a reflog entry: '{}'", spec)),
+                };
+                if let Err(error) = result {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => usage(),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Failed to read the system time")
+        .as_secs()
+}
+
+/// Parses `now`, `all`, `never`, `<n>.<unit>.ago` (seconds to years) or a timestamp.
+pub fn parse_expire_time(time: &str) -> Option<u64> {
+    match time {
+        "now" | "all" => return Some(u64::MAX),
+        "never" => return Some(0),
+        _ => {}
+    }
+    if let Ok(timestamp) = time.parse() {
+        return Some(timestamp);
+    }
+
+    let parts: Vec<&str> = time.split('.').collect();
+    let [count, unit, "ago"] = parts.as_slice() else {
+        return None;
+    };
+    let count: u64 = count.parse().ok()?;
+    let unit_seconds: u64 = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        "month" => 30 * 24 * 60 * 60,
+        "year" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(now().saturating_sub(count * unit_seconds))
+}
+
+/// Expands a short ref name to the full name its reflog is stored under. `@` is HEAD.
+fn full_ref_name(name: &str) -> Result<String, String> {
+    if name == "HEAD" || name == "@" {
+        return Ok("HEAD".to_string());
+    }
+    crate::utils::resolve_ref(name).ok_or(format!("error: unknown ref '{}'", name))
+}
+
+fn reflog_path(ref_name: &str) -> PathBuf {
+    Path::new(".rgit").join("logs").join(ref_name)
+}
+
+/// Only HEAD and the refs under `refs/` have a reflog; refs of other repositories do not.
+fn has_reflog(ref_name: &str) -> bool {
+    ref_name == "HEAD" || (ref_name.starts_with("refs/") && !Path::new(ref_name).is_absolute())
+}
+
+/// Returns the full names of all refs that have a reflog.
+pub fn logged_refs() -> Vec<String> {
+    list_refs("logs/")
+        .into_iter()
+        .map(|path| path.trim_start_matches("logs/").to_string())
+        .collect()
+}
+
+/// Records that a ref moved from `old` to `new` (empty for a missing ref).
+pub fn append_reflog(ref_name: &str, old: &str, new: &str, message: &str) {
+    if !has_reflog(ref_name) {
+        return;
+    }
+    let or_null = |hash: &str| -> String {
+        if hash.is_empty() {
+            NULL_HASH.to_string()
+        } else {
+            hash.to_string()
+        }
+    };
+
+    let log_path: PathBuf = reflog_path(ref_name);
+    if let Some(parent_dir) = log_path.parent() {
+        fs::create_dir_all(parent_dir).expect("Failed to create the logs directory");
+    }
+    let mut log: String = fs::read_to_string(&log_path).unwrap_or_default();
+    log.push_str(&format!(
+        "{} {} {}\t{}\n",
+        or_null(old),
+        or_null(new),
+        current_identity(),
+        message.lines().next().unwrap_or_default()
+    ));
+    fs::write(log_path, log).expect("Failed to write the reflog");
+}
+
+/// Returns the entries of a reflog, the latest first.
+pub fn read_reflog(ref_name: &str) -> Vec<ReflogEntry> {
+    let log: String = fs::read_to_string(reflog_path(ref_name)).unwrap_or_default();
+    let mut entries: Vec<ReflogEntry> = log
+        .lines()
+        .filter_map(|line| {
+            let (header, message): (&str, &str) = line.split_once('\t').unwrap_or((line, ""));
+            let mut parts: std::str::SplitN<'_, char> = header.splitn(3, ' ');
+            Some(ReflogEntry {
+                old: parts.next()?.to_string(),
+                new: parts.next()?.to_string(),
+                identity: parts.next().unwrap_or_default().to_string(),
+                message: message.to_string(),
+            })
+        })
+        .collect();
+    entries.reverse();
+    entries
+}
+
+/// Rewrites a reflog from its entries, the latest first. An empty reflog is removed.
+fn write_reflog(ref_name: &str, entries: &[ReflogEntry]) {
+    if entries.is_empty() {
+        remove_reflog(ref_name);
+        return;
+    }
+    let log: String = entries
+        .iter()
+        .rev()
+        .map(|entry| {
+            format!(
+                "{} {} {}\t{}\n",
+                entry.old, entry.new, entry.identity, entry.message
+            )
+        })
+        .collect();
+    fs::write(reflog_path(ref_name), log).expect("Failed to write the reflog");
+}
+
+pub fn remove_reflog(ref_name: &str) {
+    let log_path: PathBuf = reflog_path(ref_name);
+    if log_path.is_file() {
+        fs::remove_file(log_path).expect("Failed to remove the reflog");
+    }
+}
+
+/// Moves the reflog of a renamed ref.
+pub fn rename_reflog(old_ref_name: &str, new_ref_name: &str) {
+    let old_path: PathBuf = reflog_path(old_ref_name);
+    if !old_path.is_file() {
+        return;
+    }
+    let new_path: PathBuf = reflog_path(new_ref_name);
+    if let Some(parent_dir) = new_path.parent() {
+        fs::create_dir_all(parent_dir).expect("Failed to create the logs directory");
+    }
+    fs::rename(old_path, new_path).expect("Failed to move the reflog");
+}
+
+/// Removes the entry `<ref>@{<index>}` of a reflog.
+pub fn delete_reflog_entry(ref_name: &str, index: usize) -> Result<(), String> {
+    let mut entries: Vec<ReflogEntry> = read_reflog(ref_name);
+    if index >= entries.len() {
+        return Err(format!(
+            "error: reflog entry {}@{{{}}} does not exist",
+            ref_name, index
+        ));
+    }
+    entries.remove(index);
+    write_reflog(ref_name, &entries);
+    Ok(())
+}
+
+/// Removes the entries older than `cutoff` (seconds since the epoch), except the latest one,
+/// which records the current value of the ref. Returns the number of removed entries.
+pub fn expire_reflog(ref_name: &str, cutoff: u64) -> usize {
+    let entries: Vec<ReflogEntry> = read_reflog(ref_name);
+    let kept: Vec<ReflogEntry> = entries
+        .iter()
+        .enumerate()
+        .filter(|(index, entry)| {
+            entry.timestamp() >= cutoff
+                || (*index == 0 && cutoff != u64::MAX && read_ref(ref_name).is_some())
+        })
+        .map(|(_, entry)| entry.clone())
+        .collect();
+    let removed: usize = entries.len() - kept.len();
+    if removed > 0 {
+        write_reflog(ref_name, &kept);
+    }
+    removed
+}
+
+/// Formats a reflog like `git reflog`: `<short hash> <name>@{<n>}: <message>`.
+pub fn format_reflog(ref_name: &str, display_name: Option<&str>) -> String {
+    let display_name: &str = display_name.unwrap_or(ref_name);
+    read_reflog(ref_name)
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            format!(
+                "{} {}@{{{}}}: {}\n",
+                &entry.new[..7.min(entry.new.len())],
+                display_name,
+                index,
+                entry.message
+            )
+        })
+        .collect()
+}
+
+/// Splits `<ref>@{<n>}` into the ref (`HEAD` when empty) and `n`.
+fn parse_reflog_spec(spec: &str) -> Option<(&str, usize)> {
+    let (name, selector): (&str, &str) = spec.strip_suffix('}')?.split_once("@{")?;
+    let name: &str = if name.is_empty() { "HEAD" } else { name };
+    Some((name, selector.parse().ok()?))
+}
+
+/// Returns the branch (or the commit, if HEAD was detached) checked out before the `n`-th
+/// last checkout, as recorded in the reflog of HEAD: `@{-1}` is the previous branch.
+pub fn previous_branch(n: usize) -> Option<String> {
+    read_reflog("HEAD")
+        .iter()
+        .filter_map(|entry| entry.message.strip_prefix("checkout: moving from "))
+        .filter_map(|moves| moves.split_once(" to "))
+        .nth(n.checked_sub(1)?)
+        .map(|(from, _)| from.to_string())
+}
+
+/// Resolves the reflog syntax of revisions to a commit hash: `<ref>@{<n>}` is the value of
+/// the ref `n` moves ago (`@{<n>}` for the current branch), and `@{-<n>}` the `n`-th
+/// previously checked out branch. Returns `None` if `spec` does not use this syntax.
+pub fn resolve_reflog_revision(spec: &str) -> Option<Result<String, String>> {
+    let (name, selector): (&str, &str) = spec.strip_suffix('}')?.split_once("@{")?;
+
+    if let Some(n) = selector.strip_prefix('-') {
+        let n: usize = n.parse().ok()?;
+        let result: Result<String, String> = match previous_branch(n) {
+            Some(branch) if name.is_empty() => Ok(crate::utils::resolve_revision(&branch)),
+            _ => Err(format!("error: no previous branch for '{}'", spec)),
+        };
+        return Some(result);
+    }
+
+    let index: usize = selector.parse().ok()?;
+    // `@{<n>}` is about the current branch, or HEAD when detached
+    let ref_name: Result<String, String> = if name.is_empty() {
+        let head: String = fs::read_to_string(Path::new(".rgit").join("HEAD")).unwrap_or_default();
+        Ok(head
+            .strip_prefix("ref: ")
+            .map_or("HEAD".to_string(), |target| target.trim().to_string()))
+    } else {
+        full_ref_name(name)
+    };
+    Some(ref_name.and_then(|ref_name| {
+        let entries: Vec<ReflogEntry> = read_reflog(&ref_name);
+        entries
+            .get(index)
+            .map(|entry| entry.new.clone())
+            .ok_or(format!(
+                "error: log for '{}' only has {} entries",
+                name,
+                entries.len()
+            ))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::branch::create_branch;
+    use crate::commands::checkout::{checkout, CheckoutMode};
+    use crate::commands::update_ref::update_ref;
+    use crate::utils::resolve_revision;
+    use crate::utils::tests::{commit_files_on_head, remove_test_repo, setup_test_repo};
+
+    #[test]
+    fn test_ref_changes_are_logged() {
+        setup_test_repo();
+
+        let first_commit: String = commit_files_on_head("First commit", &[("file.txt", "one\n")]);
+        create_branch("topic", "HEAD").unwrap();
+        checkout("topic", CheckoutMode::Safe);
+        checkout("main", CheckoutMode::Safe);
+        let second_commit: String = commit_files_on_head("Second commit", &[("file.txt", "two\n")]);
+
+        // moving the current branch is logged for the branch and for HEAD
+        let messages: Vec<String> = read_reflog("HEAD")
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "commit: Second commit",
+                "checkout: moving from topic to main",
+                "checkout: moving from main to topic",
+                "commit (initial): First commit",
+            ]
+        );
+        let main_log: Vec<ReflogEntry> = read_reflog("refs/heads/main");
+        assert_eq!(main_log[0].old, first_commit);
+        assert_eq!(main_log[0].new, second_commit);
+        assert_eq!(main_log[1].old, NULL_HASH);
+        assert_eq!(main_log[1].message, "commit (initial): First commit");
+        assert_eq!(
+            read_reflog("refs/heads/topic")[0].message,
+            "branch: Created from HEAD"
+        );
+
+        // reflog revisions
+        assert_eq!(resolve_revision("main@{1}"), first_commit);
+        assert_eq!(resolve_revision("HEAD@{0}"), second_commit);
+        assert_eq!(resolve_revision("@{1}"), first_commit);
+        assert_eq!(
+            resolve_reflog_revision("main@{2}"),
+            Some(Err("error: log for 'main' only has 2 entries".to_string()))
+        );
+        checkout("topic", CheckoutMode::Safe);
+        assert_eq!(previous_branch(1), Some("main".to_string()));
+        assert_eq!(resolve_revision("@{-1}"), second_commit);
+
+        // a bad update can be undone from the reflog
+        update_ref("refs/heads/main", &first_commit, "update-ref: oops");
+        assert_eq!(resolve_revision("main@{1}"), second_commit);
+        assert_eq!(
+            format_reflog("refs/heads/main", Some("main"))
+                .lines()
+                .next(),
+            Some(format!("{} main@{{0}}: update-ref: oops", &first_commit[..7]).as_str())
+        );
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_reflog_delete_and_expire() {
+        setup_test_repo();
+
+        commit_files_on_head("First commit", &[("file.txt", "one\n")]);
+        commit_files_on_head("Second commit", &[("file.txt", "two\n")]);
+        commit_files_on_head("Third commit", &[("file.txt", "three\n")]);
+        assert_eq!(read_reflog("HEAD").len(), 3);
+
+        delete_reflog_entry("HEAD", 1).unwrap();
+        let messages: Vec<String> = read_reflog("HEAD")
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec!["commit: Third commit", "commit (initial): First commit"]
+        );
+        assert!(delete_reflog_entry("HEAD", 5).is_err());
+
+        // recent entries are kept, everything goes with --expire=now
+        assert_eq!(
+            expire_reflog("HEAD", parse_expire_time("1.day.ago").unwrap()),
+            0
+        );
+        assert_eq!(expire_reflog("HEAD", parse_expire_time("now").unwrap()), 2);
+        assert!(read_reflog("HEAD").is_empty());
+        assert!(!Path::new(".rgit/logs/HEAD").exists());
+
+        remove_test_repo();
+    }
+}
@@ -0,0 +1,235 @@
+use crate::commands::cat_file::{cat_file, read_object};
+use crate::commands::checkout::{head_files, switch_trees, CheckoutMode};
+use crate::commands::commit::advance_head;
+use crate::commands::format_patch::split_message;
+use crate::commands::restore::{restore_paths, RestoreOptions};
+use crate::commands::rev_parse::rev_parse;
+use crate::commands::update_index::{read_index, write_index};
+use crate::utils::{get_commit_tree, peel_to_commit, read_tree, RepoPath};
+use std::collections::{BTreeMap, HashMap};
+
+/// What `reset` resets besides the current branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetMode {
+    /// only move the branch
+    Soft,
+    /// also reset the index
+    Mixed,
+    /// also reset the index and the tracked files of the worktree
+    Hard,
+}
+
+/// Options of the `reset` command.
+pub struct ResetOptions {
+    pub mode: ResetMode,
+    pub revision: String,
+    /// with paths, only these index entries are reset and HEAD does not move
+    pub paths: Vec<String>,
+}
+
+pub fn parse_reset_options(args: &[String]) -> ResetOptions {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit reset [--soft|--mixed|--hard] [<rev>]");
+        eprintln!("       rgit reset [<rev>] [--] <paths>...");
+        std::process::exit(1);
+    };
+
+    let mut mode: Option<ResetMode> = None;
+    let mut arguments: Vec<String> = Vec::new();
+    let mut paths: Option<Vec<String>> = None;
+    for (index, arg) in args.iter().enumerate() {
+        match arg.as_str() {
+            "--soft" => mode = Some(ResetMode::Soft),
+            "--mixed" => mode = Some(ResetMode::Mixed),
+            "--hard" => mode = Some(ResetMode::Hard),
+            "--" => {
+                paths = Some(args[index + 1..].to_vec());
+                break;
+            }
+            _ if arg.starts_with('-') => usage(),
+            _ => arguments.push(arg.clone()),
+        }
+    }
+
+    // without `--`, the first argument is a revision if it names one
+    let (revision, paths): (Option<String>, Vec<String>) = match paths {
+        Some(paths) if arguments.len() <= 1 => (arguments.pop(), paths),
+        Some(_) => usage(),
+        None if arguments
+            .first()
+            .is_some_and(|argument| rev_parse(argument).is_ok()) =>
+        {
+            let revision: String = arguments.remove(0);
+            (Some(revision), arguments)
+        }
+        None => (None, arguments),
+    };
+
+    if !paths.is_empty() && mode.is_some_and(|mode| mode != ResetMode::Mixed) {
+        eprintln!("Error: cannot do a soft or hard reset with paths.");
+        std::process::exit(1);
+    }
+
+    ResetOptions {
+        mode: mode.unwrap_or(ResetMode::Mixed),
+        revision: revision.unwrap_or("HEAD".to_string()),
+        paths,
+    }
+}
+
+pub fn reset(options: &ResetOptions) {
+    let result: Result<(), String> = if options.paths.is_empty() {
+        reset_head(&options.revision, options.mode)
+    } else {
+        reset_paths(&options.revision, &options.paths)
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Moves the current branch (or the detached HEAD) to a commit, then resets the index
+/// (`Mixed`) or the index and the tracked files (`Hard`) to its tree.
+pub fn reset_head(revision: &str, mode: ResetMode) -> Result<(), String> {
+    let commit_hash: String = resolve_commit(revision)?;
+    let target_files: BTreeMap<String, String> = read_tree(
+        &RepoPath::Local,
+        &get_commit_tree(&RepoPath::Local, &commit_hash),
+    );
+
+    match mode {
+        ResetMode::Soft => {}
+        ResetMode::Mixed => {
+            let index: HashMap<String, String> = target_files.into_iter().collect();
+            write_index(&index);
+        }
+        ResetMode::Hard => {
+            // staged files that are not in HEAD are tracked too, so they are removed
+            let mut current_files: BTreeMap<String, String> = read_index().into_iter().collect();
+            current_files.extend(head_files());
+            switch_trees(&current_files, &target_files, CheckoutMode::Force, revision)?;
+        }
+    }
+    advance_head(
+        &commit_hash,
+        None,
+        &format!("reset: moving to {}", revision),
+    );
+
+    if mode == ResetMode::Hard {
+        let (subject, _): (String, String) = split_message(
+            cat_file(&RepoPath::Local, &commit_hash)
+                .split_once("\n\n")
+                .map_or("", |(_, message)| message),
+        );
+        println!("HEAD is now at {} {}", &commit_hash[..7], subject);
+    }
+    Ok(())
+}
+
+/// Resets the index entries of some paths to a commit, which unstages their changes. HEAD
+/// and the worktree are not touched.
+pub fn reset_paths(revision: &str, paths: &[String]) -> Result<(), String> {
+    // the index is restored from HEAD by default, which also works before the first commit
+    let source: Option<String> = if revision == "HEAD" {
+        None
+    } else {
+        Some(resolve_commit(revision)?)
+    };
+    restore_paths(&RestoreOptions {
+        source,
+        staged: true,
+        worktree: false,
+        overlay: false,
+        paths: paths.to_vec(),
+    })
+}
+
+fn resolve_commit(revision: &str) -> Result<String, String> {
+    let hash: String = rev_parse(revision)
+        .map(|hash| peel_to_commit(&hash))
+        .map_err(|error| format!("Error: {}", error))?;
+    let (object_type, _): (String, Vec<u8>) = read_object(&RepoPath::Local, &hash);
+    if object_type != "commit" {
+        return Err(format!("Error: '{}' is not a commit", revision));
+    }
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_reset_modes() {
+        setup_test_repo();
+
+        fs::write("a.txt", "first\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+        let first_index: HashMap<String, String> = read_index();
+
+        fs::write("a.txt", "second\n").unwrap();
+        fs::write("b.txt", "b\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        add_index("b.txt", &hash_object("b.txt"));
+        commit("Second commit", Some("John Doe"));
+        let second_commit: String = get_head_hash();
+        let second_index: HashMap<String, String> = read_index();
+
+        // --soft only moves the branch
+        reset_head(&first_commit, ResetMode::Soft).unwrap();
+        assert_eq!(get_head_hash(), first_commit);
+        assert_eq!(read_index(), second_index);
+
+        // --mixed also resets the index, and keeps the worktree
+        reset_head(&second_commit, ResetMode::Soft).unwrap();
+        reset_head(&first_commit, ResetMode::Mixed).unwrap();
+        assert_eq!(read_index(), first_index);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "second\n");
+
+        // --hard also resets the tracked files, including staged new files
+        reset_head(&second_commit, ResetMode::Mixed).unwrap();
+        fs::write("c.txt", "staged\n").unwrap();
+        add_index("c.txt", &hash_object("c.txt"));
+        fs::write("untracked.txt", "kept\n").unwrap();
+        reset_head(&first_commit, ResetMode::Hard).unwrap();
+        assert_eq!(get_head_hash(), first_commit);
+        assert_eq!(read_index(), first_index);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "first\n");
+        assert!(!Path::new("b.txt").exists());
+        assert!(!Path::new("c.txt").exists());
+        assert!(Path::new("untracked.txt").exists());
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_reset_paths_unstages_changes() {
+        setup_test_repo();
+
+        fs::write("a.txt", "first\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        commit("First commit", Some("John Doe"));
+        let head_index: HashMap<String, String> = read_index();
+
+        fs::write("a.txt", "changed\n").unwrap();
+        fs::write("new.txt", "new\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        add_index("new.txt", &hash_object("new.txt"));
+        reset_paths("HEAD", &["a.txt".to_string(), "new.txt".to_string()]).unwrap();
+        assert_eq!(read_index(), head_index);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "changed\n");
+
+        remove_test_repo();
+    }
+}
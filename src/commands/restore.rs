@@ -0,0 +1,232 @@
+use crate::commands::cat_file::read_object;
+use crate::commands::checkout::{
+    head_files, remove_worktree_file, set_index_entry, write_worktree_file,
+};
+use crate::commands::rev_parse::rev_parse;
+use crate::commands::update_index::{read_index, write_index};
+use crate::utils::{get_commit_tree, peel_to_commit, read_tree, verify_path, RepoPath};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Options of the `restore` command, also used by `checkout [<rev>] -- <paths>`.
+pub struct RestoreOptions {
+    /// the commit or tree to restore from; the index (or HEAD with `staged`) by default
+    pub source: Option<String>,
+    pub staged: bool,
+    pub worktree: bool,
+    /// keep the files missing from the source instead of removing them, like checkout does
+    pub overlay: bool,
+    pub paths: Vec<String>,
+}
+
+pub fn parse_restore_options(args: &[String]) -> RestoreOptions {
+    let mut options: RestoreOptions = RestoreOptions {
+        source: None,
+        staged: false,
+        worktree: false,
+        overlay: false,
+        paths: Vec::new(),
+    };
+
+    let mut index: usize = 0;
+    while index < args.len() {
+        let arg: &str = args[index].as_str();
+        match arg {
+            "-S" | "--staged" => options.staged = true,
+            "-W" | "--worktree" => options.worktree = true,
+            "-s" | "--source" if index + 1 < args.len() => {
+                options.source = Some(args[index + 1].clone());
+                index += 1;
+            }
+            "--" => {
+                options.paths.extend(args[index + 1..].iter().cloned());
+                break;
+            }
+            _ if arg.starts_with("--source=") => {
+                options.source = Some(arg["--source=".len()..].to_string());
+            }
+            _ if !arg.starts_with('-') => options.paths.push(arg.to_string()),
+            _ => {
+                eprintln!(
+                    "Usage: rgit restore [--source=<rev>] [--staged] [--worktree] <paths>..."
+                );
+                std::process::exit(1);
+            }
+        }
+        index += 1;
+    }
+
+    if options.paths.is_empty() {
+        eprintln!("Usage: rgit restore [--source=<rev>] [--staged] [--worktree] <paths>...");
+        std::process::exit(1);
+    }
+    // without location, only the worktree is restored
+    if !options.staged {
+        options.worktree = true;
+    }
+
+    options
+}
+
+pub fn restore(options: &RestoreOptions) {
+    if let Err(error) = restore_paths(options) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Copies the selected paths from the source to the index and/or the worktree, without
+/// moving HEAD. A path selects a file or a whole directory. Files selected in the index or
+/// the worktree but missing from the source are removed, unless `overlay` is set.
+pub fn restore_paths(options: &RestoreOptions) -> Result<(), String> {
+    let mut index: HashMap<String, String> = read_index();
+    let source: BTreeMap<String, String> = match &options.source {
+        Some(revision) => tree_files(revision)?,
+        None if options.staged => head_files(),
+        None => index.clone().into_iter().collect(),
+    };
+
+    // the files known to the source and to the locations being restored
+    let mut known_paths: BTreeSet<String> = source.keys().cloned().collect();
+    if !options.overlay {
+        known_paths.extend(index.keys().cloned());
+    }
+
+    let mut selected_paths: BTreeSet<String> = BTreeSet::new();
+    for pathspec in &options.paths {
+        let matches: Vec<&String> = known_paths
+            .iter()
+            .filter(|path| matches_pathspec(path, pathspec))
+            .collect();
+        if matches.is_empty() {
+            return Err(format!(
+                "error: pathspec '{}' did not match any file(s) known to rgit",
+                pathspec
+            ));
+        }
+        selected_paths.extend(matches.into_iter().cloned());
+    }
+    for path in &selected_paths {
+        verify_path(path).map_err(|error| format!("error: invalid path {}", error))?;
+    }
+
+    for path in &selected_paths {
+        let blob_hash: Option<&String> = source.get(path);
+        if options.staged {
+            set_index_entry(&mut index, path, blob_hash);
+        }
+        if options.worktree {
+            match blob_hash {
+                Some(blob_hash) => {
+                    let (_, content): (String, Vec<u8>) = read_object(&RepoPath::Local, blob_hash);
+                    write_worktree_file(path, &content);
+                    println!("Restored file: {}", path);
+                }
+                None => {
+                    remove_worktree_file(path);
+                    println!("Removed file: {}", path);
+                }
+            }
+        }
+    }
+
+    if options.staged {
+        write_index(&index);
+    }
+    Ok(())
+}
+
+/// Returns the files of a commit or a tree.
+fn tree_files(revision: &str) -> Result<BTreeMap<String, String>, String> {
+    let hash: String = rev_parse(revision)
+        .map(|hash| peel_to_commit(&hash))
+        .map_err(|error| format!("error: {}", error))?;
+    let (object_type, _): (String, Vec<u8>) = read_object(&RepoPath::Local, &hash);
+    let tree_hash: String = match object_type.as_str() {
+        "commit" => get_commit_tree(&RepoPath::Local, &hash),
+        "tree" => hash,
+        _ => return Err(format!("error: '{}' is not a commit or a tree", revision)),
+    };
+    Ok(read_tree(&RepoPath::Local, &tree_hash))
+}
+
+/// A pathspec matches a file, the files of a directory, or everything with `.`.
+fn matches_pathspec(path: &str, pathspec: &str) -> bool {
+    let pathspec: &str = pathspec.trim_end_matches('/');
+    if pathspec == "." || pathspec.is_empty() {
+        return true;
+    }
+    match path.strip_prefix(pathspec) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+    use std::fs;
+
+    fn options(
+        source: Option<&str>,
+        staged: bool,
+        worktree: bool,
+        paths: &[&str],
+    ) -> RestoreOptions {
+        RestoreOptions {
+            source: source.map(|source| source.to_string()),
+            staged,
+            worktree,
+            overlay: false,
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_restore_paths_from_index_head_and_source() {
+        setup_test_repo();
+
+        fs::write("a.txt", "first\n").unwrap();
+        fs::create_dir("dir").unwrap();
+        fs::write("dir/b.txt", "b\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        add_index("dir/b.txt", &hash_object("dir/b.txt"));
+        commit("First commit", Some("John Doe"));
+        let first_commit: String = get_head_hash();
+
+        fs::write("a.txt", "second\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        commit("Second commit", Some("John Doe"));
+        let second_commit: String = get_head_hash();
+
+        // the worktree is restored from the index by default
+        fs::write("a.txt", "local change\n").unwrap();
+        restore_paths(&options(None, false, true, &["a.txt"])).unwrap();
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "second\n");
+
+        // --staged unstages from HEAD, leaving the worktree alone
+        fs::write("new.txt", "new\n").unwrap();
+        add_index("new.txt", &hash_object("new.txt"));
+        restore_paths(&options(None, true, false, &["new.txt"])).unwrap();
+        assert!(!read_index().contains_key("new.txt"));
+        assert_eq!(fs::read_to_string("new.txt").unwrap(), "new\n");
+
+        // a file of an old commit is restored without moving HEAD
+        restore_paths(&options(Some(&first_commit), true, true, &["a.txt"])).unwrap();
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "first\n");
+        assert_eq!(read_index()["a.txt"], hash_object("a.txt"));
+        assert_eq!(get_head_hash(), second_commit);
+
+        // a directory selects all its files, and unknown paths are refused
+        fs::write("dir/b.txt", "changed\n").unwrap();
+        restore_paths(&options(None, false, true, &["dir/"])).unwrap();
+        assert_eq!(fs::read_to_string("dir/b.txt").unwrap(), "b\n");
+        assert!(restore_paths(&options(None, false, true, &["missing.txt"])).is_err());
+
+        remove_test_repo();
+    }
+}
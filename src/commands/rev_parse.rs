@@ -0,0 +1,398 @@
+use crate::commands::branch::{current_branch, upstream_ref};
+use crate::commands::cat_file::read_object;
+use crate::commands::reflog::{previous_branch, resolve_reflog_revision};
+use crate::commands::update_ref::head_target;
+use crate::utils::{
+    find_object_by_prefix, find_tree_entry, list_refs, object_exists, peel_to_commit, read_ref,
+    resolve_ref, RepoPath,
+};
+use std::collections::{HashSet, VecDeque};
+
+/// How `rev-parse` prints the revisions it resolves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevParseOutput {
+    /// the full object hash
+    Hash,
+    /// the object hash abbreviated to 7 characters
+    Short,
+    /// the full name of the ref, e.g. `refs/heads/main`
+    SymbolicFullName,
+    /// the shortest unambiguous name of the ref, e.g. `main`
+    AbbrevRef,
+}
+
+pub fn parse_rev_parse_options(args: &[String]) -> (RevParseOutput, Vec<String>) {
+    let mut output: RevParseOutput = RevParseOutput::Hash;
+    let mut revisions: Vec<String> = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--short" => output = RevParseOutput::Short,
+            "--symbolic-full-name" => output = RevParseOutput::SymbolicFullName,
+            "--abbrev-ref" => output = RevParseOutput::AbbrevRef,
+            "--verify" => {}
+            _ if arg.starts_with('-') && arg != "-" => {
+                eprintln!(
+                    "Usage: rgit rev-parse [--short|--symbolic-full-name|--abbrev-ref] <rev>..."
+                );
+                std::process::exit(1);
+            }
+            _ => revisions.push(arg.clone()),
+        }
+    }
+    (output, revisions)
+}
+
+pub fn rev_parse_command(output: RevParseOutput, revisions: &[String]) {
+    for revision in revisions {
+        let result: Result<String, String> = match output {
+            RevParseOutput::Hash => rev_parse(revision),
+            RevParseOutput::Short => rev_parse(revision).map(|hash| hash[..7].to_string()),
+            RevParseOutput::SymbolicFullName => symbolic_full_name(revision),
+            RevParseOutput::AbbrevRef => {
+                symbolic_full_name(revision).map(|ref_name| abbreviate_ref(&ref_name))
+            }
+        };
+        match result {
+            Ok(line) => println!("{}", line),
+            Err(error) => {
+                eprintln!("fatal: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Resolves a revision expression to an object hash, like `git rev-parse`:
+///
+/// - `<rev>:<path>`: the blob or tree at `path` in the tree of `rev` (HEAD when empty).
+/// - `:/<text>`: the latest commit reachable from HEAD or a ref whose message contains `text`.
+/// - `<rev>~<n>`: the `n`-th first-parent ancestor; `<rev>^<n>`: the `n`-th parent (`^0` is
+///   the commit itself); `<rev>^{<type>}`: the object peeled to `type` (`^{}` peels tags).
+/// - `<ref>@{<n>}`, `@{-<n>}`: an entry of a reflog, or a previously checked out branch.
+/// - `<branch>@{upstream}` (or `@{u}`): the branch tracked by `branch` (the current one when
+///   empty or `HEAD`).
+/// - `HEAD` (or `@`), a ref in the search order of `resolve_ref`, or a full or abbreviated
+///   object hash.
+///
+/// Refs are not peeled, so that an annotated tag resolves to the tag object.
+pub fn rev_parse(spec: &str) -> Result<String, String> {
+    if let Some(text) = spec.strip_prefix(":/") {
+        return find_commit_by_message(text);
+    }
+    if let Some((revision, path)) = spec.split_once(':') {
+        let revision: &str = if revision.is_empty() {
+            "HEAD"
+        } else {
+            revision
+        };
+        let tree_hash: String = peel(&rev_parse(revision)?, "tree")
+            .map_err(|_| format!("'{}' is not a tree-ish", revision))?;
+        return match find_tree_entry(&RepoPath::Local, &tree_hash, path) {
+            Some((_, hash)) => Ok(hash),
+            None => Err(format!("path '{}' does not exist in '{}'", path, revision)),
+        };
+    }
+
+    let (base, mut suffixes): (&str, &str) = split_suffixes(spec);
+    let mut hash: String = resolve_base(base)?;
+
+    // "~<n>", "^<n>" and "^{<type>}", applied from left to right
+    while !suffixes.is_empty() {
+        let operator: char = suffixes.chars().next().unwrap_or_default();
+        suffixes = &suffixes[1..];
+
+        if operator == '^' && suffixes.starts_with('{') {
+            let end: usize = suffixes
+                .find('}')
+                .ok_or(format!("unknown revision '{}'", spec))?;
+            let object_type: &str = &suffixes[1..end];
+            suffixes = &suffixes[end + 1..];
+            hash = match object_type {
+                "" => peel_to_commit(&hash),
+                "object" => hash,
+                _ => peel(&hash, object_type)?,
+            };
+            continue;
+        }
+
+        let digits: usize = suffixes
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(suffixes.len());
+        let count: usize = if digits == 0 {
+            1
+        } else {
+            suffixes[..digits]
+                .parse()
+                .map_err(|_| format!("unknown revision '{}'", spec))?
+        };
+        suffixes = &suffixes[digits..];
+
+        let commit_hash: String = peel(&hash, "commit")?;
+        hash = match operator {
+            '~' => (0..count).try_fold(commit_hash, |commit_hash, _| {
+                commit_parents(&commit_hash)
+                    .into_iter()
+                    .next()
+                    .ok_or(format!("'{}': the commit has no parent", spec))
+            })?,
+            _ if count == 0 => commit_hash,
+            _ => commit_parents(&commit_hash)
+                .into_iter()
+                .nth(count - 1)
+                .ok_or(format!("'{}': the commit has no parent {}", spec, count))?,
+        };
+    }
+
+    Ok(hash)
+}
+
+/// Splits a revision into its base and its `~`/`^` suffixes. Braces of `@{...}` in the base
+/// are skipped.
+fn split_suffixes(spec: &str) -> (&str, &str) {
+    let mut depth: usize = 0;
+    for (index, c) in spec.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            '~' | '^' if depth == 0 => return spec.split_at(index),
+            _ => {}
+        }
+    }
+    (spec, "")
+}
+
+fn resolve_base(base: &str) -> Result<String, String> {
+    let unknown = || -> String { format!("unknown revision '{}'", base) };
+
+    if base == "HEAD" || base == "@" {
+        return read_ref("HEAD").ok_or(unknown());
+    }
+    if let Some((name, selector)) = base
+        .strip_suffix('}')
+        .and_then(|base| base.split_once("@{"))
+    {
+        if selector == "upstream" || selector == "u" {
+            let upstream: String = upstream_of(name)?;
+            return read_ref(&upstream).ok_or(unknown());
+        }
+        return resolve_reflog_revision(base).unwrap_or(Err(unknown()));
+    }
+
+    if base.len() == 40 && object_exists(base) {
+        return Ok(base.to_string());
+    }
+    if let Some(hash) = resolve_ref(base).and_then(|ref_name| read_ref(&ref_name)) {
+        return Ok(hash);
+    }
+    find_object_by_prefix(base).ok_or(unknown())
+}
+
+/// Returns the full name of the upstream ref of a branch (the current branch when empty or
+/// `HEAD`).
+fn upstream_of(name: &str) -> Result<String, String> {
+    let branch: String = if name.is_empty() || name == "HEAD" {
+        current_branch().ok_or("HEAD does not point to a branch".to_string())?
+    } else {
+        name.to_string()
+    };
+    upstream_ref(&branch).ok_or(format!("no upstream configured for branch '{}'", branch))
+}
+
+/// Returns the full name of the ref a revision names: a branch, a tag, the upstream of a
+/// branch or a previous branch. HEAD names the current branch, or itself when detached.
+pub fn symbolic_full_name(spec: &str) -> Result<String, String> {
+    if spec == "HEAD" || spec == "@" {
+        return Ok(head_target().unwrap_or("HEAD".to_string()));
+    }
+    if let Some((name, selector)) = spec
+        .strip_suffix('}')
+        .and_then(|spec| spec.split_once("@{"))
+    {
+        if selector == "upstream" || selector == "u" {
+            return upstream_of(name);
+        }
+        if let Some(n) = selector.strip_prefix('-').and_then(|n| n.parse().ok()) {
+            let branch: String =
+                previous_branch(n).ok_or(format!("no previous branch for '{}'", spec))?;
+            return Ok(resolve_ref(&branch).unwrap_or(branch));
+        }
+    }
+    resolve_ref(spec).ok_or(format!("'{}' is not a ref", spec))
+}
+
+/// Shortens a full ref name: `refs/heads/main` is `main`, `refs/remotes/origin/main` is
+/// `origin/main`.
+pub fn abbreviate_ref(ref_name: &str) -> String {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| ref_name.strip_prefix(prefix))
+        .unwrap_or(ref_name)
+        .to_string()
+}
+
+/// Returns the parents of a commit, in order.
+pub fn commit_parents(commit_hash: &str) -> Vec<String> {
+    let (_, content): (String, Vec<u8>) = read_object(&RepoPath::Local, commit_hash);
+    String::from_utf8_lossy(&content)
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.strip_prefix("parent "))
+        .map(|parent| parent.trim().to_string())
+        .collect()
+}
+
+/// Peels an object to the given type: tags are followed, and a commit gives its tree.
+fn peel(hash: &str, object_type: &str) -> Result<String, String> {
+    let mut hash: String = hash.to_string();
+    loop {
+        let (current_type, content): (String, Vec<u8>) = read_object(&RepoPath::Local, &hash);
+        if current_type == object_type {
+            return Ok(hash);
+        }
+        let content: String = String::from_utf8_lossy(&content).into_owned();
+        let next_line: Option<&str> = match current_type.as_str() {
+            "tag" => content
+                .lines()
+                .find_map(|line| line.strip_prefix("object ")),
+            "commit" if object_type == "tree" => {
+                content.lines().find_map(|line| line.strip_prefix("tree "))
+            }
+            _ => None,
+        };
+        match next_line {
+            Some(next_hash) => hash = next_hash.trim().to_string(),
+            None => {
+                return Err(format!(
+                    "'{}' is a {}, not a {}",
+                    &hash[..7],
+                    current_type,
+                    object_type
+                ))
+            }
+        }
+    }
+}
+
+/// Finds the latest commit whose message contains `text`, walking back from HEAD and then
+/// from the other refs, closest commits first.
+fn find_commit_by_message(text: &str) -> Result<String, String> {
+    let mut to_visit: VecDeque<String> = read_ref("HEAD").into_iter().collect();
+    to_visit.extend(
+        list_refs("refs/")
+            .iter()
+            .filter_map(|ref_name| read_ref(ref_name)),
+    );
+    let mut visited: HashSet<String> = HashSet::new();
+
+    while let Some(hash) = to_visit.pop_front() {
+        if !visited.insert(hash.clone()) {
+            continue;
+        }
+        let commit_hash: String = match peel(&hash, "commit") {
+            Ok(commit_hash) => commit_hash,
+            Err(_) => continue,
+        };
+        let (_, content): (String, Vec<u8>) = read_object(&RepoPath::Local, &commit_hash);
+        let content: String = String::from_utf8_lossy(&content).into_owned();
+        let message: &str = content
+            .split_once("\n\n")
+            .map_or("", |(_, message)| message);
+        if message.contains(text) {
+            return Ok(commit_hash);
+        }
+        to_visit.extend(commit_parents(&commit_hash));
+    }
+    Err(format!("no commit message matches '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::branch::create_branch;
+    use crate::commands::checkout::{checkout, CheckoutMode};
+    use crate::commands::tag::create_tag;
+    use crate::commands::update_ref::update_ref;
+    use crate::utils::compute_hash;
+    use crate::utils::get_commit_tree;
+    use crate::utils::tests::{
+        commit_files_on_head, remove_test_repo, set_test_identity, setup_test_repo,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_rev_parse_ancestry_and_peeling() {
+        setup_test_repo();
+        set_test_identity();
+
+        let first_commit: String = commit_files_on_head("First commit", &[("file.txt", "one\n")]);
+        let second_commit: String = commit_files_on_head("Second commit", &[("file.txt", "two\n")]);
+        let third_commit: String = commit_files_on_head("Third commit", &[("file.txt", "three\n")]);
+
+        assert_eq!(rev_parse("HEAD"), Ok(third_commit.clone()));
+        assert_eq!(rev_parse("@"), Ok(third_commit.clone()));
+        assert_eq!(rev_parse("main~2"), Ok(first_commit.clone()));
+        assert_eq!(rev_parse("HEAD~1~1"), Ok(first_commit.clone()));
+        assert_eq!(rev_parse("HEAD^^"), Ok(first_commit.clone()));
+        assert_eq!(rev_parse("main^1"), Ok(second_commit.clone()));
+        assert_eq!(rev_parse("main^0"), Ok(third_commit.clone()));
+        assert!(rev_parse("main^2").is_err());
+        assert!(rev_parse("HEAD~3").is_err());
+        assert_eq!(rev_parse(&third_commit[..8]), Ok(third_commit.clone()));
+
+        // tags are peeled on request only
+        let tag_hash: String = create_tag("v1.0", "HEAD~1", Some("Release"), false).unwrap();
+        assert_eq!(rev_parse("v1.0"), Ok(tag_hash));
+        assert_eq!(rev_parse("v1.0^{}"), Ok(second_commit.clone()));
+        assert_eq!(rev_parse("v1.0^{commit}"), Ok(second_commit.clone()));
+        assert_eq!(rev_parse("v1.0~1"), Ok(first_commit.clone()));
+        assert_eq!(
+            rev_parse("v1.0^{tree}"),
+            Ok(get_commit_tree(&RepoPath::Local, &second_commit))
+        );
+        assert!(rev_parse("v1.0^{blob}").is_err());
+
+        // paths and messages
+        assert_eq!(
+            rev_parse("HEAD~2:file.txt"),
+            Ok(compute_hash("blob", b"one\n"))
+        );
+        assert_eq!(rev_parse(":/Second"), Ok(second_commit));
+        assert!(rev_parse(":/Missing").is_err());
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_rev_parse_upstream_and_previous_branch() {
+        setup_test_repo();
+
+        let first_commit: String = commit_files_on_head("First commit", &[("file.txt", "one\n")]);
+        create_branch("topic", "HEAD").unwrap();
+        let second_commit: String = commit_files_on_head("Second commit", &[("file.txt", "two\n")]);
+        checkout("topic", CheckoutMode::Safe);
+
+        update_ref("refs/remotes/origin/main", &first_commit, "fetch");
+        fs::write(
+            ".rgit/config",
+            "[branch \"main\"]\n    remote = origin\n    merge = refs/heads/main\n[branch \"topic\"]\n    remote = .\n    merge = refs/heads/main\n",
+        )
+        .unwrap();
+
+        assert_eq!(rev_parse("main@{upstream}"), Ok(first_commit.clone()));
+        assert_eq!(rev_parse("@{u}"), Ok(second_commit.clone()));
+        assert_eq!(rev_parse("HEAD@{upstream}"), Ok(second_commit.clone()));
+        assert_eq!(
+            symbolic_full_name("main@{u}"),
+            Ok("refs/remotes/origin/main".to_string())
+        );
+        assert_eq!(abbreviate_ref(&symbolic_full_name("@{u}").unwrap()), "main");
+        assert_eq!(rev_parse("@{-1}"), Ok(second_commit.clone()));
+        assert_eq!(rev_parse("@{-1}~1"), Ok(first_commit));
+        assert_eq!(
+            symbolic_full_name("HEAD"),
+            Ok("refs/heads/topic".to_string())
+        );
+
+        remove_test_repo();
+    }
+}
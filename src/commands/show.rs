@@ -0,0 +1,204 @@
+use crate::commands::cat_file::read_object;
+use crate::commands::diff::commit_diffs;
+use crate::commands::log::format_commit;
+use crate::commands::rev_parse::rev_parse;
+use crate::diff::{format_diffs, DiffFormat, DiffSettings, FileDiff};
+use crate::utils::{
+    format_rfc2822_date, object_exists, parse_signature, read_tree_entries, RepoPath, Signature,
+};
+use std::io::Write;
+
+/// Options of the `show` command.
+pub struct ShowOptions {
+    pub objects: Vec<String>,
+    pub settings: DiffSettings,
+    pub format: DiffFormat,
+}
+
+pub fn parse_show_options(args: &[String]) -> ShowOptions {
+    let mut options: ShowOptions = ShowOptions {
+        objects: Vec::new(),
+        settings: DiffSettings::from_config(),
+        format: DiffFormat::Patch,
+    };
+
+    for arg in args {
+        match arg.as_str() {
+            "--stat" => options.format = DiffFormat::Stat,
+            "--name-only" => options.format = DiffFormat::NameOnly,
+            "--name-status" => options.format = DiffFormat::NameStatus,
+            _ if options.settings.parse_option(arg) => {}
+            _ if arg.starts_with('-') => {
+                eprintln!("Error: unknown show option '{}'", arg);
+                std::process::exit(1);
+            }
+            _ => options.objects.push(arg.clone()),
+        }
+    }
+
+    if options.objects.is_empty() {
+        options.objects.push("HEAD".to_string());
+    }
+
+    options
+}
+
+pub fn show(options: &ShowOptions) {
+    let mut stdout: std::io::Stdout = std::io::stdout();
+    for object in &options.objects {
+        let hash: String = match rev_parse(object) {
+            Ok(hash) => hash,
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                std::process::exit(1);
+            }
+        };
+        stdout
+            .write_all(&show_object(&hash, object, options))
+            .expect("Failed to write to standard output");
+    }
+}
+
+/// Formats an object for people: a commit with its changes, the entries of a tree, the
+/// contents of a blob, or a tag followed by the object it points to.
+pub fn show_object(hash: &str, spec: &str, options: &ShowOptions) -> Vec<u8> {
+    let (object_type, content): (String, Vec<u8>) = read_object(&RepoPath::Local, hash);
+    let text: String = String::from_utf8_lossy(&content).into_owned();
+
+    match object_type.as_str() {
+        "commit" => {
+            let file_diffs: Vec<FileDiff> = commit_diffs(hash, &options.settings);
+            let mut output: String = format_commit(hash, &text);
+            let changes: String = format_diffs(&file_diffs, options.format, &options.settings);
+            if !changes.is_empty() {
+                output.push('\n');
+                output.push_str(&changes);
+            }
+            output.into_bytes()
+        }
+        "tree" => {
+            let mut output: String = format!("tree {}\n\n", spec);
+            for entry in read_tree_entries(&RepoPath::Local, hash) {
+                let suffix: &str = if entry.object_type == "tree" { "/" } else { "" };
+                output.push_str(&format!("{}{}\n", entry.name, suffix));
+            }
+            output.into_bytes()
+        }
+        "tag" => {
+            // "object <hash>\ntype <type>\ntag <name>\ntagger <tagger>\n\n<message>"
+            let header_value = |name: &str| -> String {
+                text.lines()
+                    .take_while(|line| !line.is_empty())
+                    .find_map(|line| line.strip_prefix(&format!("{} ", name)))
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let message: String = text
+                .lines()
+                .skip_while(|line| !line.is_empty())
+                .skip(1)
+                .collect::<Vec<&str>>()
+                .join("\n");
+
+            let mut output: Vec<u8> = format!("tag {}\n", header_value("tag")).into_bytes();
+            let tagger: String = header_value("tagger");
+            if !tagger.is_empty() {
+                // like the author of a commit in `log`
+                let tagger: Signature = parse_signature(&tagger);
+                output.extend(format!("Tagger: {}\n", tagger.identity()).into_bytes());
+                if let Some((timestamp, timezone)) = &tagger.date {
+                    let date: String = format_rfc2822_date(*timestamp, timezone);
+                    output.extend(format!("Date: {}\n", date).into_bytes());
+                }
+            }
+            output.extend(format!("\n{}\n\n", message).into_bytes());
+
+            let target: String = header_value("object");
+            if object_exists(&target) {
+                output.extend(show_object(&target, &target, options));
+            }
+            output
+        }
+        _ => content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::cat_file::cat_file;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::tag::create_tag;
+    use crate::commands::update_index::add_index;
+    use crate::utils::get_commit_tree;
+    use crate::utils::tests::{remove_test_repo, set_test_identity, setup_test_repo};
+    use std::fs;
+
+    fn show_output(spec: &str) -> String {
+        let options: ShowOptions = parse_show_options(&[]);
+        let hash: String = rev_parse(spec).unwrap();
+        String::from_utf8(show_object(&hash, spec, &options)).unwrap()
+    }
+
+    #[test]
+    fn test_show_commits_blobs_trees_and_tags() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+
+        fs::write("file.txt", "one\ntwo\n").unwrap();
+        fs::create_dir("src").unwrap();
+        fs::write("src/main.rs", "fn main() {}\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        add_index("src/main.rs", &hash_object("src/main.rs"));
+        commit(
+            "Second commit",
+            Some("John Doe <john@example.com> 1681396200 +0200"),
+        );
+        let commit_hash: String = get_head_hash();
+
+        // a commit shows its header and its changes
+        let output: String = show_output("HEAD");
+        assert!(output.starts_with(&format!(
+            "Commit: {}\nAuthor: John Doe <john@example.com>\nDate: Thu, 13 Apr 2023 16:30:00 +0200\nMessage: Second commit\n\ndiff --git a/file.txt b/file.txt\n",
+            commit_hash
+        )));
+        assert!(output.contains("@@ -1 +1,2 @@\n one\n+two\n"));
+
+        // abbreviated hashes and files at a revision
+        assert!(show_output(&commit_hash[..7]).starts_with("Commit: "));
+        assert_eq!(show_output("HEAD:file.txt"), "one\ntwo\n");
+        assert_eq!(show_output("HEAD:src/main.rs"), "fn main() {}\n");
+        assert!(rev_parse("HEAD:missing.txt").is_err());
+
+        // a tree lists its entries
+        let tree_hash: String = get_commit_tree(&RepoPath::Local, &commit_hash);
+        assert_eq!(
+            show_output(&tree_hash),
+            format!("tree {}\n\nfile.txt\nsrc/\n", tree_hash)
+        );
+
+        // a tag shows its tagger and message, then the tagged object
+        set_test_identity();
+        let tag_hash: String = create_tag("v1.0", "HEAD", Some("First release"), false).unwrap();
+        let tagger: Signature = parse_signature(
+            cat_file(&RepoPath::Local, &tag_hash)
+                .lines()
+                .find_map(|line| line.strip_prefix("tagger "))
+                .unwrap(),
+        );
+        let (timestamp, timezone): (i64, String) = tagger.date.unwrap();
+        let output: String = show_output("v1.0");
+        assert!(output.starts_with(&format!(
+            "tag v1.0\nTagger: Config User <config@example.com>\nDate: {}\n\nFirst release\n\nCommit: {}\n",
+            format_rfc2822_date(timestamp, &timezone),
+            commit_hash
+        )));
+
+        remove_test_repo();
+    }
+}
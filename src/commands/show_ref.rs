@@ -0,0 +1,144 @@
+use crate::utils::{list_refs, peel_to_commit, read_ref};
+
+/// Options of the `show-ref` command.
+pub struct ShowRefOptions {
+    pub heads: bool,
+    pub tags: bool,
+    /// also show the object an annotated tag points to, as `<ref>^{}`
+    pub dereference: bool,
+    /// only show the refs whose name ends with one of these components, e.g. `main` or
+    /// `heads/main`
+    pub patterns: Vec<String>,
+}
+
+pub fn parse_show_ref_options(args: &[String]) -> ShowRefOptions {
+    let mut options: ShowRefOptions = ShowRefOptions {
+        heads: false,
+        tags: false,
+        dereference: false,
+        patterns: Vec::new(),
+    };
+    for arg in args {
+        match arg.as_str() {
+            "--heads" => options.heads = true,
+            "--tags" => options.tags = true,
+            "-d" | "--dereference" => options.dereference = true,
+            _ if arg.starts_with('-') => {
+                eprintln!(
+                    "Usage: rgit show-ref [--heads] [--tags] [-d|--dereference] [<pattern>...]"
+                );
+                std::process::exit(1);
+            }
+            _ => options.patterns.push(arg.clone()),
+        }
+    }
+    options
+}
+
+/// Prints `<hash> <ref>` for each ref, and exits with status 1 if none matches.
+pub fn show_ref(options: &ShowRefOptions) {
+    let lines: Vec<String> = show_ref_lines(options);
+    if lines.is_empty() {
+        std::process::exit(1);
+    }
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+/// Returns the lines of `show-ref`: the loose and packed refs under `refs/` (only branches
+/// and/or tags with `heads` and `tags`), sorted by name.
+pub fn show_ref_lines(options: &ShowRefOptions) -> Vec<String> {
+    let mut namespaces: Vec<&str> = Vec::new();
+    if options.heads {
+        namespaces.push("refs/heads/");
+    }
+    if options.tags {
+        namespaces.push("refs/tags/");
+    }
+    if namespaces.is_empty() {
+        namespaces.push("refs/");
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    for namespace in namespaces {
+        for ref_name in list_refs(namespace) {
+            let matches_pattern: bool = options.patterns.is_empty()
+                || options.patterns.iter().any(|pattern| {
+                    ref_name == *pattern || ref_name.ends_with(&format!("/{}", pattern))
+                });
+            let Some(hash) = read_ref(&ref_name).filter(|_| matches_pattern) else {
+                continue;
+            };
+            lines.push(format!("{} {}", hash, ref_name));
+
+            let peeled_hash: String = peel_to_commit(&hash);
+            if options.dereference && peeled_hash != hash {
+                lines.push(format!("{} {}^{{}}", peeled_hash, ref_name));
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::branch::create_branch;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::pack_refs::pack_loose_refs;
+    use crate::commands::tag::create_tag;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{remove_test_repo, set_test_identity, setup_test_repo};
+    use std::fs;
+
+    #[test]
+    fn test_show_ref_lists_loose_and_packed_refs() {
+        setup_test_repo();
+        set_test_identity();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let commit_hash: String = get_head_hash();
+        create_branch("feature/main", "HEAD").unwrap();
+        let tag_hash: String = create_tag("v1.0", "HEAD", Some("Release"), false).unwrap();
+        pack_loose_refs(false, true).unwrap();
+
+        let options: ShowRefOptions = parse_show_ref_options(&[]);
+        assert_eq!(
+            show_ref_lines(&options),
+            vec![
+                format!("{} refs/heads/feature/main", commit_hash),
+                format!("{} refs/heads/main", commit_hash),
+                format!("{} refs/tags/v1.0", tag_hash),
+            ]
+        );
+
+        let options: ShowRefOptions =
+            parse_show_ref_options(&["--tags".to_string(), "-d".to_string()]);
+        assert_eq!(
+            show_ref_lines(&options),
+            vec![
+                format!("{} refs/tags/v1.0", tag_hash),
+                format!("{} refs/tags/v1.0^{{}}", commit_hash),
+            ]
+        );
+
+        // patterns match whole trailing components
+        let options: ShowRefOptions =
+            parse_show_ref_options(&["--heads".to_string(), "main".to_string()]);
+        assert_eq!(show_ref_lines(&options).len(), 2);
+        let options: ShowRefOptions = parse_show_ref_options(&["heads/main".to_string()]);
+        assert_eq!(
+            show_ref_lines(&options),
+            vec![format!("{} refs/heads/main", commit_hash)]
+        );
+        let options: ShowRefOptions = parse_show_ref_options(&["ain".to_string()]);
+        assert!(show_ref_lines(&options).is_empty());
+
+        remove_test_repo();
+    }
+}
@@ -0,0 +1,513 @@
+use crate::commands::branch::current_branch;
+use crate::commands::cat_file::{cat_file, read_object};
+use crate::commands::checkout::{
+    head_files, remove_worktree_file, worktree_hash, write_worktree_file,
+};
+use crate::commands::commit_tree::commit_tree;
+use crate::commands::diff::commit_diffs;
+use crate::commands::format_patch::split_message;
+use crate::commands::get_head_hash::get_head_hash;
+use crate::commands::reflog::{delete_reflog_entry, read_reflog};
+use crate::commands::reset::{reset_head, ResetMode};
+use crate::commands::update_index::{read_index, write_index};
+use crate::commands::update_ref::update_ref;
+use crate::commands::write_tree::{write_files_tree, write_tree};
+use crate::diff::merge::{merge3, MergeResult};
+use crate::diff::{format_diffs, DiffAlgorithm, DiffFormat, DiffSettings, FileDiff};
+use crate::utils::{
+    current_identity, delete_ref, get_commit_tree, hash_and_store, read_tree, RepoPath,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The reference to the latest stash; older stashes are only kept in its reflog.
+const STASH_REF: &str = "refs/stash";
+
+/// A saved stash: the commit holding the worktree state, and the message of its reflog entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StashEntry {
+    pub commit: String,
+    pub message: String,
+}
+
+pub fn stash(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit stash [push] [-m <message>] [-u|--include-untracked]");
+        eprintln!("       rgit stash list");
+        eprintln!("       rgit stash show [-p] [<stash>]");
+        eprintln!("       rgit stash (apply|pop) [--index] [<stash>]");
+        eprintln!("       rgit stash drop [<stash>]");
+        std::process::exit(1);
+    };
+    let exit_on_error = |result: Result<(), String>| {
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let (command, args): (&str, &[String]) = match args.first() {
+        Some(command) if !command.starts_with('-') => (command.as_str(), &args[1..]),
+        _ => ("push", args),
+    };
+    let options: Vec<&str> = args
+        .iter()
+        .map(|arg| arg.as_str())
+        .filter(|arg| arg.starts_with('-'))
+        .collect();
+    let stash_index = || -> usize {
+        let specs: Vec<&String> = args.iter().filter(|arg| !arg.starts_with('-')).collect();
+        match specs.as_slice() {
+            [] => 0,
+            [spec] => parse_stash_spec(spec).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }),
+            _ => usage(),
+        }
+    };
+
+    match command {
+        "push" => {
+            let mut message: Option<&str> = None;
+            let mut include_untracked: bool = false;
+            let mut index: usize = 0;
+            while index < args.len() {
+                match args[index].as_str() {
+                    "-m" | "--message" if index + 1 < args.len() => {
+                        message = Some(&args[index + 1]);
+                        index += 1;
+                    }
+                    "-u" | "--include-untracked" => include_untracked = true,
+                    _ => usage(),
+                }
+                index += 1;
+            }
+            match stash_push(message, include_untracked) {
+                Ok(Some(entry)) => {
+                    println!("Saved working directory and index state {}", entry.message)
+                }
+                Ok(None) => println!("No local changes to save"),
+                Err(error) => exit_on_error(Err(error)),
+            }
+        }
+        "list" => {
+            for (index, entry) in stash_entries().iter().enumerate() {
+                println!("stash@{{{}}}: {}", index, entry.message);
+            }
+        }
+        "show" => {
+            let format: DiffFormat = if options.contains(&"-p") || options.contains(&"--patch") {
+                DiffFormat::Patch
+            } else {
+                DiffFormat::Stat
+            };
+            exit_on_error(stash_show(stash_index(), format).map(|output| print!("{}", output)));
+        }
+        "apply" | "pop" => {
+            let restore_index: bool = options.contains(&"--index");
+            let index: usize = stash_index();
+            let conflicted_paths: Vec<String> =
+                stash_apply(index, restore_index).unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                });
+            if !conflicted_paths.is_empty() {
+                for path in &conflicted_paths {
+                    eprintln!("CONFLICT (content): Merge conflict in {}", path);
+                }
+                if command == "pop" {
+                    eprintln!("The stash entry is kept in case you need it again.");
+                }
+                std::process::exit(1);
+            }
+            if command == "pop" {
+                exit_on_error(stash_drop(index));
+            }
+        }
+        "drop" => exit_on_error(stash_drop(stash_index())),
+        _ => usage(),
+    }
+}
+
+/// Parses `stash@{<n>}` or `<n>`.
+fn parse_stash_spec(spec: &str) -> Result<usize, String> {
+    let number: &str = spec
+        .strip_prefix("stash@{")
+        .and_then(|spec| spec.strip_suffix('}'))
+        .unwrap_or(spec);
+    number
+        .parse()
+        .map_err(|_| format!("Error: '{}' is not a stash reference", spec))
+}
+
+/// Saves the index and the tracked files of the worktree (and the untracked files with
+/// `include_untracked`) as a stash, then resets them to HEAD. Returns `None` if there is
+/// nothing to save.
+///
+/// The stash commit holds the worktree state; its parents are the HEAD commit, a commit
+/// holding the index state, and a commit holding the untracked files if any.
+pub fn stash_push(
+    message: Option<&str>,
+    include_untracked: bool,
+) -> Result<Option<StashEntry>, String> {
+    let head_hash: String = get_head_hash();
+    if head_hash.is_empty() {
+        return Err("Error: you do not have the initial commit yet".to_string());
+    }
+    let head_tree: String = get_commit_tree(&RepoPath::Local, &head_hash);
+    let index: HashMap<String, String> = read_index();
+
+    // the tracked files as found in the worktree
+    let mut tracked_paths: BTreeSet<String> = index.keys().cloned().collect();
+    tracked_paths.extend(head_files().into_keys());
+    let mut worktree_files: BTreeMap<String, String> = BTreeMap::new();
+    for path in &tracked_paths {
+        if Path::new(path).is_file() {
+            let content: Vec<u8> = fs::read(path).expect("Failed to read file");
+            worktree_files.insert(path.clone(), hash_and_store("blob", &content));
+        }
+    }
+
+    let mut untracked_files: BTreeMap<String, String> = BTreeMap::new();
+    if include_untracked {
+        for path in untracked_paths(&index) {
+            let content: Vec<u8> = fs::read(&path).expect("Failed to read file");
+            untracked_files.insert(path, hash_and_store("blob", &content));
+        }
+    }
+
+    let index_tree: String = write_tree();
+    let worktree_tree: String = write_files_tree(&worktree_files);
+    if index_tree == head_tree && worktree_tree == head_tree && untracked_files.is_empty() {
+        return Ok(None);
+    }
+
+    // "WIP on main: 1234567 Subject" or "On main: <message>"
+    let branch: String = current_branch().unwrap_or("(no branch)".to_string());
+    let (subject, _): (String, String) = split_message(
+        cat_file(&RepoPath::Local, &head_hash)
+            .split_once("\n\n")
+            .map_or("", |(_, message)| message),
+    );
+    let summary: String = format!("{} {}", &head_hash[..7], subject);
+    let message: String = match message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!("WIP on {}: {}", branch, summary),
+    };
+
+    let index_commit: String = write_stash_commit(
+        &index_tree,
+        &[&head_hash],
+        &format!("index on {}: {}", branch, summary),
+    );
+    let mut parents: Vec<&str> = vec![&head_hash, &index_commit];
+    let untracked_commit: String;
+    if !untracked_files.is_empty() {
+        untracked_commit = write_stash_commit(
+            &write_files_tree(&untracked_files),
+            &[],
+            &format!("untracked files on {}: {}", branch, summary),
+        );
+        parents.push(&untracked_commit);
+    }
+    let stash_commit: String = write_stash_commit(&worktree_tree, &parents, &message);
+
+    update_ref(STASH_REF, &stash_commit, &message);
+
+    // go back to a clean worktree
+    reset_head("HEAD", ResetMode::Hard)?;
+    for path in untracked_files.keys() {
+        remove_worktree_file(path);
+    }
+
+    Ok(Some(StashEntry {
+        commit: stash_commit,
+        message,
+    }))
+}
+
+/// Returns the stashes, the latest first.
+pub fn stash_entries() -> Vec<StashEntry> {
+    read_reflog(STASH_REF)
+        .into_iter()
+        .map(|entry| StashEntry {
+            commit: entry.new,
+            message: entry.message,
+        })
+        .collect()
+}
+
+fn stash_entry(index: usize) -> Result<StashEntry, String> {
+    let entries: Vec<StashEntry> = stash_entries();
+    if entries.is_empty() {
+        return Err("No stash entries found.".to_string());
+    }
+    entries
+        .get(index)
+        .cloned()
+        .ok_or(format!("Error: stash@{{{}}} does not exist", index))
+}
+
+/// Formats the changes saved in a stash, compared to the commit it was made on.
+pub fn stash_show(index: usize, format: DiffFormat) -> Result<String, String> {
+    let entry: StashEntry = stash_entry(index)?;
+    let settings: DiffSettings = DiffSettings::from_config();
+    let file_diffs: Vec<FileDiff> = commit_diffs(&entry.commit, &settings);
+    Ok(format_diffs(&file_diffs, format, &settings))
+}
+
+/// Applies the changes of a stash to the worktree, merging them with the local changes.
+/// New files are staged; with `restore_index`, the staged changes are restored to the index
+/// too. Nothing is written if a local change or an untracked file would be lost. Returns the
+/// paths merged with conflicts.
+pub fn stash_apply(index: usize, restore_index: bool) -> Result<Vec<String>, String> {
+    let entry: StashEntry = stash_entry(index)?;
+    let stash_content: String = cat_file(&RepoPath::Local, &entry.commit);
+    let parents: Vec<&str> = stash_content
+        .lines()
+        .filter_map(|line| line.strip_prefix("parent "))
+        .collect();
+    let commit_files = |commit: &str| -> BTreeMap<String, String> {
+        read_tree(&RepoPath::Local, &get_commit_tree(&RepoPath::Local, commit))
+    };
+    let base_files: BTreeMap<String, String> = commit_files(parents[0]);
+    let stashed_files: BTreeMap<String, String> = commit_files(&entry.commit);
+    let stashed_index: BTreeMap<String, String> = commit_files(parents[1]);
+    let untracked_files: BTreeMap<String, String> = parents
+        .get(2)
+        .map_or(BTreeMap::new(), |commit| commit_files(commit));
+
+    // decide what to do with each changed path before writing anything
+    let mut updates: Vec<(&str, Option<&String>)> = Vec::new();
+    let mut merges: Vec<&str> = Vec::new();
+    let mut lost_paths: Vec<&str> = Vec::new();
+    let paths: BTreeSet<&String> = base_files.keys().chain(stashed_files.keys()).collect();
+    for path in paths {
+        let base_hash: Option<&String> = base_files.get(path);
+        let stashed_hash: Option<&String> = stashed_files.get(path);
+        let current_hash: Option<String> = worktree_hash(path);
+        if base_hash == stashed_hash || current_hash.as_ref() == stashed_hash {
+            continue;
+        }
+        if current_hash.as_ref() == base_hash {
+            updates.push((path, stashed_hash));
+        } else if base_hash.is_some() && stashed_hash.is_some() && current_hash.is_some() {
+            merges.push(path);
+        } else {
+            lost_paths.push(path);
+        }
+    }
+    for path in untracked_files.keys() {
+        if worktree_hash(path).is_some_and(|hash| Some(&hash) != untracked_files.get(path)) {
+            lost_paths.push(path);
+        }
+    }
+    if !lost_paths.is_empty() {
+        return Err(format!(
+            "error: Your local changes to the following files would be overwritten by stash apply:\n\t{}\nAborting",
+            lost_paths.join("\n\t")
+        ));
+    }
+
+    let mut index: HashMap<String, String> = read_index();
+    for (path, blob_hash) in updates {
+        match blob_hash {
+            Some(blob_hash) => {
+                write_worktree_file(path, &read_object(&RepoPath::Local, blob_hash).1);
+                // new files are staged so that they are not forgotten
+                if !base_files.contains_key(path) {
+                    index.insert(path.to_string(), blob_hash.clone());
+                }
+            }
+            None => remove_worktree_file(path),
+        }
+    }
+
+    let read_blob = |hash: &String| -> String {
+        String::from_utf8_lossy(&read_object(&RepoPath::Local, hash).1).into_owned()
+    };
+    let algorithm: DiffAlgorithm = DiffSettings::from_config().algorithm;
+    let mut conflicted_paths: Vec<String> = Vec::new();
+    for path in merges {
+        let ours: String =
+            String::from_utf8_lossy(&fs::read(path).expect("Failed to read file")).into_owned();
+        let merge_result: MergeResult = merge3(
+            &read_blob(&base_files[path]),
+            &ours,
+            &read_blob(&stashed_files[path]),
+            "Updated upstream",
+            "Stashed changes",
+            algorithm,
+        );
+        write_worktree_file(path, merge_result.content.as_bytes());
+        if merge_result.has_conflicts {
+            conflicted_paths.push(path.to_string());
+        }
+    }
+
+    for (path, blob_hash) in &untracked_files {
+        write_worktree_file(path, &read_object(&RepoPath::Local, blob_hash).1);
+    }
+
+    if restore_index {
+        let paths: BTreeSet<&String> = base_files.keys().chain(stashed_index.keys()).collect();
+        for path in paths {
+            if base_files.get(path) == stashed_index.get(path) {
+                continue;
+            }
+            match stashed_index.get(path) {
+                Some(blob_hash) => index.insert(path.clone(), blob_hash.clone()),
+                None => index.remove(path),
+            };
+        }
+    }
+    write_index(&index);
+
+    Ok(conflicted_paths)
+}
+
+/// Removes a stash from the reflog, and moves `refs/stash` to the next one.
+pub fn stash_drop(index: usize) -> Result<(), String> {
+    let entry: StashEntry = stash_entry(index)?;
+    delete_reflog_entry(STASH_REF, index)?;
+
+    // the ref is moved without a new reflog entry, which would be a stash of its own
+    match stash_entries().first() {
+        None => delete_ref(STASH_REF),
+        Some(latest) if index == 0 => fs::write(Path::new(".rgit").join(STASH_REF), &latest.commit)
+            .expect("Failed to update the stash reference"),
+        Some(_) => {}
+    }
+
+    println!("Dropped stash@{{{}}} ({})", index, entry.commit);
+    Ok(())
+}
+
+/// Lists the files of the worktree that are not in the index.
+fn untracked_paths(index: &HashMap<String, String>) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    let mut directories: Vec<PathBuf> = vec![PathBuf::new()];
+    while let Some(directory) = directories.pop() {
+        let read_path: &Path = if directory.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &directory
+        };
+        for dir_entry in fs::read_dir(read_path).expect("Failed to read directory") {
+            let dir_entry: fs::DirEntry = dir_entry.expect("Failed to read directory entry");
+            let path: PathBuf = directory.join(dir_entry.file_name());
+            if path == Path::new(".rgit") {
+                continue;
+            }
+            if path.is_dir() {
+                directories.push(path);
+            } else {
+                let path: String = path.to_string_lossy().into_owned();
+                if !index.contains_key(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+    paths.sort();
+    paths
+}
+
+fn write_stash_commit(tree_hash: &str, parents: &[&str], message: &str) -> String {
+    commit_tree(
+        message,
+        Some(&current_identity()),
+        tree_hash.to_string(),
+        parents,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::commit;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::add_index;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+
+    #[test]
+    fn test_stash_push_and_pop_restore_index_and_worktree() {
+        setup_test_repo();
+
+        fs::write("a.txt", "one\ntwo\nthree\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        commit("Initial commit", Some("John Doe"));
+        let head_index: HashMap<String, String> = read_index();
+
+        // a staged new file, an unstaged change and an untracked file
+        fs::write("staged.txt", "staged\n").unwrap();
+        add_index("staged.txt", &hash_object("staged.txt"));
+        let stashed_index: HashMap<String, String> = read_index();
+        fs::write("a.txt", "one\ntwo\nTHREE\n").unwrap();
+        fs::write("untracked.txt", "untracked\n").unwrap();
+
+        let entry: StashEntry = stash_push(Some("work in progress"), true).unwrap().unwrap();
+        assert_eq!(entry.message, "On main: work in progress");
+        assert_eq!(stash_entries(), vec![entry]);
+        assert_eq!(read_index(), head_index);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "one\ntwo\nthree\n");
+        assert!(!Path::new("staged.txt").exists());
+        assert!(!Path::new("untracked.txt").exists());
+        assert_eq!(stash_push(None, true), Ok(None));
+
+        let stat: String = stash_show(0, DiffFormat::Stat).unwrap();
+        assert!(stat.contains(" a.txt      | 2 +-\n"));
+        assert!(stat.contains(" staged.txt | 1 +\n"));
+
+        // a local change to another part of the file is merged with the stashed one
+        fs::write("a.txt", "ONE\ntwo\nthree\n").unwrap();
+        assert_eq!(stash_apply(0, true), Ok(Vec::new()));
+        stash_drop(0).unwrap();
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "ONE\ntwo\nTHREE\n");
+        assert_eq!(fs::read_to_string("staged.txt").unwrap(), "staged\n");
+        assert_eq!(fs::read_to_string("untracked.txt").unwrap(), "untracked\n");
+        assert_eq!(read_index(), stashed_index);
+        assert!(stash_entries().is_empty());
+        assert!(!Path::new(".rgit/refs/stash").exists());
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_stash_list_and_drop_keep_order() {
+        setup_test_repo();
+
+        fs::write("a.txt", "zero\n").unwrap();
+        add_index("a.txt", &hash_object("a.txt"));
+        commit("Initial commit", Some("John Doe"));
+
+        for content in ["first\n", "second\n", "third\n"] {
+            fs::write("a.txt", content).unwrap();
+            stash_push(Some(content.trim()), false).unwrap();
+        }
+        let messages = || -> Vec<String> {
+            stash_entries()
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect()
+        };
+        assert_eq!(
+            messages(),
+            vec!["On main: third", "On main: second", "On main: first"]
+        );
+
+        stash_drop(1).unwrap();
+        stash_drop(0).unwrap();
+        assert_eq!(messages(), vec!["On main: first"]);
+        assert_eq!(
+            fs::read_to_string(".rgit/refs/stash").unwrap(),
+            stash_entries()[0].commit
+        );
+        assert_eq!(stash_apply(0, false), Ok(Vec::new()));
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "first\n");
+
+        remove_test_repo();
+    }
+}
@@ -1,19 +1,37 @@
+use crate::commands::reflog::append_reflog;
+use crate::commands::update_ref::lock_ref;
+use crate::utils::{read_ref, verify_full_ref_name};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn symbolic_ref(ref_name: &str, target_ref: &str) {
+/// Attaches a symbolic ref to another ref. When the hash it resolves to changes, the move is
+/// recorded in its reflog.
+pub fn symbolic_ref(ref_name: &str, target_ref: &str, reason: &str) {
+    for name in [ref_name, target_ref] {
+        if let Err(error) = verify_full_ref_name(name) {
+            eprintln!("fatal: {}", error);
+            std::process::exit(1);
+        }
+    }
+    let old_hash: String = read_ref(ref_name).unwrap_or_default();
+
     // construct the full path to the symbolic reference file (e.g., ".rgit/HEAD")
     let ref_path: PathBuf = Path::new(".rgit").join(ref_name);
 
-    // create necessary directories if they don't exist
-    if let Some(parent_dir) = ref_path.parent() {
-        fs::create_dir_all(parent_dir)
-            .expect("Failed to create parent directories for the reference");
-    }
-
-    // write the target reference (e.g., "ref: refs/heads/main") to the file
+    // write the target reference (e.g., "ref: refs/heads/main") to the lock file, then
+    // move it in place
+    let lock_path: PathBuf = lock_ref(ref_name).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
     let symbolic_content: String = format!("ref: {}", target_ref);
-    fs::write(&ref_path, symbolic_content).expect("Failed to update symbolic reference");
+    fs::write(&lock_path, symbolic_content).expect("Failed to update symbolic reference");
+    fs::rename(&lock_path, &ref_path).expect("Failed to update symbolic reference");
+
+    let new_hash: String = read_ref(target_ref).unwrap_or_default();
+    if !new_hash.is_empty() {
+        append_reflog(ref_name, &old_hash, &new_hash, reason);
+    }
 
     println!(
         "Updated symbolic reference '{}' to point to '{}'",
@@ -34,7 +52,7 @@ mod tests {
         // initialize a symbolic HEAD reference pointing to the `refs/heads/main` branch
         let ref_name: &str = "HEAD";
         let target_ref: &str = "refs/heads/main";
-        symbolic_ref(ref_name, target_ref);
+        symbolic_ref(ref_name, target_ref, "symbolic-ref");
 
         // check that the `.rgit/HEAD` file contains the correct symbolic reference
         let head_content: String = fs::read_to_string(".rgit/HEAD").unwrap();
@@ -0,0 +1,232 @@
+use crate::commands::cat_file::read_object;
+use crate::commands::rev_parse::rev_parse;
+use crate::commands::update_ref::update_ref;
+use crate::utils::{
+    committer_signature, delete_ref, glob_match, hash_and_store, list_refs, read_ref,
+    verify_ref_name, RepoPath,
+};
+
+/// What the `tag` command does.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TagAction {
+    /// list the tags matching a pattern, all tags without pattern
+    List {
+        pattern: Option<String>,
+    },
+    /// create a lightweight tag, or an annotated tag object when there is a message
+    Create {
+        name: String,
+        target: String,
+        message: Option<String>,
+        force: bool,
+    },
+    Delete {
+        names: Vec<String>,
+    },
+}
+
+pub fn parse_tag_options(args: &[String]) -> TagAction {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit tag [-l [<pattern>]]");
+        eprintln!("       rgit tag [-f] [-a -m <message>] <name> [<rev>]");
+        eprintln!("       rgit tag -d <name>...");
+        std::process::exit(1);
+    };
+
+    let mut list: bool = false;
+    let mut delete: bool = false;
+    let mut annotate: bool = false;
+    let mut force: bool = false;
+    let mut message: Option<String> = None;
+    let mut names: Vec<String> = Vec::new();
+    let mut index: usize = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "-l" | "--list" => list = true,
+            "-d" | "--delete" => delete = true,
+            "-a" | "--annotate" => annotate = true,
+            "-f" | "--force" => force = true,
+            "-m" | "--message" if index + 1 < args.len() => {
+                message = Some(args[index + 1].clone());
+                index += 1;
+            }
+            arg if arg.starts_with('-') => usage(),
+            arg => names.push(arg.to_string()),
+        }
+        index += 1;
+    }
+
+    if annotate && message.is_none() {
+        eprintln!("Error: an annotated tag needs a message (-m <message>).");
+        std::process::exit(1);
+    }
+
+    match (list, delete, names.as_slice()) {
+        (_, false, []) => TagAction::List { pattern: None },
+        (true, false, [pattern]) => TagAction::List {
+            pattern: Some(pattern.clone()),
+        },
+        (false, true, names) => TagAction::Delete {
+            names: names.to_vec(),
+        },
+        (false, false, [name]) | (false, false, [name, _]) => TagAction::Create {
+            name: name.clone(),
+            target: names.get(1).cloned().unwrap_or("HEAD".to_string()),
+            message,
+            force,
+        },
+        _ => usage(),
+    }
+}
+
+pub fn tag(action: &TagAction) {
+    let result: Result<(), String> = match action {
+        TagAction::List { pattern } => {
+            for name in list_tags(pattern.as_deref()) {
+                println!("{}", name);
+            }
+            Ok(())
+        }
+        TagAction::Create {
+            name,
+            target,
+            message,
+            force,
+        } => create_tag(name, target, message.as_deref(), *force).map(|_| ()),
+        TagAction::Delete { names } => names.iter().try_for_each(|name| {
+            let hash: String = delete_tag(name)?;
+            println!("Deleted tag '{}' (was {})", name, &hash[..7]);
+            Ok(())
+        }),
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn tag_ref(name: &str) -> String {
+    format!("refs/tags/{}", name)
+}
+
+/// Returns the names of the tags matching a shell pattern (all tags without pattern), sorted.
+pub fn list_tags(pattern: Option<&str>) -> Vec<String> {
+    list_refs("refs/tags/")
+        .into_iter()
+        .map(|ref_name| ref_name.trim_start_matches("refs/tags/").to_string())
+        .filter(|name| pattern.is_none_or(|pattern| glob_match(pattern, name)))
+        .collect()
+}
+
+/// Creates `refs/tags/<name>` pointing to `target`. With a message, the ref points to a new
+/// tag object recording the target, the tagger (the committer identity, which must be set)
+/// and the message. Returns the hash the ref points to.
+pub fn create_tag(
+    name: &str,
+    target: &str,
+    message: Option<&str>,
+    force: bool,
+) -> Result<String, String> {
+    verify_ref_name(name).map_err(|_| format!("fatal: '{}' is not a valid tag name", name))?;
+    if !force && read_ref(&tag_ref(name)).is_some() {
+        return Err(format!("fatal: tag '{}' already exists", name));
+    }
+    let target_hash: String = rev_parse(target)
+        .map_err(|_| format!("fatal: failed to resolve '{}' as a valid ref", target))?;
+
+    let tag_hash: String = match message {
+        Some(message) => {
+            // "object <hash>\ntype <type>\ntag <name>\ntagger <tagger>\n\n<message>\n"
+            let (object_type, _): (String, Vec<u8>) = read_object(&RepoPath::Local, &target_hash);
+            let tag_content: String = format!(
+                "object {}\ntype {}\ntag {}\ntagger {}\n\n{}\n",
+                target_hash,
+                object_type,
+                name,
+                committer_signature(None)?,
+                message.trim_end()
+            );
+            hash_and_store("tag", tag_content.as_bytes())
+        }
+        None => target_hash,
+    };
+
+    update_ref(&tag_ref(name), &tag_hash, "tag");
+    Ok(tag_hash)
+}
+
+/// Deletes a tag. Returns the hash its ref pointed to.
+pub fn delete_tag(name: &str) -> Result<String, String> {
+    let hash: String =
+        read_ref(&tag_ref(name)).ok_or(format!("error: tag '{}' not found.", name))?;
+    delete_ref(&tag_ref(name));
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::cat_file::cat_file;
+    use crate::commands::commit::commit;
+    use crate::commands::get_head_hash::get_head_hash;
+    use crate::commands::hash_object::hash_object;
+    use crate::commands::update_index::add_index;
+    use crate::config::get_config;
+    use crate::utils::resolve_revision;
+    use crate::utils::tests::{remove_test_repo, set_test_identity, setup_test_repo};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_lightweight_and_annotated_tags() {
+        setup_test_repo();
+
+        fs::write("file.txt", "one\n").unwrap();
+        add_index("file.txt", &hash_object("file.txt"));
+        commit("First commit", Some("John Doe"));
+        let commit_hash: String = get_head_hash();
+
+        // a lightweight tag is a ref to the commit
+        assert_eq!(
+            create_tag("v0.1", "HEAD", None, false),
+            Ok(commit_hash.clone())
+        );
+        assert!(create_tag("v0.1", "HEAD", None, false).is_err());
+        assert!(create_tag("../v0.1", "HEAD", None, false).is_err());
+
+        // an annotated tag needs a tagger identity, and the repository has none
+        fs::write(
+            ".rgit/config",
+            "[core]\n    bare = false\n[user]\n    email = nobody@example.com\n",
+        )
+        .unwrap();
+        assert_eq!(get_config("user.name"), None);
+        assert!(create_tag("v1.0", "v0.1", Some("First release"), false).is_err());
+        assert_eq!(read_ref("refs/tags/v1.0"), None);
+        set_test_identity();
+
+        // an annotated tag is a tag object, peeled to the commit when used as a revision
+        let tag_hash: String = create_tag("v1.0", "v0.1", Some("First release"), false).unwrap();
+        assert_eq!(read_ref("refs/tags/v1.0"), Some(tag_hash.clone()));
+        let tag_content: String = cat_file(&RepoPath::Local, &tag_hash);
+        assert!(tag_content.starts_with(&format!(
+            "object {}\ntype commit\ntag v1.0\ntagger Config User <config@example.com> ",
+            commit_hash
+        )));
+        assert!(tag_content.ends_with("\n\nFirst release\n"));
+        assert_eq!(resolve_revision("v1.0"), commit_hash);
+        assert_eq!(rev_parse("v1.0"), Ok(tag_hash));
+
+        create_tag("v1.1-rc1", "HEAD", None, false).unwrap();
+        assert_eq!(list_tags(None), vec!["v0.1", "v1.0", "v1.1-rc1"]);
+        assert_eq!(list_tags(Some("v1.*")), vec!["v1.0", "v1.1-rc1"]);
+        assert_eq!(list_tags(Some("v?.?")), vec!["v0.1", "v1.0"]);
+
+        assert_eq!(delete_tag("v0.1"), Ok(commit_hash));
+        assert!(delete_tag("v0.1").is_err());
+        assert!(!Path::new(".rgit/refs/tags/v0.1").exists());
+        assert!(Path::new(".rgit/refs/tags").exists());
+
+        remove_test_repo();
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-fn read_index() -> HashMap<String, String> {
+pub fn read_index() -> HashMap<String, String> {
     let index_path: PathBuf = Path::new(".rgit").join("index");
     let mut index_map: HashMap<String, String> = HashMap::new();
 
@@ -20,7 +20,7 @@ fn read_index() -> HashMap<String, String> {
     index_map
 }
 
-fn write_index(index_map: &HashMap<String, String>) {
+pub fn write_index(index_map: &HashMap<String, String>) {
     let index_path: PathBuf = Path::new(".rgit").join("index");
 
     let new_index_content: String = index_map
@@ -1,28 +1,327 @@
+use crate::commands::reflog::{append_reflog, NULL_HASH};
+use crate::commands::rev_parse::rev_parse;
+use crate::utils::{delete_ref, object_exists, read_ref, verify_full_ref_name};
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
-pub fn update_ref(ref_name: &str, commit_hash: &str) {
-    // construct the full path to the reference (e.g., ".rgit/refs/master")
-    let ref_path: PathBuf = Path::new(".rgit").join(ref_name);
+/// The change a transaction makes to a ref.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefChange {
+    /// point the ref to a hash, creating it if needed
+    Update(String),
+    Delete,
+    /// only check the old value of the ref
+    Verify,
+}
+
+/// A change to a ref, made only if the ref has the expected old value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub ref_name: String,
+    pub change: RefChange,
+    /// the value the ref must have, `NULL_HASH` if it must not exist; not checked when `None`
+    pub old: Option<String>,
+}
+
+/// Options of the `update-ref` command.
+pub struct UpdateRefOptions {
+    /// the reason recorded in the reflogs
+    pub reason: String,
+    /// read a transaction from the standard input instead of a single update
+    pub stdin: bool,
+    pub update: Option<RefUpdate>,
+}
+
+pub fn parse_update_ref_options(args: &[String]) -> UpdateRefOptions {
+    let usage = || -> ! {
+        eprintln!("Usage: rgit update-ref [-m <reason>] <ref_name> <new_hash> [<old_hash>]");
+        eprintln!("       rgit update-ref [-m <reason>] -d <ref_name> [<old_hash>]");
+        eprintln!("       rgit update-ref [-m <reason>] --stdin");
+        std::process::exit(1);
+    };
+
+    let mut options: UpdateRefOptions = UpdateRefOptions {
+        reason: "update-ref".to_string(),
+        stdin: false,
+        update: None,
+    };
+    let mut delete: bool = false;
+    let mut values: Vec<String> = Vec::new();
+    let mut index: usize = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "-m" if index + 1 < args.len() => {
+                options.reason = args[index + 1].clone();
+                index += 1;
+            }
+            "-d" => delete = true,
+            "--stdin" => options.stdin = true,
+            arg if arg.starts_with('-') => usage(),
+            arg => values.push(arg.to_string()),
+        }
+        index += 1;
+    }
+
+    options.update = match (options.stdin, delete, values.as_slice()) {
+        (true, false, []) => None,
+        (false, false, [ref_name, new]) | (false, false, [ref_name, new, _]) => Some(RefUpdate {
+            ref_name: ref_name.clone(),
+            change: RefChange::Update(new.clone()),
+            old: values.get(2).cloned(),
+        }),
+        (false, true, [ref_name]) | (false, true, [ref_name, _]) => Some(RefUpdate {
+            ref_name: ref_name.clone(),
+            change: RefChange::Delete,
+            old: values.get(1).cloned(),
+        }),
+        _ => usage(),
+    };
+    options
+}
+
+pub fn update_ref_command(options: &UpdateRefOptions) {
+    let updates: Result<Vec<RefUpdate>, String> = match &options.update {
+        Some(update) => Ok(vec![update.clone()]),
+        None => {
+            let mut input: String = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .expect("Failed to read the standard input");
+            parse_transaction(&input)
+        }
+    };
+    if let Err(error) = updates.and_then(|updates| update_refs(&updates, &options.reason)) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Parses the commands of `update-ref --stdin`, one per line:
+///
+/// ```text
+/// update <ref> <new> [<old>]
+/// create <ref> <new>
+/// delete <ref> [<old>]
+/// verify <ref> [<old>]
+/// ```
+pub fn parse_transaction(input: &str) -> Result<Vec<RefUpdate>, String> {
+    let mut updates: Vec<RefUpdate> = Vec::new();
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let update: Option<RefUpdate> = match words.as_slice() {
+            ["update", ref_name, new] | ["update", ref_name, new, _] => Some(RefUpdate {
+                ref_name: ref_name.to_string(),
+                change: RefChange::Update(new.to_string()),
+                old: words.get(3).map(|old| old.to_string()),
+            }),
+            ["create", ref_name, new] => Some(RefUpdate {
+                ref_name: ref_name.to_string(),
+                change: RefChange::Update(new.to_string()),
+                old: Some(NULL_HASH.to_string()),
+            }),
+            ["delete", ref_name] | ["delete", ref_name, _] => Some(RefUpdate {
+                ref_name: ref_name.to_string(),
+                change: RefChange::Delete,
+                old: words.get(2).map(|old| old.to_string()),
+            }),
+            // without old value, verify checks that the ref does not exist
+            ["verify", ref_name] | ["verify", ref_name, _] => Some(RefUpdate {
+                ref_name: ref_name.to_string(),
+                change: RefChange::Verify,
+                old: Some(words.get(2).unwrap_or(&NULL_HASH).to_string()),
+            }),
+            _ => None,
+        };
+        updates.push(update.ok_or(format!("fatal: invalid update-ref command: {}", line))?);
+    }
+    Ok(updates)
+}
+
+/// Points a ref to a hash, recording the move and its reason in the reflog of the ref, and
+/// in the reflog of HEAD when HEAD is attached to the ref. Exits if the ref is locked.
+pub fn update_ref(ref_name: &str, commit_hash: &str, reason: &str) {
+    let update: RefUpdate = RefUpdate {
+        ref_name: ref_name.to_string(),
+        change: RefChange::Update(commit_hash.to_string()),
+        old: None,
+    };
+    if let Err(error) = update_refs(&[update], reason) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Applies the changes to refs all or nothing. The new and old values may be any revision
+/// (see `rev_parse`), and are resolved to object hashes first. Each ref is then locked by
+/// creating `<ref>.lock`, the old values are checked, and only then the new values are moved
+/// in place. If a ref is already locked or does not have its expected value, nothing changes.
+pub fn update_refs(updates: &[RefUpdate], reason: &str) -> Result<(), String> {
+    let updates: Vec<RefUpdate> = updates
+        .iter()
+        .map(resolve_update)
+        .collect::<Result<Vec<RefUpdate>, String>>()?;
+    let updates: &[RefUpdate] = &updates;
+
+    let mut lock_paths: Vec<PathBuf> = Vec::new();
+    let prepared: Result<(), String> = prepare_updates(updates, &mut lock_paths);
+    if let Err(error) = prepared {
+        for lock_path in &lock_paths {
+            // the lock files are ours, and may already be gone if the repository is removed
+            let _ = fs::remove_file(lock_path);
+        }
+        return Err(error);
+    }
+
+    for (update, lock_path) in updates.iter().zip(&lock_paths) {
+        let ref_path: PathBuf = Path::new(".rgit").join(&update.ref_name);
+        let old_hash: String = read_ref(&update.ref_name).unwrap_or_default();
+        match &update.change {
+            RefChange::Update(new_hash) => {
+                fs::rename(lock_path, &ref_path).expect("Failed to update reference");
+                append_reflog(&update.ref_name, &old_hash, new_hash, reason);
+                if update.ref_name != "HEAD"
+                    && head_target().as_deref() == Some(update.ref_name.as_str())
+                {
+                    append_reflog("HEAD", &old_hash, new_hash, reason);
+                }
+                println!(
+                    "Updated reference '{}' to point to commit {}",
+                    update.ref_name, new_hash
+                );
+            }
+            RefChange::Delete => {
+                fs::remove_file(lock_path).expect("Failed to remove the lock file");
+                if !old_hash.is_empty() {
+                    delete_ref(&update.ref_name);
+                }
+                println!("Deleted reference '{}'", update.ref_name);
+            }
+            RefChange::Verify => {
+                fs::remove_file(lock_path).expect("Failed to remove the lock file");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the new and old values of an update to full hashes of existing objects. The zero
+/// hash (or an empty value) stands for a ref that does not exist: as the new value, the ref is
+/// deleted.
+fn resolve_update(update: &RefUpdate) -> Result<RefUpdate, String> {
+    let resolve = |value: &str| -> Result<String, String> {
+        if value.is_empty() || value == NULL_HASH {
+            return Ok(NULL_HASH.to_string());
+        }
+        match rev_parse(value) {
+            Ok(hash) if hash.len() == 40 && object_exists(&hash) => Ok(hash),
+            _ => Err(format!(
+                "fatal: {}: not a valid object name for '{}'",
+                value, update.ref_name
+            )),
+        }
+    };
+
+    let change: RefChange = match &update.change {
+        RefChange::Update(new) => match resolve(new)?.as_str() {
+            NULL_HASH => RefChange::Delete,
+            new_hash => RefChange::Update(new_hash.to_string()),
+        },
+        change => change.clone(),
+    };
+    let old: Option<String> = match &update.old {
+        Some(old) => Some(resolve(old)?),
+        None => None,
+    };
+    Ok(RefUpdate {
+        ref_name: update.ref_name.clone(),
+        change,
+        old,
+    })
+}
+
+/// Locks every ref of a transaction, checks its old value, and writes its new value in the
+/// lock file. The lock files created so far are pushed to `lock_paths`, also on error.
+fn prepare_updates(updates: &[RefUpdate], lock_paths: &mut Vec<PathBuf>) -> Result<(), String> {
+    for (index, update) in updates.iter().enumerate() {
+        if updates[..index]
+            .iter()
+            .any(|other| other.ref_name == update.ref_name)
+        {
+            return Err(format!(
+                "fatal: multiple updates for ref '{}' not allowed",
+                update.ref_name
+            ));
+        }
 
-    // create necessary directories if they don't exist
-    if let Some(parent_dir) = ref_path.parent() {
+        verify_full_ref_name(&update.ref_name).map_err(|error| format!("fatal: {}", error))?;
+        let lock_path: PathBuf = lock_ref(&update.ref_name)?;
+        lock_paths.push(lock_path.clone());
+
+        let current_hash: String = read_ref(&update.ref_name).unwrap_or_default();
+        match update.old.as_deref() {
+            Some(NULL_HASH) if !current_hash.is_empty() => {
+                return Err(format!(
+                    "fatal: cannot lock ref '{}': reference already exists",
+                    update.ref_name
+                ));
+            }
+            Some(NULL_HASH) | None => {}
+            Some(_) if current_hash.is_empty() => {
+                return Err(format!(
+                    "fatal: cannot lock ref '{}': unable to resolve reference",
+                    update.ref_name
+                ));
+            }
+            Some(old_hash) if old_hash != current_hash => {
+                return Err(format!(
+                    "fatal: cannot lock ref '{}': is at {} but expected {}",
+                    update.ref_name, current_hash, old_hash
+                ));
+            }
+            Some(_) => {}
+        }
+
+        if let RefChange::Update(new_hash) = &update.change {
+            fs::write(&lock_path, new_hash).expect("Failed to write the lock file");
+        }
+    }
+    Ok(())
+}
+
+/// Takes the lock of a ref by creating `<ref>.lock`, which fails if it already exists. The
+/// lock is released by renaming it over the ref or removing it.
+pub fn lock_ref(ref_name: &str) -> Result<PathBuf, String> {
+    let lock_path: PathBuf = Path::new(".rgit").join(format!("{}.lock", ref_name));
+    if let Some(parent_dir) = lock_path.parent() {
         fs::create_dir_all(parent_dir)
             .expect("Failed to create parent directories for the reference");
     }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock_path)
+        .map_err(|error| {
+            format!(
+                "fatal: Unable to create '{}': {}.\nAnother rgit process seems to be running in this repository; if not, remove the file.",
+                lock_path.display(),
+                error
+            )
+        })?;
+    Ok(lock_path)
+}
 
-    // write the commit hash to the reference file
-    fs::write(&ref_path, commit_hash).expect("Failed to update reference");
-
-    println!(
-        "Updated reference '{}' to point to commit {}",
-        ref_name, commit_hash
-    );
+/// Returns the ref HEAD is attached to, if any.
+pub fn head_target() -> Option<String> {
+    let head: String = fs::read_to_string(Path::new(".rgit").join("HEAD")).ok()?;
+    head.strip_prefix("ref: ")
+        .map(|target| target.trim().to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::hash_and_store;
     use crate::utils::tests::{remove_test_repo, setup_test_repo};
     use std::fs;
     use std::path::Path;
@@ -31,12 +330,12 @@ mod tests {
     fn test_update_ref_creates_reference_file() {
         setup_test_repo();
 
-        // update a reference (e.g., "refs/heads/main") with a dummy commit hash
+        // update a reference (e.g., "refs/heads/main") with the hash of a stored object
         let ref_name: &str = "refs/heads/main";
-        let commit_hash: &str = "1234567890abcdef1234567890abcdef12345678"; // dummy commit hash
+        let commit_hash: &str = &hash_and_store("blob", b"content");
 
         // call the `update_ref` function
-        update_ref(ref_name, commit_hash);
+        update_ref(ref_name, commit_hash, "update-ref");
 
         // check that the reference file is created with the correct content
         let ref_file_path: String = format!(".rgit/{}", ref_name); // the full path to the reference file
@@ -56,4 +355,86 @@ mod tests {
 
         remove_test_repo();
     }
+
+    #[test]
+    fn test_update_refs_is_all_or_nothing() {
+        setup_test_repo();
+
+        let first_hash: &str = &hash_and_store("blob", b"first");
+        let second_hash: &str = &hash_and_store("blob", b"second");
+        update_ref("refs/heads/main", first_hash, "update-ref");
+
+        // a wrong old value or a locked ref leaves every ref untouched
+        let transaction: Vec<RefUpdate> = parse_transaction(&format!(
+            "create refs/heads/topic {}\nupdate refs/heads/main {} {}\n",
+            first_hash, second_hash, second_hash
+        ))
+        .unwrap();
+        assert!(update_refs(&transaction, "test").is_err());
+        assert!(!Path::new(".rgit/refs/heads/topic").exists());
+        assert!(!Path::new(".rgit/refs/heads/topic.lock").exists());
+
+        fs::write(".rgit/refs/heads/main.lock", "").unwrap();
+        let transaction: Vec<RefUpdate> = parse_transaction(&format!(
+            "create refs/heads/topic {}\nupdate refs/heads/main {} {}\n",
+            first_hash, second_hash, first_hash
+        ))
+        .unwrap();
+        assert!(update_refs(&transaction, "test").is_err());
+        assert!(!Path::new(".rgit/refs/heads/topic").exists());
+
+        // with the lock released, the transaction goes through
+        fs::remove_file(".rgit/refs/heads/main.lock").unwrap();
+        update_refs(&transaction, "test").unwrap();
+        assert_eq!(read_ref("refs/heads/main"), Some(second_hash.to_string()));
+        assert_eq!(read_ref("refs/heads/topic"), Some(first_hash.to_string()));
+        assert!(!Path::new(".rgit/refs/heads/main.lock").exists());
+
+        // deletion, checked against the old value
+        let transaction: Vec<RefUpdate> = parse_transaction(&format!(
+            "verify refs/heads/main {}\ndelete refs/heads/topic {}\n",
+            second_hash, first_hash
+        ))
+        .unwrap();
+        update_refs(&transaction, "test").unwrap();
+        assert_eq!(read_ref("refs/heads/topic"), None);
+        assert!(parse_transaction("move refs/heads/main").is_err());
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_update_refs_resolves_revisions() {
+        setup_test_repo();
+
+        let first_hash: String = hash_and_store("blob", b"first");
+        let second_hash: String = hash_and_store("blob", b"second");
+        update_ref("refs/heads/main", &first_hash, "update-ref");
+        update_ref("refs/heads/other", &second_hash, "update-ref");
+
+        // symbolic values are stored as the hashes they name
+        let update = |new: &str, old: Option<&str>| -> Result<(), String> {
+            let update: RefUpdate = RefUpdate {
+                ref_name: "refs/heads/topic".to_string(),
+                change: RefChange::Update(new.to_string()),
+                old: old.map(|old| old.to_string()),
+            };
+            update_refs(&[update], "test")
+        };
+        update("HEAD", Some(NULL_HASH)).unwrap();
+        assert_eq!(read_ref("refs/heads/topic"), Some(first_hash.clone()));
+        update("other", Some("main")).unwrap();
+        assert_eq!(read_ref("refs/heads/topic"), Some(second_hash.clone()));
+
+        // the old value is compared once resolved
+        assert!(update("main", Some("main")).is_err());
+        assert_eq!(read_ref("refs/heads/topic"), Some(second_hash.clone()));
+
+        // unknown revisions and missing objects are refused
+        assert!(update("nothing", None).is_err());
+        assert!(update("1234567890abcdef1234567890abcdef12345678", None).is_err());
+        assert_eq!(read_ref("refs/heads/topic"), Some(second_hash));
+
+        remove_test_repo();
+    }
 }
@@ -1,4 +1,5 @@
 use crate::utils::hash_and_store;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -6,7 +7,7 @@ pub fn write_tree() -> String {
     let index_path: PathBuf = Path::new(".rgit").join("index");
     let index_content: String = fs::read_to_string(&index_path).expect("Failed to read index");
 
-    let mut tree_entries: Vec<String> = Vec::new();
+    let mut files: Vec<(&str, &str)> = Vec::new();
 
     for line in index_content.lines() {
         let parts: Vec<&str> = line.split_whitespace().collect();
@@ -15,11 +16,51 @@ pub fn write_tree() -> String {
             continue;
         }
         let (file_name, blob_hash): (&str, &str) = (parts[0], parts[1]);
-        let entry: String = format!("100644 blob {} {}\n", blob_hash, file_name);
-        tree_entries.push(entry);
+        files.push((file_name, blob_hash));
     }
 
-    let tree_data: String = tree_entries.join("");
+    write_subtree(&files)
+}
+
+/// Writes the tree of a set of files, given by their paths and blob hashes, without going
+/// through the index.
+pub fn write_files_tree(files: &BTreeMap<String, String>) -> String {
+    let files: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(path, blob_hash)| (path.as_str(), blob_hash.as_str()))
+        .collect();
+    write_subtree(&files)
+}
+
+/// Writes the tree of a directory from the paths (relative to it) and blob hashes of its
+/// files. Subdirectories are written as their own trees, so entry names never contain `/`.
+fn write_subtree(files: &[(&str, &str)]) -> String {
+    let mut blobs: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut subdirectories: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+
+    for (path, blob_hash) in files {
+        match path.split_once('/') {
+            Some((directory, rest)) => subdirectories
+                .entry(directory)
+                .or_default()
+                .push((rest, blob_hash)),
+            None => {
+                blobs.insert(path, blob_hash);
+            }
+        }
+    }
+
+    // entries are sorted by name
+    let mut tree_entries: BTreeMap<&str, String> = BTreeMap::new();
+    for (name, blob_hash) in blobs {
+        tree_entries.insert(name, format!("100644 blob {} {}\n", blob_hash, name));
+    }
+    for (name, subdirectory_files) in subdirectories {
+        let subtree_hash: String = write_subtree(&subdirectory_files);
+        tree_entries.insert(name, format!("040000 tree {} {}\n", subtree_hash, name));
+    }
+
+    let tree_data: String = tree_entries.into_values().collect();
     let tree_hash: String = hash_and_store("tree", &tree_data.into_bytes());
 
     tree_hash
@@ -0,0 +1,107 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns the value of a config key written `section.name` (or `section.subsection.name`),
+/// looking first in the repository config `.rgit/config`, then in the user config
+/// `~/.rgitconfig`. Tests only read the repository config, so that the user's settings do not
+/// leak into them.
+///
+/// Both files use git's format:
+/// ```text
+/// [diff]
+///     algorithm = patience
+/// ```
+pub fn get_config(key: &str) -> Option<String> {
+    let repo_config: PathBuf = Path::new(".rgit").join("config");
+    if let Some(value) = read_config_value(&repo_config, key) {
+        return Some(value);
+    }
+
+    if cfg!(test) {
+        return None;
+    }
+    let home: String = env::var("HOME").ok()?;
+    read_config_value(&Path::new(&home).join(".rgitconfig"), key)
+}
+
+/// Reads the last value of `key` in a config file, if both exist.
+fn read_config_value(path: &Path, key: &str) -> Option<String> {
+    let content: String = fs::read_to_string(path).ok()?;
+    let (wanted_section, wanted_name): (String, String) = split_key(key)?;
+
+    let mut section: String = String::new();
+    let mut value: Option<String> = None;
+    for line in content.lines() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        // section header: [section] or [section "subsection"]
+        if let Some(header) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            section = parse_section_header(header);
+            continue;
+        }
+
+        let (name, raw_value): (&str, &str) = match line.split_once('=') {
+            Some((name, raw_value)) => (name.trim(), raw_value.trim()),
+            // a key without value is a boolean set to true
+            None => (line, "true"),
+        };
+        if section == wanted_section && name.to_lowercase() == wanted_name {
+            value = Some(raw_value.trim_matches('"').to_string());
+        }
+    }
+
+    value
+}
+
+/// Splits `section.subsection.name` into the section part and the lowercased name.
+/// Only the section and the name are case-insensitive, like in git.
+fn split_key(key: &str) -> Option<(String, String)> {
+    let (section, name): (&str, &str) = key.rsplit_once('.')?;
+    let section: String = match section.split_once('.') {
+        Some((main, subsection)) => format!("{}.{}", main.to_lowercase(), subsection),
+        None => section.to_lowercase(),
+    };
+    Some((section, name.to_lowercase()))
+}
+
+fn parse_section_header(header: &str) -> String {
+    match header.split_once(' ') {
+        Some((main, subsection)) => format!(
+            "{}.{}",
+            main.trim().to_lowercase(),
+            subsection.trim().trim_matches('"')
+        ),
+        None => header.trim().to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::{remove_test_repo, setup_test_repo};
+
+    #[test]
+    fn test_get_config_reads_repository_config() {
+        setup_test_repo();
+
+        fs::write(
+            ".rgit/config",
+            "# comment\n[diff]\n\talgorithm = patience\n[branch \"Main\"]\n\tremote = origin\n",
+        )
+        .unwrap();
+
+        assert_eq!(get_config("diff.algorithm"), Some("patience".to_string()));
+        assert_eq!(get_config("DIFF.Algorithm"), Some("patience".to_string()));
+        assert_eq!(get_config("branch.Main.remote"), Some("origin".to_string()));
+        assert_eq!(get_config("branch.main.remote"), None);
+
+        remove_test_repo();
+    }
+}
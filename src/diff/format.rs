@@ -0,0 +1,416 @@
+use super::{
+    diff_lines, is_binary, split_lines, ChangeKind, DiffAlgorithm, DiffFormat, DiffSettings, Edit,
+    FileDiff,
+};
+
+/// Formats file diffs in the requested output format.
+pub fn format_diffs(
+    file_diffs: &[FileDiff],
+    format: DiffFormat,
+    settings: &DiffSettings,
+) -> String {
+    match format {
+        DiffFormat::Patch => file_diffs
+            .iter()
+            .map(|file_diff| format_patch(file_diff, settings))
+            .collect(),
+        DiffFormat::Stat => format_stat(file_diffs, settings.algorithm),
+        DiffFormat::NameOnly => file_diffs
+            .iter()
+            .map(|file_diff| format!("{}\n", file_diff.new_path))
+            .collect(),
+        DiffFormat::NameStatus => file_diffs
+            .iter()
+            .map(|file_diff| match file_diff.kind {
+                ChangeKind::Renamed(_) | ChangeKind::Copied(_) => format!(
+                    "{}\t{}\t{}\n",
+                    file_diff.kind.status(),
+                    file_diff.old_path,
+                    file_diff.new_path
+                ),
+                _ => format!("{}\t{}\n", file_diff.kind.status(), file_diff.new_path),
+            })
+            .collect(),
+    }
+}
+
+/// Formats one file diff as a git-style unified patch.
+pub fn format_patch(file_diff: &FileDiff, settings: &DiffSettings) -> String {
+    let mut output: String = format!(
+        "diff --git a/{} b/{}\n",
+        file_diff.old_path, file_diff.new_path
+    );
+
+    let old_short: &str = short_hash(&file_diff.old_hash);
+    let new_short: &str = short_hash(&file_diff.new_hash);
+    match file_diff.kind {
+        ChangeKind::Added => {
+            output.push_str("new file mode 100644\n");
+            output.push_str(&format!("index 0000000..{}\n", new_short));
+        }
+        ChangeKind::Deleted => {
+            output.push_str("deleted file mode 100644\n");
+            output.push_str(&format!("index {}..0000000\n", old_short));
+        }
+        ChangeKind::Modified => {
+            output.push_str(&format!("index {}..{} 100644\n", old_short, new_short));
+        }
+        ChangeKind::Renamed(similarity) | ChangeKind::Copied(similarity) => {
+            let verb: &str = match file_diff.kind {
+                ChangeKind::Renamed(_) => "rename",
+                _ => "copy",
+            };
+            output.push_str(&format!("similarity index {}%\n", similarity));
+            output.push_str(&format!("{} from {}\n", verb, file_diff.old_path));
+            output.push_str(&format!("{} to {}\n", verb, file_diff.new_path));
+
+            // an exact rename or copy has no content change to show
+            if file_diff.old_hash == file_diff.new_hash {
+                return output;
+            }
+            output.push_str(&format!("index {}..{} 100644\n", old_short, new_short));
+        }
+    }
+
+    let old_label: String = match file_diff.kind {
+        ChangeKind::Added => "/dev/null".to_string(),
+        _ => format!("a/{}", file_diff.old_path),
+    };
+    let new_label: String = match file_diff.kind {
+        ChangeKind::Deleted => "/dev/null".to_string(),
+        _ => format!("b/{}", file_diff.new_path),
+    };
+
+    if is_binary(&file_diff.old_content) || is_binary(&file_diff.new_content) {
+        output.push_str(&format!(
+            "Binary files {} and {} differ\n",
+            old_label, new_label
+        ));
+        return output;
+    }
+
+    let old_text: String = String::from_utf8_lossy(&file_diff.old_content).into_owned();
+    let new_text: String = String::from_utf8_lossy(&file_diff.new_content).into_owned();
+    let hunks: String = unified_diff(&old_text, &new_text, settings);
+    if !hunks.is_empty() {
+        output.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+        output.push_str(&hunks);
+    }
+
+    output
+}
+
+/// One line of a `--stat` summary.
+struct StatRow {
+    path: String,
+    insertions: usize,
+    deletions: usize,
+    /// old and new sizes, for binary files
+    binary_sizes: Option<(usize, usize)>,
+}
+
+/// Formats a `--stat` summary of the file diffs.
+pub fn format_stat(file_diffs: &[FileDiff], algorithm: DiffAlgorithm) -> String {
+    if file_diffs.is_empty() {
+        return String::new();
+    }
+
+    let mut rows: Vec<StatRow> = Vec::new();
+    for file_diff in file_diffs {
+        let path: String = if file_diff.old_path == file_diff.new_path {
+            file_diff.new_path.clone()
+        } else {
+            format!("{} => {}", file_diff.old_path, file_diff.new_path)
+        };
+        if is_binary(&file_diff.old_content) || is_binary(&file_diff.new_content) {
+            rows.push(StatRow {
+                path,
+                insertions: 0,
+                deletions: 0,
+                binary_sizes: Some((file_diff.old_content.len(), file_diff.new_content.len())),
+            });
+        } else {
+            let (insertions, deletions): (usize, usize) =
+                count_changes(&file_diff.old_content, &file_diff.new_content, algorithm);
+            rows.push(StatRow {
+                path,
+                insertions,
+                deletions,
+                binary_sizes: None,
+            });
+        }
+    }
+
+    let path_width: usize = rows.iter().map(|row| row.path.len()).max().unwrap_or(0);
+    let max_changes: usize = rows
+        .iter()
+        .map(|row| row.insertions + row.deletions)
+        .max()
+        .unwrap_or(0);
+    let count_width: usize = max_changes.to_string().len();
+    let bar_width: usize = 50;
+
+    let mut output: String = String::new();
+    let mut total_insertions: usize = 0;
+    let mut total_deletions: usize = 0;
+    for row in &rows {
+        total_insertions += row.insertions;
+        total_deletions += row.deletions;
+
+        if let Some((old_size, new_size)) = row.binary_sizes {
+            output.push_str(&format!(
+                " {:<path_width$} | Bin {} -> {} bytes\n",
+                row.path, old_size, new_size
+            ));
+            continue;
+        }
+
+        // scale the bar down when the largest change does not fit
+        let (plus_count, minus_count): (usize, usize) = if max_changes > bar_width {
+            (
+                (row.insertions * bar_width).div_ceil(max_changes),
+                (row.deletions * bar_width).div_ceil(max_changes),
+            )
+        } else {
+            (row.insertions, row.deletions)
+        };
+        output.push_str(&format!(
+            " {:<path_width$} | {:>count_width$} {}{}\n",
+            row.path,
+            row.insertions + row.deletions,
+            "+".repeat(plus_count),
+            "-".repeat(minus_count)
+        ));
+    }
+
+    let file_count: usize = rows.len();
+    output.push_str(&format!(
+        " {} file{} changed",
+        file_count,
+        if file_count == 1 { "" } else { "s" }
+    ));
+    if total_insertions > 0 {
+        output.push_str(&format!(
+            ", {} insertion{}(+)",
+            total_insertions,
+            if total_insertions == 1 { "" } else { "s" }
+        ));
+    }
+    if total_deletions > 0 {
+        output.push_str(&format!(
+            ", {} deletion{}(-)",
+            total_deletions,
+            if total_deletions == 1 { "" } else { "s" }
+        ));
+    }
+    output.push('\n');
+
+    output
+}
+
+/// Counts inserted and deleted lines between two contents.
+pub fn count_changes(
+    old_content: &[u8],
+    new_content: &[u8],
+    algorithm: DiffAlgorithm,
+) -> (usize, usize) {
+    let old_text: String = String::from_utf8_lossy(old_content).into_owned();
+    let new_text: String = String::from_utf8_lossy(new_content).into_owned();
+    let old_lines: Vec<&str> = split_lines(&old_text);
+    let new_lines: Vec<&str> = split_lines(&new_text);
+
+    let edits: Vec<Edit> = diff_lines(&old_lines, &new_lines, algorithm);
+    let insertions: usize = edits
+        .iter()
+        .filter(|edit| matches!(edit, Edit::Insert(_)))
+        .count();
+    let deletions: usize = edits
+        .iter()
+        .filter(|edit| matches!(edit, Edit::Delete(_)))
+        .count();
+
+    (insertions, deletions)
+}
+
+/// Produces the hunks of a unified diff between two texts, or an empty string if they are equal.
+pub fn unified_diff(old_text: &str, new_text: &str, settings: &DiffSettings) -> String {
+    let old_lines: Vec<&str> = split_lines(old_text);
+    let new_lines: Vec<&str> = split_lines(new_text);
+    let edits: Vec<Edit> = diff_lines(&old_lines, &new_lines, settings.algorithm);
+    format_hunks(&edits, &old_lines, &new_lines, settings.context)
+}
+
+/// Groups an edit script into hunks with `context` lines around each change.
+pub fn format_hunks(
+    edits: &[Edit],
+    old_lines: &[&str],
+    new_lines: &[&str],
+    context: usize,
+) -> String {
+    // number of old and new lines consumed before each edit
+    let mut positions: Vec<(usize, usize)> = Vec::with_capacity(edits.len());
+    let mut old_position: usize = 0;
+    let mut new_position: usize = 0;
+    for edit in edits {
+        positions.push((old_position, new_position));
+        match edit {
+            Edit::Equal(_, _) => {
+                old_position += 1;
+                new_position += 1;
+            }
+            Edit::Delete(_) => old_position += 1,
+            Edit::Insert(_) => new_position += 1,
+        }
+    }
+
+    let mut output: String = String::new();
+    let mut index: usize = 0;
+    let mut previous_end: usize = 0;
+    while index < edits.len() {
+        if matches!(edits[index], Edit::Equal(_, _)) {
+            index += 1;
+            continue;
+        }
+
+        // the hunk starts `context` lines before the first change
+        let start: usize = index.saturating_sub(context).max(previous_end);
+
+        // extend the hunk while the gap between two changes is small enough to be merged
+        let mut end: usize = index;
+        loop {
+            while end < edits.len() && !matches!(edits[end], Edit::Equal(_, _)) {
+                end += 1;
+            }
+            let mut equal_run: usize = 0;
+            while end + equal_run < edits.len()
+                && matches!(edits[end + equal_run], Edit::Equal(_, _))
+            {
+                equal_run += 1;
+            }
+            if end + equal_run == edits.len() || equal_run > 2 * context {
+                end += equal_run.min(context);
+                break;
+            }
+            end += equal_run;
+        }
+
+        output.push_str(&format_hunk(
+            &edits[start..end],
+            positions[start],
+            old_lines,
+            new_lines,
+        ));
+        previous_end = end;
+        index = end;
+    }
+
+    output
+}
+
+fn format_hunk(
+    edits: &[Edit],
+    (old_start, new_start): (usize, usize),
+    old_lines: &[&str],
+    new_lines: &[&str],
+) -> String {
+    let old_count: usize = edits
+        .iter()
+        .filter(|edit| !matches!(edit, Edit::Insert(_)))
+        .count();
+    let new_count: usize = edits
+        .iter()
+        .filter(|edit| !matches!(edit, Edit::Delete(_)))
+        .count();
+
+    let mut output: String = format!(
+        "@@ -{} +{} @@\n",
+        hunk_range(old_start, old_count),
+        hunk_range(new_start, new_count)
+    );
+
+    for edit in edits {
+        let (prefix, line): (char, &str) = match *edit {
+            Edit::Equal(old_index, _) => (' ', old_lines[old_index]),
+            Edit::Delete(old_index) => ('-', old_lines[old_index]),
+            Edit::Insert(new_index) => ('+', new_lines[new_index]),
+        };
+        output.push(prefix);
+        match line.strip_suffix('\n') {
+            Some(stripped) => {
+                output.push_str(stripped);
+                output.push('\n');
+            }
+            None => {
+                output.push_str(line);
+                output.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    output
+}
+
+/// Formats "start,count" as in a hunk header; an empty range points at the line before it.
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+fn short_hash(hash: &str) -> &str {
+    if hash.is_empty() {
+        "0000000"
+    } else {
+        &hash[..hash.len().min(7)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff_formats_hunks() {
+        let old_text: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+        let new_text: &str = "one\ntwo\nthree\nFOUR\nfive\nsix\nseven\neight\nnine\nten\neleven\n";
+        let mut settings: DiffSettings = DiffSettings {
+            algorithm: DiffAlgorithm::Myers,
+            context: 3,
+            renames: None,
+            copies: false,
+        };
+
+        // changes separated by at most twice the context are merged into a single hunk
+        let output: String = unified_diff(old_text, new_text, &settings);
+        assert_eq!(
+            output,
+            "@@ -1,10 +1,11 @@\n one\n two\n three\n-four\n+FOUR\n five\n six\n seven\n\
+             \x20eight\n nine\n ten\n+eleven\n"
+        );
+
+        // without context, each change gets its own hunk
+        settings.context = 0;
+        let output: String = unified_diff(old_text, new_text, &settings);
+        assert_eq!(
+            output,
+            "@@ -4 +4 @@\n-four\n+FOUR\n@@ -10,0 +11 @@\n+eleven\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_marks_missing_newline() {
+        let settings: DiffSettings = DiffSettings {
+            algorithm: DiffAlgorithm::Myers,
+            context: 3,
+            renames: None,
+            copies: false,
+        };
+        let output: String = unified_diff("a\nb", "a\nb\n", &settings);
+        assert_eq!(
+            output,
+            "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use super::{common_ends, myers, Edit};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Lines occurring more often than this on the old side are never used as anchors.
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Computes an edit script with the histogram algorithm: the longest common region seeded by
+/// the least frequent line of the old side is matched first, then the regions before and
+/// after it are diffed recursively. Regions without a usable anchor fall back to Myers.
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let mut edits: Vec<Edit> = Vec::new();
+    diff_range(old, new, 0..old.len(), 0..new.len(), &mut edits);
+    edits
+}
+
+/// A common region between both sides.
+struct Region {
+    old_start: usize,
+    new_start: usize,
+    length: usize,
+    /// occurrences of the seed line on the old side
+    occurrences: usize,
+}
+
+fn diff_range(
+    old: &[&str],
+    new: &[&str],
+    old_range: Range<usize>,
+    new_range: Range<usize>,
+    edits: &mut Vec<Edit>,
+) {
+    let (prefix, suffix): (usize, usize) = common_ends(old, new, &old_range, &new_range);
+    for offset in 0..prefix {
+        edits.push(Edit::Equal(
+            old_range.start + offset,
+            new_range.start + offset,
+        ));
+    }
+
+    let old_middle: Range<usize> = old_range.start + prefix..old_range.end - suffix;
+    let new_middle: Range<usize> = new_range.start + prefix..new_range.end - suffix;
+
+    match find_region(old, new, &old_middle, &new_middle) {
+        Some(region) => {
+            diff_range(
+                old,
+                new,
+                old_middle.start..region.old_start,
+                new_middle.start..region.new_start,
+                edits,
+            );
+            for offset in 0..region.length {
+                edits.push(Edit::Equal(
+                    region.old_start + offset,
+                    region.new_start + offset,
+                ));
+            }
+            diff_range(
+                old,
+                new,
+                region.old_start + region.length..old_middle.end,
+                region.new_start + region.length..new_middle.end,
+                edits,
+            );
+        }
+        None => myers::diff_range(
+            old,
+            new,
+            old_middle.clone(),
+            new_middle.clone(),
+            false,
+            edits,
+        ),
+    }
+
+    for offset in 0..suffix {
+        edits.push(Edit::Equal(
+            old_middle.end + offset,
+            new_middle.end + offset,
+        ));
+    }
+}
+
+/// Finds the common region seeded by the rarest old line, preferring longer regions on ties.
+fn find_region(
+    old: &[&str],
+    new: &[&str],
+    old_range: &Range<usize>,
+    new_range: &Range<usize>,
+) -> Option<Region> {
+    if old_range.is_empty() || new_range.is_empty() {
+        return None;
+    }
+
+    // histogram of the old side: line -> positions
+    let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for old_index in old_range.clone() {
+        positions.entry(old[old_index]).or_default().push(old_index);
+    }
+
+    let mut best: Option<Region> = None;
+    for new_index in new_range.clone() {
+        let line_positions: &Vec<usize> = match positions.get(new[new_index]) {
+            Some(line_positions) => line_positions,
+            None => continue,
+        };
+        let occurrences: usize = line_positions.len();
+        if occurrences > MAX_CHAIN_LENGTH {
+            continue;
+        }
+        if let Some(region) = &best {
+            if occurrences > region.occurrences {
+                continue;
+            }
+        }
+
+        for &old_index in line_positions {
+            // extend the match in both directions
+            let mut old_start: usize = old_index;
+            let mut new_start: usize = new_index;
+            while old_start > old_range.start
+                && new_start > new_range.start
+                && old[old_start - 1] == new[new_start - 1]
+            {
+                old_start -= 1;
+                new_start -= 1;
+            }
+            let mut old_end: usize = old_index + 1;
+            let mut new_end: usize = new_index + 1;
+            while old_end < old_range.end && new_end < new_range.end && old[old_end] == new[new_end]
+            {
+                old_end += 1;
+                new_end += 1;
+            }
+
+            let length: usize = old_end - old_start;
+            let is_better: bool = match &best {
+                Some(region) => {
+                    occurrences < region.occurrences
+                        || (occurrences == region.occurrences && length > region.length)
+                }
+                None => true,
+            };
+            if is_better {
+                best = Some(Region {
+                    old_start,
+                    new_start,
+                    length,
+                    occurrences,
+                });
+            }
+        }
+    }
+
+    best
+}
@@ -0,0 +1,145 @@
+use super::{diff_lines, split_lines, DiffAlgorithm, Edit};
+
+/// The result of a three-way merge.
+pub struct MergeResult {
+    pub content: String,
+    pub has_conflicts: bool,
+}
+
+/// Merges the changes made from `base` to `ours` and from `base` to `theirs` (diff3).
+/// Regions changed differently on both sides are kept as conflicts between markers labelled
+/// with `our_label` and `their_label`.
+pub fn merge3(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    our_label: &str,
+    their_label: &str,
+    algorithm: DiffAlgorithm,
+) -> MergeResult {
+    let base_lines: Vec<&str> = split_lines(base);
+    let our_lines: Vec<&str> = split_lines(ours);
+    let their_lines: Vec<&str> = split_lines(theirs);
+
+    // for each base line, the index of the same line on each side, if it was kept
+    let our_matches: Vec<Option<usize>> = match_base_lines(
+        &diff_lines(&base_lines, &our_lines, algorithm),
+        base_lines.len(),
+    );
+    let their_matches: Vec<Option<usize>> = match_base_lines(
+        &diff_lines(&base_lines, &their_lines, algorithm),
+        base_lines.len(),
+    );
+
+    let mut content: String = String::new();
+    let mut has_conflicts: bool = false;
+    let (mut base_index, mut our_index, mut their_index): (usize, usize, usize) = (0, 0, 0);
+
+    loop {
+        // lines kept on both sides are stable and copied as they are
+        while base_index < base_lines.len()
+            && our_matches[base_index] == Some(our_index)
+            && their_matches[base_index] == Some(their_index)
+        {
+            content.push_str(base_lines[base_index]);
+            base_index += 1;
+            our_index += 1;
+            their_index += 1;
+        }
+
+        // the unstable chunk goes until the next base line kept on both sides
+        let mut next_base: usize = base_index;
+        while next_base < base_lines.len()
+            && (our_matches[next_base].is_none() || their_matches[next_base].is_none())
+        {
+            next_base += 1;
+        }
+        let (next_ours, next_theirs): (usize, usize) = if next_base < base_lines.len() {
+            (
+                our_matches[next_base].unwrap(),
+                their_matches[next_base].unwrap(),
+            )
+        } else {
+            (our_lines.len(), their_lines.len())
+        };
+
+        let base_chunk: &[&str] = &base_lines[base_index..next_base];
+        let our_chunk: &[&str] = &our_lines[our_index..next_ours];
+        let their_chunk: &[&str] = &their_lines[their_index..next_theirs];
+
+        if our_chunk == base_chunk || our_chunk == their_chunk {
+            content.push_str(&their_chunk.concat());
+        } else if their_chunk == base_chunk {
+            content.push_str(&our_chunk.concat());
+        } else {
+            has_conflicts = true;
+            content.push_str(&format!("<<<<<<< {}\n", our_label));
+            push_chunk(&mut content, our_chunk);
+            content.push_str("=======\n");
+            push_chunk(&mut content, their_chunk);
+            content.push_str(&format!(">>>>>>> {}\n", their_label));
+        }
+
+        if next_base >= base_lines.len() {
+            break;
+        }
+        base_index = next_base;
+        our_index = next_ours;
+        their_index = next_theirs;
+    }
+
+    MergeResult {
+        content,
+        has_conflicts,
+    }
+}
+
+fn match_base_lines(edits: &[Edit], base_length: usize) -> Vec<Option<usize>> {
+    let mut matches: Vec<Option<usize>> = vec![None; base_length];
+    for edit in edits {
+        if let Edit::Equal(base_index, side_index) = *edit {
+            matches[base_index] = Some(side_index);
+        }
+    }
+    matches
+}
+
+/// Appends conflicting lines, making sure the following marker starts on its own line.
+fn push_chunk(content: &mut String, chunk: &[&str]) {
+    content.push_str(&chunk.concat());
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge3_combines_independent_changes() {
+        let base: &str = "one\ntwo\nthree\nfour\nfive\n";
+        let ours: &str = "ONE\ntwo\nthree\nfour\nfive\n";
+        let theirs: &str = "one\ntwo\nthree\nfour\nFIVE\n";
+
+        let result: MergeResult =
+            merge3(base, ours, theirs, "ours", "theirs", DiffAlgorithm::Myers);
+        assert!(!result.has_conflicts);
+        assert_eq!(result.content, "ONE\ntwo\nthree\nfour\nFIVE\n");
+    }
+
+    #[test]
+    fn test_merge3_marks_conflicts() {
+        let base: &str = "one\ntwo\nthree\n";
+        let ours: &str = "one\n2\nthree\n";
+        let theirs: &str = "one\ndeux\nthree\n";
+
+        let result: MergeResult =
+            merge3(base, ours, theirs, "ours", "theirs", DiffAlgorithm::Myers);
+        assert!(result.has_conflicts);
+        assert_eq!(
+            result.content,
+            "one\n<<<<<<< ours\n2\n=======\ndeux\n>>>>>>> theirs\nthree\n"
+        );
+    }
+}
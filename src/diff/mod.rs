@@ -0,0 +1,387 @@
+mod format;
+mod histogram;
+pub mod merge;
+mod myers;
+pub mod patch;
+mod patience;
+mod rename;
+
+pub use format::{format_diffs, format_stat};
+
+use crate::config::get_config;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+/// Default minimum similarity for renames and copies, in percent.
+pub const DEFAULT_RENAME_THRESHOLD: u8 = 50;
+
+/// One step of an edit script turning the old lines into the new lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// the line is kept: (old index, new index)
+    Equal(usize, usize),
+    /// the line at this old index is removed
+    Delete(usize),
+    /// the line at this new index is added
+    Insert(usize),
+}
+
+/// How a path changed between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    /// moved from `old_path`, with the similarity of both contents in percent
+    Renamed(u8),
+    /// copied from `old_path`, which still exists, with the similarity in percent
+    Copied(u8),
+}
+
+impl ChangeKind {
+    /// Returns the status used by `--name-status`, e.g. `M` or `R087`.
+    pub fn status(&self) -> String {
+        match self {
+            ChangeKind::Added => "A".to_string(),
+            ChangeKind::Deleted => "D".to_string(),
+            ChangeKind::Modified => "M".to_string(),
+            ChangeKind::Renamed(similarity) => format!("R{:03}", similarity),
+            ChangeKind::Copied(similarity) => format!("C{:03}", similarity),
+        }
+    }
+}
+
+/// A changed file together with the contents of both sides.
+pub struct FileDiff {
+    pub kind: ChangeKind,
+    pub old_path: String,
+    pub new_path: String,
+    pub old_hash: String,
+    pub new_hash: String,
+    pub old_content: Vec<u8>,
+    pub new_content: Vec<u8>,
+}
+
+/// The way a list of file diffs is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Patch,
+    Stat,
+    NameOnly,
+    NameStatus,
+}
+
+/// The algorithm used to compute line diffs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    /// Myers' algorithm, giving up on the optimal script for very expensive inputs
+    Myers,
+    /// Myers' algorithm, always producing the shortest edit script
+    Minimal,
+    /// matches lines that are unique on both sides first
+    Patience,
+    /// like patience, but anchors on the least frequent lines instead of unique ones
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    /// Parses an algorithm name as given to `--diff-algorithm` or `diff.algorithm`.
+    pub fn parse(name: &str) -> Option<DiffAlgorithm> {
+        match name.to_lowercase().as_str() {
+            "myers" | "default" => Some(DiffAlgorithm::Myers),
+            "minimal" => Some(DiffAlgorithm::Minimal),
+            "patience" => Some(DiffAlgorithm::Patience),
+            "histogram" => Some(DiffAlgorithm::Histogram),
+            _ => None,
+        }
+    }
+}
+
+/// Settings shared by every command printing diffs.
+#[derive(Debug, Clone, Copy)]
+pub struct DiffSettings {
+    pub algorithm: DiffAlgorithm,
+    pub context: usize,
+    /// minimum similarity in percent to detect renames, or `None` to disable them
+    pub renames: Option<u8>,
+    /// also detect copies from modified and deleted files
+    pub copies: bool,
+}
+
+impl DiffSettings {
+    /// Reads the defaults from the `diff.algorithm`, `diff.context` and `diff.renames` config
+    /// keys, falling back to Myers with 3 lines of context and rename detection enabled.
+    pub fn from_config() -> DiffSettings {
+        let algorithm: DiffAlgorithm = match get_config("diff.algorithm") {
+            Some(name) => DiffAlgorithm::parse(&name).unwrap_or_else(|| {
+                eprintln!("Warning: unknown diff.algorithm '{}', using myers", name);
+                DiffAlgorithm::Myers
+            }),
+            None => DiffAlgorithm::Myers,
+        };
+        let context: usize = get_config("diff.context")
+            .and_then(|value| value.parse::<usize>().ok())
+            .unwrap_or(3);
+        let renames_config: String = get_config("diff.renames").unwrap_or("true".to_string());
+        let renames: Option<u8> = match renames_config.as_str() {
+            "false" | "no" | "off" | "0" => None,
+            _ => Some(DEFAULT_RENAME_THRESHOLD),
+        };
+        let copies: bool = matches!(renames_config.as_str(), "copies" | "copy");
+
+        DiffSettings {
+            algorithm,
+            context,
+            renames,
+            copies,
+        }
+    }
+
+    /// Pairs added and deleted files into renames and copies, if enabled.
+    pub fn detect_renames(&self, file_diffs: Vec<FileDiff>) -> Vec<FileDiff> {
+        match self.renames {
+            Some(threshold) => rename::detect_renames(file_diffs, threshold, self.copies),
+            None => file_diffs,
+        }
+    }
+
+    /// Applies a diff option shared by every command printing diffs (algorithm, context,
+    /// rename and copy detection). Returns false if the argument is not one of them.
+    pub fn parse_option(&mut self, arg: &str) -> bool {
+        match arg {
+            "--minimal" => self.algorithm = DiffAlgorithm::Minimal,
+            "--patience" => self.algorithm = DiffAlgorithm::Patience,
+            "--histogram" => self.algorithm = DiffAlgorithm::Histogram,
+            "--no-renames" => {
+                self.renames = None;
+                self.copies = false;
+            }
+            _ if arg.starts_with("--diff-algorithm=") => {
+                let name: &str = arg.trim_start_matches("--diff-algorithm=");
+                self.algorithm = match DiffAlgorithm::parse(name) {
+                    Some(algorithm) => algorithm,
+                    None => {
+                        eprintln!("Error: unknown diff algorithm '{}'", name);
+                        eprintln!("Supported algorithms: myers, minimal, patience, histogram");
+                        std::process::exit(1);
+                    }
+                };
+            }
+            _ if arg.starts_with("-U") || arg.starts_with("--unified=") => {
+                let value: &str = arg
+                    .trim_start_matches("--unified=")
+                    .trim_start_matches("-U");
+                self.context = match value.parse::<usize>() {
+                    Ok(context) => context,
+                    Err(_) => {
+                        eprintln!("Error: invalid context length '{}'", value);
+                        std::process::exit(1);
+                    }
+                };
+            }
+            _ if arg.starts_with("-M") || arg.starts_with("--find-renames") => {
+                let value: &str = arg
+                    .trim_start_matches("--find-renames")
+                    .trim_start_matches('=')
+                    .trim_start_matches("-M");
+                self.renames = Some(parse_similarity(value));
+            }
+            _ if arg.starts_with("-C") || arg.starts_with("--find-copies") => {
+                let value: &str = arg
+                    .trim_start_matches("--find-copies")
+                    .trim_start_matches('=')
+                    .trim_start_matches("-C");
+                let threshold: u8 = if value.is_empty() {
+                    self.renames.unwrap_or(DEFAULT_RENAME_THRESHOLD)
+                } else {
+                    parse_similarity(value)
+                };
+                self.renames = Some(threshold);
+                self.copies = true;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Parses a similarity threshold like git: `90%` is a percentage, while bare digits are the
+/// decimal part of a fraction (`9` and `90` both mean 90%, `05` means 5%).
+fn parse_similarity(value: &str) -> u8 {
+    if value.is_empty() {
+        return DEFAULT_RENAME_THRESHOLD;
+    }
+
+    let parsed: Option<u8> = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<u8>().ok().filter(|&percent| percent <= 100),
+        None if value.chars().all(|c| c.is_ascii_digit()) => {
+            // keep the first two digits of the fraction
+            let digits: String = format!("{:0<2}", &value[..value.len().min(2)]);
+            digits.parse::<u8>().ok()
+        }
+        None => None,
+    };
+
+    match parsed {
+        Some(threshold) => threshold,
+        None => {
+            eprintln!("Error: invalid similarity '{}'", value);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Computes an edit script between two line sequences with the given algorithm.
+pub fn diff_lines(old: &[&str], new: &[&str], algorithm: DiffAlgorithm) -> Vec<Edit> {
+    match algorithm {
+        DiffAlgorithm::Myers => myers::diff(old, new, false),
+        DiffAlgorithm::Minimal => myers::diff(old, new, true),
+        DiffAlgorithm::Patience => patience::diff(old, new),
+        DiffAlgorithm::Histogram => histogram::diff(old, new),
+    }
+}
+
+/// Returns the lengths of the common prefix and suffix of two ranges (not overlapping).
+fn common_ends(
+    old: &[&str],
+    new: &[&str],
+    old_range: &Range<usize>,
+    new_range: &Range<usize>,
+) -> (usize, usize) {
+    let max_length: usize = old_range.len().min(new_range.len());
+
+    let mut prefix: usize = 0;
+    while prefix < max_length && old[old_range.start + prefix] == new[new_range.start + prefix] {
+        prefix += 1;
+    }
+
+    let mut suffix: usize = 0;
+    while suffix < max_length - prefix
+        && old[old_range.end - 1 - suffix] == new[new_range.end - 1 - suffix]
+    {
+        suffix += 1;
+    }
+
+    (prefix, suffix)
+}
+
+/// Splits a text into lines, keeping the trailing newline of each line.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Returns true if the data looks binary (a NUL byte in the first 8000 bytes, like git).
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|&byte| byte == 0)
+}
+
+/// Compares two snapshots (path -> hash) and returns the changed paths with their contents.
+/// `read_old` and `read_new` receive a path and its hash and return the file contents.
+pub fn diff_snapshots(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+    read_old: &dyn Fn(&str, &str) -> Vec<u8>,
+    read_new: &dyn Fn(&str, &str) -> Vec<u8>,
+) -> Vec<FileDiff> {
+    let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut file_diffs: Vec<FileDiff> = Vec::new();
+
+    for path in paths {
+        let old_hash: Option<&String> = old.get(path);
+        let new_hash: Option<&String> = new.get(path);
+
+        let kind: ChangeKind = match (old_hash, new_hash) {
+            (Some(old_hash), Some(new_hash)) if old_hash == new_hash => continue,
+            (Some(_), Some(_)) => ChangeKind::Modified,
+            (Some(_), None) => ChangeKind::Deleted,
+            (None, Some(_)) => ChangeKind::Added,
+            (None, None) => continue,
+        };
+
+        let old_content: Vec<u8> = match old_hash {
+            Some(hash) => read_old(path, hash),
+            None => Vec::new(),
+        };
+        let new_content: Vec<u8> = match new_hash {
+            Some(hash) => read_new(path, hash),
+            None => Vec::new(),
+        };
+
+        file_diffs.push(FileDiff {
+            kind,
+            old_path: path.clone(),
+            new_path: path.clone(),
+            old_hash: old_hash.cloned().unwrap_or_default(),
+            new_hash: new_hash.cloned().unwrap_or_default(),
+            old_content,
+            new_content,
+        });
+    }
+
+    file_diffs
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// applies an edit script to the old lines, checking that every index is used in order
+    pub fn apply_edits(edits: &[Edit], old: &[&str], new: &[&str]) -> Vec<String> {
+        let mut result: Vec<String> = Vec::new();
+        let mut old_position: usize = 0;
+        let mut new_position: usize = 0;
+        for edit in edits {
+            match *edit {
+                Edit::Equal(old_index, new_index) => {
+                    assert_eq!((old_index, new_index), (old_position, new_position));
+                    assert_eq!(old[old_index], new[new_index]);
+                    result.push(old[old_index].to_string());
+                    old_position += 1;
+                    new_position += 1;
+                }
+                Edit::Insert(new_index) => {
+                    assert_eq!(new_index, new_position);
+                    result.push(new[new_index].to_string());
+                    new_position += 1;
+                }
+                Edit::Delete(old_index) => {
+                    assert_eq!(old_index, old_position);
+                    old_position += 1;
+                }
+            }
+        }
+        assert_eq!(old_position, old.len(), "every old line should be used.");
+        result
+    }
+
+    #[test]
+    fn test_every_algorithm_produces_a_valid_script() {
+        let old: Vec<&str> = vec!["a", "b", "c", "a", "b", "b", "a", "d", "e", "a"];
+        let new: Vec<&str> = vec!["c", "b", "a", "b", "a", "c", "e", "d", "a"];
+
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Minimal,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Histogram,
+        ] {
+            let edits: Vec<Edit> = diff_lines(&old, &new, algorithm);
+            assert_eq!(apply_edits(&edits, &old, &new), new, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_parse_similarity_follows_git() {
+        assert_eq!(parse_similarity(""), 50);
+        assert_eq!(parse_similarity("90%"), 90);
+        assert_eq!(parse_similarity("9"), 90);
+        assert_eq!(parse_similarity("75"), 75);
+        assert_eq!(parse_similarity("05"), 5);
+    }
+
+    #[test]
+    fn test_is_binary_detects_nul_bytes() {
+        assert!(is_binary(b"abc\0def"));
+        assert!(!is_binary(b"plain text\n"));
+    }
+}
@@ -0,0 +1,252 @@
+use super::Edit;
+use std::ops::{Index, IndexMut, Range};
+
+/// Below this many edit steps, the search never gives up looking for the optimal path.
+const MIN_COST_LIMIT: usize = 256;
+
+/// Computes an edit script with Myers' algorithm, using the linear-space divide-and-conquer
+/// variant. Unless `minimal` is set, searches that get too expensive stop at the furthest
+/// point reached, trading a slightly longer script for speed on very different inputs.
+pub fn diff(old: &[&str], new: &[&str], minimal: bool) -> Vec<Edit> {
+    let mut edits: Vec<Edit> = Vec::new();
+    diff_range(old, new, 0..old.len(), 0..new.len(), minimal, &mut edits);
+    edits
+}
+
+/// Appends the edit script between `old[old_range]` and `new[new_range]` to `edits`.
+pub fn diff_range(
+    old: &[&str],
+    new: &[&str],
+    old_range: Range<usize>,
+    new_range: Range<usize>,
+    minimal: bool,
+    edits: &mut Vec<Edit>,
+) {
+    let length: usize = old_range.len() + new_range.len();
+    let max_d: usize = length.div_ceil(2) + 1;
+    let cost_limit: usize = if minimal {
+        usize::MAX
+    } else {
+        MIN_COST_LIMIT.max(length.isqrt())
+    };
+
+    let mut searcher: Searcher = Searcher {
+        old,
+        new,
+        forward: DiagonalVec::new(max_d),
+        backward: DiagonalVec::new(max_d),
+        cost_limit,
+    };
+    searcher.conquer(old_range, new_range, edits);
+}
+
+/// Furthest x reached on each diagonal k, indexed by k in -max_d..=max_d.
+struct DiagonalVec {
+    offset: isize,
+    values: Vec<usize>,
+}
+
+impl DiagonalVec {
+    fn new(max_d: usize) -> DiagonalVec {
+        DiagonalVec {
+            offset: max_d as isize,
+            values: vec![0; 2 * max_d + 1],
+        }
+    }
+}
+
+impl Index<isize> for DiagonalVec {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.values[(k + self.offset) as usize]
+    }
+}
+
+impl IndexMut<isize> for DiagonalVec {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.values[(k + self.offset) as usize]
+    }
+}
+
+struct Searcher<'a> {
+    old: &'a [&'a str],
+    new: &'a [&'a str],
+    forward: DiagonalVec,
+    backward: DiagonalVec,
+    cost_limit: usize,
+}
+
+impl Searcher<'_> {
+    fn conquer(&mut self, old_range: Range<usize>, new_range: Range<usize>, edits: &mut Vec<Edit>) {
+        let (mut old_start, mut old_end): (usize, usize) = (old_range.start, old_range.end);
+        let (mut new_start, mut new_end): (usize, usize) = (new_range.start, new_range.end);
+
+        // common prefix
+        while old_start < old_end
+            && new_start < new_end
+            && self.old[old_start] == self.new[new_start]
+        {
+            edits.push(Edit::Equal(old_start, new_start));
+            old_start += 1;
+            new_start += 1;
+        }
+
+        // common suffix, emitted after the middle part
+        let mut suffix_length: usize = 0;
+        while old_start < old_end
+            && new_start < new_end
+            && self.old[old_end - 1] == self.new[new_end - 1]
+        {
+            old_end -= 1;
+            new_end -= 1;
+            suffix_length += 1;
+        }
+
+        if old_start == old_end {
+            for new_index in new_start..new_end {
+                edits.push(Edit::Insert(new_index));
+            }
+        } else if new_start == new_end {
+            for old_index in old_start..old_end {
+                edits.push(Edit::Delete(old_index));
+            }
+        } else {
+            let (old_split, new_split): (usize, usize) =
+                self.find_middle_snake(old_start..old_end, new_start..new_end);
+            self.conquer(old_start..old_split, new_start..new_split, edits);
+            self.conquer(old_split..old_end, new_split..new_end, edits);
+        }
+
+        for offset in 0..suffix_length {
+            edits.push(Edit::Equal(old_end + offset, new_end + offset));
+        }
+    }
+
+    /// Finds a point where to split the problem in two, by running the search from both ends
+    /// until the two paths overlap. The ranges must be non-empty and differ at both ends.
+    fn find_middle_snake(
+        &mut self,
+        old_range: Range<usize>,
+        new_range: Range<usize>,
+    ) -> (usize, usize) {
+        let (old_start, old_end): (usize, usize) = (old_range.start, old_range.end);
+        let (new_start, new_end): (usize, usize) = (new_range.start, new_range.end);
+        let n: isize = old_range.len() as isize;
+        let m: isize = new_range.len() as isize;
+        let delta: isize = n - m;
+        let odd: bool = delta & 1 == 1;
+        let max_d: isize = (n + m + 1) / 2;
+
+        self.forward[1] = 0;
+        self.backward[1] = 0;
+
+        for d in 0..=max_d {
+            // forward search from the top-left corner
+            let mut k: isize = -d;
+            while k <= d {
+                let forward: &DiagonalVec = &self.forward;
+                let mut x: isize = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                    forward[k + 1] as isize
+                } else {
+                    forward[k - 1] as isize + 1
+                };
+                let mut y: isize = x - k;
+                let (snake_x, snake_y): (isize, isize) = (x, y);
+                while x < n
+                    && y < m
+                    && self.old[old_start + x as usize] == self.new[new_start + y as usize]
+                {
+                    x += 1;
+                    y += 1;
+                }
+                self.forward[k] = x as usize;
+
+                if odd && (k - delta).abs() < d && x + self.backward[delta - k] as isize >= n {
+                    return (old_start + snake_x as usize, new_start + snake_y as usize);
+                }
+                k += 2;
+            }
+
+            // backward search from the bottom-right corner, x and y counted from the end
+            let mut k: isize = -d;
+            while k <= d {
+                let backward: &DiagonalVec = &self.backward;
+                let mut x: isize = if k == -d || (k != d && backward[k - 1] < backward[k + 1]) {
+                    backward[k + 1] as isize
+                } else {
+                    backward[k - 1] as isize + 1
+                };
+                let mut y: isize = x - k;
+                while x < n
+                    && y < m
+                    && self.old[old_end - 1 - x as usize] == self.new[new_end - 1 - y as usize]
+                {
+                    x += 1;
+                    y += 1;
+                }
+                self.backward[k] = x as usize;
+
+                if !odd && (k - delta).abs() <= d && x + self.forward[delta - k] as isize >= n {
+                    return (old_end - x as usize, new_end - y as usize);
+                }
+                k += 2;
+            }
+
+            if d as usize >= self.cost_limit {
+                if let Some(split) = self.furthest_forward_point(d, n, m) {
+                    return (old_start + split.0, new_start + split.1);
+                }
+            }
+        }
+
+        // unreachable for non-empty inputs: the paths always meet before max_d
+        (old_end, new_end)
+    }
+
+    /// Returns the strictly inner point the forward search got closest to the end with, so
+    /// that splitting there always makes progress.
+    fn furthest_forward_point(&self, d: isize, n: isize, m: isize) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut best_progress: isize = 0;
+
+        let mut k: isize = -d;
+        while k <= d {
+            let x: isize = self.forward[k] as isize;
+            let y: isize = x - k;
+            let progress: isize = x + y;
+            if (0..=n).contains(&x)
+                && (0..=m).contains(&y)
+                && progress > best_progress
+                && progress < n + m
+            {
+                best = Some((x as usize, y as usize));
+                best_progress = progress;
+            }
+            k += 2;
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_myers_produces_shortest_edit_script() {
+        let old: Vec<&str> = vec!["a", "b", "c", "a", "b", "b", "a"];
+        let new: Vec<&str> = vec!["c", "b", "a", "b", "a", "c"];
+
+        let edits: Vec<Edit> = diff(&old, &new, true);
+        assert_eq!(crate::diff::tests::apply_edits(&edits, &old, &new), new);
+
+        // the classic example from Myers' paper has an edit distance of 5
+        let changes: usize = edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Equal(_, _)))
+            .count();
+        assert_eq!(changes, 5, "the edit script should be minimal.");
+    }
+}
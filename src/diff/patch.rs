@@ -0,0 +1,566 @@
+use super::split_lines;
+
+/// One line of a hunk, with its trailing newline unless the file does not end with one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Delete(String),
+    Insert(String),
+}
+
+/// A hunk of a unified diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// first line of the hunk in the old file (1-based, 0 for an empty range)
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    /// Lines the hunk expects to find in the old file.
+    fn preimage(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Delete(text) => Some(text.as_str()),
+                HunkLine::Insert(_) => None,
+            })
+            .collect()
+    }
+
+    /// Lines the hunk leaves in the new file.
+    fn postimage(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Insert(text) => Some(text.as_str()),
+                HunkLine::Delete(_) => None,
+            })
+            .collect()
+    }
+
+    /// Number of context lines at the start and at the end of the hunk.
+    fn context_lengths(&self) -> (usize, usize) {
+        let is_context = |line: &&HunkLine| matches!(line, HunkLine::Context(_));
+        let leading: usize = self.lines.iter().take_while(is_context).count();
+        let trailing: usize = self.lines.iter().rev().take_while(is_context).count();
+        (leading, trailing)
+    }
+}
+
+/// The changes to one file in a patch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilePatch {
+    /// `None` when the file is created
+    pub old_path: Option<String>,
+    /// `None` when the file is deleted
+    pub new_path: Option<String>,
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    /// the new file is a copy of the old one, which is kept
+    pub is_copy: bool,
+    /// abbreviated hashes from the `index` line, if any
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+    /// the patch only says "Binary files differ"
+    pub is_binary: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    /// Returns the path used to name the file in messages.
+    pub fn display_path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("")
+    }
+}
+
+/// A hunk that could not be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedHunk {
+    /// 1-based number of the hunk in the file patch
+    pub number: usize,
+    /// line where the hunk was expected in the old file
+    pub line: usize,
+}
+
+/// Parses the unified diffs contained in a text. Lines outside of file patches (mail headers,
+/// commit messages...) are ignored. Both `diff --git` patches and plain `diff -u` output
+/// are understood; paths lose their first component (`a/`, `b/`) like `patch -p1`.
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut index: usize = 0;
+
+    while index < lines.len() {
+        let line: &str = lines[index];
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let mut patch: FilePatch = FilePatch::default();
+            if let Some((old_path, new_path)) = split_git_paths(paths) {
+                patch.old_path = Some(old_path);
+                patch.new_path = Some(new_path);
+            }
+            index += 1;
+            index = parse_extended_headers(&lines, index, &mut patch);
+            index = parse_file_body(&lines, index, &mut patch)?;
+            patches.push(patch);
+        } else if line.starts_with("--- ")
+            && index + 1 < lines.len()
+            && lines[index + 1].starts_with("+++ ")
+        {
+            let mut patch: FilePatch = FilePatch::default();
+            index = parse_file_body(&lines, index, &mut patch)?;
+            patches.push(patch);
+        } else {
+            index += 1;
+        }
+    }
+
+    Ok(patches)
+}
+
+/// Splits "a/old b/new" from a `diff --git` line, assuming paths without spaces.
+fn split_git_paths(paths: &str) -> Option<(String, String)> {
+    let (old_path, new_path): (&str, &str) = paths.split_once(' ')?;
+    Some((
+        strip_prefix_component(old_path),
+        strip_prefix_component(new_path),
+    ))
+}
+
+/// Removes the first path component (`a/`, `b/`) and any trailing timestamp.
+fn strip_prefix_component(path: &str) -> String {
+    let path: &str = path.split('\t').next().unwrap_or(path).trim_end();
+    match path.split_once('/') {
+        Some((_, rest)) => rest.to_string(),
+        None => path.to_string(),
+    }
+}
+
+fn parse_extended_headers(lines: &[&str], mut index: usize, patch: &mut FilePatch) -> usize {
+    while index < lines.len() {
+        let line: &str = lines[index];
+        if let Some(mode) = line.strip_prefix("old mode ") {
+            patch.old_mode = Some(mode.trim().to_string());
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            patch.new_mode = Some(mode.trim().to_string());
+        } else if let Some(mode) = line.strip_prefix("new file mode ") {
+            patch.old_path = None;
+            patch.new_mode = Some(mode.trim().to_string());
+        } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+            patch.new_path = None;
+            patch.old_mode = Some(mode.trim().to_string());
+        } else if let Some(path) = line
+            .strip_prefix("rename from ")
+            .or(line.strip_prefix("copy from "))
+        {
+            patch.is_copy = line.starts_with("copy");
+            patch.old_path = Some(path.trim().to_string());
+        } else if let Some(path) = line
+            .strip_prefix("rename to ")
+            .or(line.strip_prefix("copy to "))
+        {
+            patch.new_path = Some(path.trim().to_string());
+        } else if let Some(hashes) = line.strip_prefix("index ") {
+            // "index <old>..<new> [<mode>]"
+            let mut parts = hashes.split_whitespace();
+            if let Some((old_hash, new_hash)) =
+                parts.next().and_then(|range| range.split_once(".."))
+            {
+                patch.old_hash = Some(old_hash.to_string());
+                patch.new_hash = Some(new_hash.to_string());
+            }
+            if let Some(mode) = parts.next() {
+                patch.old_mode.get_or_insert(mode.to_string());
+                patch.new_mode.get_or_insert(mode.to_string());
+            }
+        } else if line.starts_with("similarity index ") || line.starts_with("dissimilarity index ")
+        {
+            // informative only
+        } else if line.starts_with("Binary files ") || line.starts_with("GIT binary patch") {
+            patch.is_binary = true;
+        } else {
+            break;
+        }
+        index += 1;
+    }
+    index
+}
+
+/// Parses the `---`/`+++` lines and the hunks of a file, if present.
+fn parse_file_body(
+    lines: &[&str],
+    mut index: usize,
+    patch: &mut FilePatch,
+) -> Result<usize, String> {
+    if index + 1 < lines.len()
+        && lines[index].starts_with("--- ")
+        && lines[index + 1].starts_with("+++ ")
+    {
+        patch.old_path = parse_file_line(&lines[index][4..]);
+        patch.new_path = parse_file_line(&lines[index + 1][4..]);
+        index += 2;
+    }
+
+    while index < lines.len() && lines[index].starts_with("@@ ") {
+        let (hunk, next_index): (Hunk, usize) = parse_hunk(lines, index)?;
+        patch.hunks.push(hunk);
+        index = next_index;
+    }
+
+    Ok(index)
+}
+
+fn parse_file_line(path: &str) -> Option<String> {
+    let path: &str = path.split('\t').next().unwrap_or(path).trim_end();
+    if path == "/dev/null" {
+        None
+    } else {
+        Some(strip_prefix_component(path))
+    }
+}
+
+fn parse_hunk(lines: &[&str], index: usize) -> Result<(Hunk, usize), String> {
+    let header: &str = lines[index];
+    let ranges: Vec<&str> = header
+        .trim_start_matches("@@ ")
+        .split(" @@")
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+        .collect();
+    if ranges.len() != 2 || !ranges[0].starts_with('-') || !ranges[1].starts_with('+') {
+        return Err(format!("corrupt patch at line {}: {}", index + 1, header));
+    }
+    let (old_start, old_count): (usize, usize) = parse_range(&ranges[0][1..])
+        .ok_or_else(|| format!("corrupt patch at line {}: {}", index + 1, header))?;
+    let (new_start, new_count): (usize, usize) = parse_range(&ranges[1][1..])
+        .ok_or_else(|| format!("corrupt patch at line {}: {}", index + 1, header))?;
+
+    let mut hunk: Hunk = Hunk {
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+        lines: Vec::new(),
+    };
+
+    let mut old_remaining: usize = old_count;
+    let mut new_remaining: usize = new_count;
+    let mut line_index: usize = index + 1;
+    while line_index < lines.len()
+        && (old_remaining > 0 || new_remaining > 0 || lines[line_index].starts_with('\\'))
+    {
+        let line: &str = lines[line_index];
+        if line.starts_with('\\') {
+            // "\ No newline at end of file" applies to the previous line
+            if let Some(HunkLine::Context(text) | HunkLine::Delete(text) | HunkLine::Insert(text)) =
+                hunk.lines.last_mut()
+            {
+                if text.ends_with('\n') {
+                    text.pop();
+                }
+            }
+            line_index += 1;
+            continue;
+        }
+
+        let (marker, text): (char, &str) = match line.chars().next() {
+            Some(marker) => (marker, &line[1..]),
+            // some tools strip the space of empty context lines
+            None => (' ', ""),
+        };
+        let text: String = format!("{}\n", text);
+        match marker {
+            ' ' if old_remaining > 0 && new_remaining > 0 => {
+                hunk.lines.push(HunkLine::Context(text));
+                old_remaining -= 1;
+                new_remaining -= 1;
+            }
+            '-' if old_remaining > 0 => {
+                hunk.lines.push(HunkLine::Delete(text));
+                old_remaining -= 1;
+            }
+            '+' if new_remaining > 0 => {
+                hunk.lines.push(HunkLine::Insert(text));
+                new_remaining -= 1;
+            }
+            _ => {
+                return Err(format!(
+                    "corrupt patch at line {}: {}",
+                    line_index + 1,
+                    line
+                ));
+            }
+        }
+        line_index += 1;
+    }
+
+    if old_remaining > 0 || new_remaining > 0 {
+        return Err(format!("truncated hunk at line {}: {}", index + 1, header));
+    }
+
+    Ok((hunk, line_index))
+}
+
+/// Parses "start[,count]" from a hunk header; the count defaults to 1.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// How far a hunk may be moved from its expected position, and how many context lines may be
+/// ignored at each end, to still apply.
+const MAX_FUZZ: usize = 2;
+
+/// Applies hunks to a content. Hunks that do not match exactly where expected are searched
+/// for around that position, then retried with up to `MAX_FUZZ` context lines ignored at
+/// each end, as long as one line is left. The shift of each applied hunk is carried over to
+/// the next ones. Returns the new content, or the hunks that could not be applied.
+pub fn apply_hunks(content: &str, hunks: &[Hunk]) -> Result<String, Vec<RejectedHunk>> {
+    let mut lines: Vec<String> = split_lines(content)
+        .into_iter()
+        .map(|line| line.to_string())
+        .collect();
+    let mut rejected: Vec<RejectedHunk> = Vec::new();
+
+    // shift between the old line numbers and the current content
+    let mut offset: isize = 0;
+    // hunks may not overlap the lines already written by the previous ones
+    let mut minimum_position: usize = 0;
+
+    for (number, hunk) in hunks.iter().enumerate() {
+        let applied: Option<(usize, usize, usize, usize)> = (0..=MAX_FUZZ).find_map(|fuzz| {
+            let (leading, trailing): (usize, usize) = hunk.context_lengths();
+            let skip_start: usize = fuzz.min(leading);
+            let skip_end: usize = fuzz.min(trailing);
+            if fuzz > 0 && skip_start == 0 && skip_end == 0 {
+                return None;
+            }
+
+            let preimage: Vec<&str> = hunk.preimage();
+            // keep at least one line to anchor the hunk, short or context-only hunks have no
+            // fuzz left once their context is gone
+            if fuzz > 0 && skip_start + skip_end >= preimage.len() {
+                return None;
+            }
+            let preimage: &[&str] = &preimage[skip_start..preimage.len() - skip_end];
+            // position of the (trimmed) preimage in the old file, 0-based
+            let expected: isize = hunk.old_start.max(1) as isize - 1 + skip_start as isize + offset;
+            let expected: usize = if hunk.old_count == 0 {
+                // an empty preimage is anchored after its line, not on it
+                (hunk.old_start as isize + offset).max(0) as usize
+            } else {
+                expected.max(0) as usize
+            };
+
+            find_preimage(&lines, preimage, expected, minimum_position)
+                .map(|position| (position, expected, skip_start, skip_end))
+        });
+
+        match applied {
+            Some((position, expected, skip_start, skip_end)) => {
+                let postimage: Vec<&str> = hunk.postimage();
+                let postimage: &[&str] = &postimage[skip_start..postimage.len() - skip_end];
+                let preimage_length: usize = hunk.preimage().len() - skip_start - skip_end;
+
+                lines.splice(
+                    position..position + preimage_length,
+                    postimage.iter().map(|line| line.to_string()),
+                );
+                minimum_position = position + postimage.len();
+                // the next hunks are expected to be moved as much as this one was
+                offset += position as isize - expected as isize;
+                offset += postimage.len() as isize - preimage_length as isize;
+            }
+            None => rejected.push(RejectedHunk {
+                number: number + 1,
+                line: hunk.old_start,
+            }),
+        }
+    }
+
+    if rejected.is_empty() {
+        Ok(lines.concat())
+    } else {
+        Err(rejected)
+    }
+}
+
+/// Finds the position of `preimage` in `lines` closest to `expected`, not before `minimum`.
+fn find_preimage(
+    lines: &[String],
+    preimage: &[&str],
+    expected: usize,
+    minimum: usize,
+) -> Option<usize> {
+    if preimage.len() > lines.len() {
+        return None;
+    }
+    let last_position: usize = lines.len() - preimage.len();
+    let expected: usize = expected.clamp(minimum.min(last_position), last_position);
+
+    let matches_at = |position: usize| -> bool {
+        position >= minimum
+            && position <= last_position
+            && lines[position..position + preimage.len()]
+                .iter()
+                .zip(preimage)
+                .all(|(line, expected_line)| line == expected_line)
+    };
+
+    // look alternately after and before the expected position
+    for distance in 0..=lines.len() {
+        if matches_at(expected + distance) {
+            return Some(expected + distance);
+        }
+        if distance > 0 && distance <= expected && matches_at(expected - distance) {
+            return Some(expected - distance);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "From 1234 Mon Sep 17 00:00:00 2001
+Subject: [PATCH] Example
+
+diff --git a/hello.txt b/hello.txt
+index 1111111..2222222 100644
+--- a/hello.txt
++++ b/hello.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+ three
+diff --git a/old.txt b/new.txt
+similarity index 90%
+rename from old.txt
+rename to new.txt
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index 3333333..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+\\ No newline at end of file
+";
+
+    #[test]
+    fn test_parse_patch_reads_git_headers() {
+        let patches: Vec<FilePatch> = parse_patch(PATCH).unwrap();
+        assert_eq!(patches.len(), 3);
+
+        assert_eq!(patches[0].old_path.as_deref(), Some("hello.txt"));
+        assert_eq!(patches[0].old_hash.as_deref(), Some("1111111"));
+        assert_eq!(
+            patches[0].hunks[0].lines[1],
+            HunkLine::Delete("two\n".to_string())
+        );
+
+        assert_eq!(patches[1].old_path.as_deref(), Some("old.txt"));
+        assert_eq!(patches[1].new_path.as_deref(), Some("new.txt"));
+        assert!(patches[1].hunks.is_empty());
+
+        assert_eq!(patches[2].new_path, None);
+        assert_eq!(
+            patches[2].hunks[0].lines,
+            vec![HunkLine::Delete("bye".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_apply_hunks_with_offset_and_rejection() {
+        let patches: Vec<FilePatch> = parse_patch(PATCH).unwrap();
+
+        // two lines were added at the top since the patch was made
+        let content: &str = "zero\nzero bis\none\ntwo\nthree\n";
+        assert_eq!(
+            apply_hunks(content, &patches[0].hunks),
+            Ok("zero\nzero bis\none\nTWO\nthree\n".to_string())
+        );
+
+        // the line to change is gone
+        let content: &str = "one\n2\nthree\n";
+        assert_eq!(
+            apply_hunks(content, &patches[0].hunks),
+            Err(vec![RejectedHunk { number: 1, line: 1 }])
+        );
+    }
+
+    #[test]
+    fn test_apply_hunks_with_fuzz() {
+        let patches: Vec<FilePatch> = parse_patch(PATCH).unwrap();
+
+        // the first context line changed, but the hunk still applies by ignoring it
+        let content: &str = "ONE\ntwo\nthree\n";
+        assert_eq!(
+            apply_hunks(content, &patches[0].hunks),
+            Ok("ONE\nTWO\nthree\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_hunks_keeps_one_line_when_fuzzing() {
+        // short hunks must not lose all their lines to fuzz and apply anywhere
+        let patch: &str = "--- a/file.txt
++++ b/file.txt
+@@ -1,2 +1,3 @@
+ one
++new
+ two
+@@ -4,2 +5,2 @@
+ four
+ five
+";
+        let patches: Vec<FilePatch> = parse_patch(patch).unwrap();
+        assert_eq!(
+            apply_hunks("x\ny\nz\nw\n", &patches[0].hunks),
+            Err(vec![
+                RejectedHunk { number: 1, line: 1 },
+                RejectedHunk { number: 2, line: 4 }
+            ])
+        );
+
+        // they still apply where they match exactly
+        assert_eq!(
+            apply_hunks("x\ny\nz\nfour\nfive\n", &patches[0].hunks),
+            Err(vec![RejectedHunk { number: 1, line: 1 }])
+        );
+    }
+
+    #[test]
+    fn test_apply_hunks_carries_offset_to_next_hunks() {
+        let patch: &str = "--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-first
++FIRST
+@@ -5 +5 @@
+-dup
++DUP
+";
+        let patches: Vec<FilePatch> = parse_patch(patch).unwrap();
+
+        // four lines were added at the top, and another "dup" line appeared between the hunks:
+        // the second hunk is looked for four lines further down, like the first one was
+        let content: &str = "new\nnew\nnew\nnew\nfirst\na\ndup\nc\ndup\n";
+        assert_eq!(
+            apply_hunks(content, &patches[0].hunks),
+            Ok("new\nnew\nnew\nnew\nFIRST\na\ndup\nc\nDUP\n".to_string())
+        );
+    }
+}
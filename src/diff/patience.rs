@@ -0,0 +1,175 @@
+use super::{common_ends, myers, Edit};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Computes an edit script with the patience algorithm: lines appearing exactly once on both
+/// sides are matched first, keeping the longest sequence of them that is in the same order,
+/// then the gaps between these anchors are diffed recursively. Regions without any unique
+/// common line fall back to Myers.
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let mut edits: Vec<Edit> = Vec::new();
+    diff_range(old, new, 0..old.len(), 0..new.len(), &mut edits);
+    edits
+}
+
+fn diff_range(
+    old: &[&str],
+    new: &[&str],
+    old_range: Range<usize>,
+    new_range: Range<usize>,
+    edits: &mut Vec<Edit>,
+) {
+    let (prefix, suffix): (usize, usize) = common_ends(old, new, &old_range, &new_range);
+    for offset in 0..prefix {
+        edits.push(Edit::Equal(
+            old_range.start + offset,
+            new_range.start + offset,
+        ));
+    }
+
+    let old_middle: Range<usize> = old_range.start + prefix..old_range.end - suffix;
+    let new_middle: Range<usize> = new_range.start + prefix..new_range.end - suffix;
+
+    if old_middle.is_empty() || new_middle.is_empty() {
+        // only insertions or only deletions: any algorithm gives the same script
+        myers::diff_range(
+            old,
+            new,
+            old_middle.clone(),
+            new_middle.clone(),
+            true,
+            edits,
+        );
+    } else {
+        let anchors: Vec<(usize, usize)> = unique_anchors(old, new, &old_middle, &new_middle);
+        if anchors.is_empty() {
+            myers::diff_range(
+                old,
+                new,
+                old_middle.clone(),
+                new_middle.clone(),
+                false,
+                edits,
+            );
+        } else {
+            let mut old_position: usize = old_middle.start;
+            let mut new_position: usize = new_middle.start;
+            for (old_index, new_index) in anchors {
+                diff_range(
+                    old,
+                    new,
+                    old_position..old_index,
+                    new_position..new_index,
+                    edits,
+                );
+                edits.push(Edit::Equal(old_index, new_index));
+                old_position = old_index + 1;
+                new_position = new_index + 1;
+            }
+            diff_range(
+                old,
+                new,
+                old_position..old_middle.end,
+                new_position..new_middle.end,
+                edits,
+            );
+        }
+    }
+
+    for offset in 0..suffix {
+        edits.push(Edit::Equal(
+            old_middle.end + offset,
+            new_middle.end + offset,
+        ));
+    }
+}
+
+/// Returns the longest increasing sequence of (old index, new index) pairs of lines that
+/// appear exactly once in both ranges.
+fn unique_anchors(
+    old: &[&str],
+    new: &[&str],
+    old_range: &Range<usize>,
+    new_range: &Range<usize>,
+) -> Vec<(usize, usize)> {
+    // line -> (occurrences in old, index in old, occurrences in new, index in new)
+    let mut counts: HashMap<&str, (usize, usize, usize, usize)> = HashMap::new();
+    for old_index in old_range.clone() {
+        let entry: &mut (usize, usize, usize, usize) =
+            counts.entry(old[old_index]).or_insert((0, 0, 0, 0));
+        entry.0 += 1;
+        entry.1 = old_index;
+    }
+    for new_index in new_range.clone() {
+        if let Some(entry) = counts.get_mut(new[new_index]) {
+            entry.2 += 1;
+            entry.3 = new_index;
+        }
+    }
+
+    // unique common lines, in the order of the old side
+    let mut pairs: Vec<(usize, usize)> = counts
+        .values()
+        .filter(|entry| entry.0 == 1 && entry.2 == 1)
+        .map(|entry| (entry.1, entry.3))
+        .collect();
+    pairs.sort();
+
+    longest_increasing_sequence(&pairs)
+}
+
+/// Patience sorting: keeps the longest subsequence whose new indices are increasing.
+fn longest_increasing_sequence(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // top of each pile, as an index into `pairs`
+    let mut pile_tops: Vec<usize> = Vec::new();
+    // for each pair, the top of the previous pile when it was placed
+    let mut predecessors: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (index, pair) in pairs.iter().enumerate() {
+        let pile: usize = pile_tops.partition_point(|&top| pairs[top].1 < pair.1);
+        predecessors[index] = if pile > 0 {
+            Some(pile_tops[pile - 1])
+        } else {
+            None
+        };
+        if pile == pile_tops.len() {
+            pile_tops.push(index);
+        } else {
+            pile_tops[pile] = index;
+        }
+    }
+
+    let mut sequence: Vec<(usize, usize)> = Vec::new();
+    let mut current: Option<usize> = pile_tops.last().copied();
+    while let Some(index) = current {
+        sequence.push(pairs[index]);
+        current = predecessors[index];
+    }
+    sequence.reverse();
+
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patience_keeps_unique_lines_together() {
+        // swapping two functions: one of them is kept whole instead of matching lone braces
+        let old: Vec<&str> = vec!["fn a() {\n", "a\n", "}\n", "fn b() {\n", "b\n", "}\n"];
+        let new: Vec<&str> = vec!["fn b() {\n", "b\n", "}\n", "fn a() {\n", "a\n", "}\n"];
+
+        let edits: Vec<Edit> = diff(&old, &new);
+        assert_eq!(crate::diff::tests::apply_edits(&edits, &old, &new), new);
+
+        let kept: Vec<&str> = edits
+            .iter()
+            .filter_map(|edit| match *edit {
+                Edit::Equal(old_index, _) => Some(old[old_index]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kept, vec!["fn b() {\n", "b\n", "}\n"]);
+    }
+}
@@ -0,0 +1,225 @@
+use super::{ChangeKind, FileDiff};
+use std::collections::HashMap;
+
+/// Pairs deleted and added files whose contents are similar enough into renames, and, if
+/// `find_copies` is set, added files similar to a modified or deleted file into copies.
+/// Exact matches are paired first, then the most similar pairs.
+pub fn detect_renames(
+    file_diffs: Vec<FileDiff>,
+    threshold: u8,
+    find_copies: bool,
+) -> Vec<FileDiff> {
+    let mut deleted: Vec<FileDiff> = Vec::new();
+    let mut added: Vec<FileDiff> = Vec::new();
+    let mut result: Vec<FileDiff> = Vec::new();
+    for file_diff in file_diffs {
+        match file_diff.kind {
+            ChangeKind::Deleted => deleted.push(file_diff),
+            ChangeKind::Added => added.push(file_diff),
+            _ => result.push(file_diff),
+        }
+    }
+
+    // every (score, deleted index, added index) pair above the threshold
+    let mut candidates: Vec<(u8, usize, usize)> = Vec::new();
+    for (added_index, added_file) in added.iter().enumerate() {
+        for (deleted_index, deleted_file) in deleted.iter().enumerate() {
+            let score: u8 = if added_file.new_hash == deleted_file.old_hash {
+                100
+            } else {
+                similarity(&deleted_file.old_content, &added_file.new_content)
+            };
+            if score >= threshold {
+                candidates.push((score, deleted_index, added_index));
+            }
+        }
+    }
+    // best scores first, then in path order so that the result is stable
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut deleted_used: Vec<bool> = vec![false; deleted.len()];
+    let mut added_used: Vec<bool> = vec![false; added.len()];
+    for (score, deleted_index, added_index) in candidates {
+        if deleted_used[deleted_index] || added_used[added_index] {
+            continue;
+        }
+        deleted_used[deleted_index] = true;
+        added_used[added_index] = true;
+
+        let source: &FileDiff = &deleted[deleted_index];
+        let target: &FileDiff = &added[added_index];
+        result.push(FileDiff {
+            kind: ChangeKind::Renamed(score),
+            old_path: source.old_path.clone(),
+            new_path: target.new_path.clone(),
+            old_hash: source.old_hash.clone(),
+            new_hash: target.new_hash.clone(),
+            old_content: source.old_content.clone(),
+            new_content: target.new_content.clone(),
+        });
+    }
+
+    if find_copies {
+        for (added_index, target) in added.iter().enumerate() {
+            if added_used[added_index] {
+                continue;
+            }
+
+            // copies may come from any file of the old side that changed, even a renamed one
+            let mut best: Option<(u8, &FileDiff)> = None;
+            let sources = result
+                .iter()
+                .filter(|file_diff| file_diff.kind == ChangeKind::Modified)
+                .chain(deleted.iter());
+            for source in sources {
+                let score: u8 = if target.new_hash == source.old_hash {
+                    100
+                } else {
+                    similarity(&source.old_content, &target.new_content)
+                };
+                if score >= threshold && best.is_none_or(|(best_score, _)| score > best_score) {
+                    best = Some((score, source));
+                }
+            }
+
+            if let Some((score, source)) = best {
+                let copy: FileDiff = FileDiff {
+                    kind: ChangeKind::Copied(score),
+                    old_path: source.old_path.clone(),
+                    new_path: target.new_path.clone(),
+                    old_hash: source.old_hash.clone(),
+                    new_hash: target.new_hash.clone(),
+                    old_content: source.old_content.clone(),
+                    new_content: target.new_content.clone(),
+                };
+                added_used[added_index] = true;
+                result.push(copy);
+            }
+        }
+    }
+
+    for (deleted_index, file_diff) in deleted.into_iter().enumerate() {
+        if !deleted_used[deleted_index] {
+            result.push(file_diff);
+        }
+    }
+    for (added_index, file_diff) in added.into_iter().enumerate() {
+        if !added_used[added_index] {
+            result.push(file_diff);
+        }
+    }
+
+    result.sort_by(|a, b| a.new_path.cmp(&b.new_path));
+    result
+}
+
+/// Estimates how similar two contents are, in percent: the size of the lines they have in
+/// common relative to the size of the larger one.
+pub fn similarity(old_content: &[u8], new_content: &[u8]) -> u8 {
+    let max_size: usize = old_content.len().max(new_content.len());
+    let min_size: usize = old_content.len().min(new_content.len());
+    if max_size == 0 {
+        return 100;
+    }
+    if min_size == 0 {
+        return 0;
+    }
+
+    // occurrences of each line of the old content
+    let mut old_lines: HashMap<&[u8], usize> = HashMap::new();
+    for line in old_content.split_inclusive(|&byte| byte == b'\n') {
+        *old_lines.entry(line).or_insert(0) += 1;
+    }
+
+    let mut common_size: usize = 0;
+    for line in new_content.split_inclusive(|&byte| byte == b'\n') {
+        if let Some(count) = old_lines.get_mut(line) {
+            if *count > 0 {
+                *count -= 1;
+                common_size += line.len();
+            }
+        }
+    }
+
+    (common_size * 100 / max_size) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::DEFAULT_RENAME_THRESHOLD;
+
+    fn file_diff(kind: ChangeKind, path: &str, hash: &str, content: &str) -> FileDiff {
+        let (old_hash, new_hash, old_content, new_content): (&str, &str, &str, &str) = match kind {
+            ChangeKind::Added => ("", hash, "", content),
+            _ => (hash, "", content, ""),
+        };
+        FileDiff {
+            kind,
+            old_path: path.to_string(),
+            new_path: path.to_string(),
+            old_hash: old_hash.to_string(),
+            new_hash: new_hash.to_string(),
+            old_content: old_content.as_bytes().to_vec(),
+            new_content: new_content.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_detect_renames_pairs_similar_files() {
+        let file_diffs: Vec<FileDiff> = vec![
+            file_diff(ChangeKind::Deleted, "old.txt", "1111", "a\nb\nc\nd\n"),
+            file_diff(ChangeKind::Deleted, "gone.txt", "2222", "something else\n"),
+            file_diff(ChangeKind::Added, "new.txt", "3333", "a\nb\nc\nD\n"),
+            file_diff(ChangeKind::Added, "other.txt", "4444", "unrelated\n"),
+        ];
+
+        let result: Vec<FileDiff> = detect_renames(file_diffs, DEFAULT_RENAME_THRESHOLD, false);
+        let summary: Vec<(String, String, String)> = result
+            .iter()
+            .map(|diff| {
+                (
+                    diff.kind.status(),
+                    diff.old_path.clone(),
+                    diff.new_path.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "D".to_string(),
+                    "gone.txt".to_string(),
+                    "gone.txt".to_string()
+                ),
+                (
+                    "R075".to_string(),
+                    "old.txt".to_string(),
+                    "new.txt".to_string()
+                ),
+                (
+                    "A".to_string(),
+                    "other.txt".to_string(),
+                    "other.txt".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_renames_finds_copies_when_asked() {
+        let mut modified: FileDiff = file_diff(ChangeKind::Modified, "src.txt", "1111", "x\ny\n");
+        modified.new_hash = "2222".to_string();
+        modified.new_content = b"x\ny\nz\n".to_vec();
+        let file_diffs: Vec<FileDiff> = vec![
+            modified,
+            file_diff(ChangeKind::Added, "copy.txt", "1111", "x\ny\n"),
+        ];
+
+        let result: Vec<FileDiff> = detect_renames(file_diffs, DEFAULT_RENAME_THRESHOLD, true);
+        assert_eq!(result[0].kind, ChangeKind::Copied(100));
+        assert_eq!(result[0].old_path, "src.txt");
+        assert_eq!(result[1].kind, ChangeKind::Modified);
+    }
+}
@@ -1,18 +1,38 @@
 mod commands;
+mod config;
+mod diff;
 mod utils;
 
 use crate::utils::RepoPath;
+use commands::am::am;
+use commands::apply::*;
+use commands::blame::*;
+use commands::branch::*;
 use commands::cat_file::cat_file;
-use commands::checkout::checkout;
+use commands::check_ref_format::*;
+use commands::checkout::*;
 use commands::commit::*;
-use commands::commit_tree::commit_tree;
+use commands::commit_tree::*;
+use commands::diff::*;
 use commands::fetch::*;
+use commands::for_each_ref::*;
+use commands::format_patch::*;
+use commands::gc::gc;
 use commands::get_head_hash::*;
 use commands::hash_object::hash_object;
-use commands::init::init;
+use commands::init::{init, parse_init_options};
 use commands::log::*;
+use commands::pack_refs::pack_refs;
 use commands::push::*;
+use commands::reflog::reflog;
+use commands::reset::*;
+use commands::restore::*;
+use commands::rev_parse::*;
+use commands::show::*;
+use commands::show_ref::*;
+use commands::stash::stash;
 use commands::symbolic_ref::*;
+use commands::tag::*;
 use commands::update_index::*;
 use commands::update_ref::*;
 use commands::write_tree::write_tree;
@@ -28,24 +48,49 @@ fn print_usage() {
     println!("  --version                          Show the rgit version and exit.");
     println!();
     println!("Commands:");
-    println!("  init                               Initialize a new rgit repository.");
+    println!("  init [--bare] [-b <branch>] [--template=<dir>] [<dir>]  Initialize a new rgit repository.");
     println!("  hash-object <file>                 Compute and store the hash of a file.");
     println!("  cat-file <hash>                    Display the contents of an object.");
     println!("  index --add <file> <blob_hash>      Add a file to the index.");
     println!("  index --modify <file> <blob_hash>   Modify an entry in the index.");
     println!("  index --remove <file>               Remove a file from the index.");
     println!("  write-tree                         Write the current index to a tree object.");
-    println!("  commit-tree <message> <author> <tree_hash> [parent_hash]  Create a commit object.");
-    println!("  checkout <commit_hash|branch>       Checkout a specific commit or branch.");
-    println!("  log <commit_hash|branch>            Show the log starting from the given commit or branch.");
-    println!("  update-ref <ref_name> <commit_hash> Update a reference to a commit hash.");
-    println!("  symbolic-ref <ref_name> <target_ref> Set a symbolic reference.");
+    println!("  commit-tree <tree> [-p <parent>]... [-m <message>]... [--author <author>]  Create a commit object.");
+    println!("  branch [-v|-vv] | <name> [<start>] | (-d|-D) <name>... | -m [<old>] <new>  List, create, delete or rename branches.");
+    println!("  tag [-l [<pattern>]] | [-f] [-a -m <message>] <name> [<rev>] | -d <name>...  List, create or delete tags.");
+    println!("  checkout [-f|--force] [-m|--merge] <commit_hash|branch>  Checkout a specific commit or branch.");
+    println!("  checkout [<rev>] -- <paths>...      Restore files from the index or a commit, keeping HEAD.");
+    println!("  reset [--soft|--mixed|--hard] [<rev>]  Move the current branch and reset the index and worktree.");
+    println!("  reset [<rev>] [--] <paths>...       Unstage files.");
+    println!("  restore [--source=<rev>] [--staged] [--worktree] <paths>...  Restore files in the index or the worktree.");
+    println!("  log [<commit_hash|branch>] [--follow <path>]  Show the log starting from the given commit or branch.");
+    println!(
+        "  update-ref [-m <reason>] <ref_name> <commit_hash>  Update a reference to a commit hash."
+    );
+    println!("  symbolic-ref [-m <reason>] <ref_name> <target_ref>  Set a symbolic reference.");
+    println!("  reflog [show] [<ref>] | expire [--expire=<time>] (--all|<ref>...) | delete <ref>@{{<n>}}...  Show or prune the history of a ref.");
+    println!("  gc [--prune=<time>|--no-prune]      Pack refs and remove unreachable objects.");
     println!("  push <remote_path> <branch>         Push local changes to a remote repository.");
-    println!("  fetch <remote_path> <branch>        Fetch changes from a remote repository.");
+    println!(
+        "  fetch [--name <remote>] <remote_path> <branch>  Fetch changes from a remote repository."
+    );
+    println!("  pack-refs [--all] [--no-prune]     Move loose tags (all refs with --all) into packed-refs.");
+    println!("  rev-parse [--short|--symbolic-full-name|--abbrev-ref] <rev>...  Resolve revisions to object hashes or ref names.");
+    println!("  show-ref [--heads] [--tags] [-d] [<pattern>...]  List refs and the objects they point to.");
+    println!("  for-each-ref [--sort=<key>] [--format=<format>] [--count=<n>] [<pattern>...]  List refs with formatted fields.");
+    println!("  check-ref-format [--allow-onelevel] [--normalize] [--branch] <refname>  Check that a name is a valid ref name.");
     println!("  get-head-hash                       Display the commit hash pointed to by HEAD.");
     println!("  add <file_name>                     Add a file to the staging area.");
     println!("  remove <file_name>                  Remove a file from the index.");
-    println!("  commit <commit_message> <author>    Commit the staged changes.");
+    println!("  commit [-m <message>]... [-F <file>] [-t <template>] [-e] [--cleanup=<mode>] [--author <author>]  Commit the staged changes.");
+    println!("  commit <commit_message> [<author>]  Commit the staged changes with a message.");
+    println!("  diff [--cached] [<rev> [<rev>]] [-M] [-C]  Show changes between the worktree, index and commits.");
+    println!("  apply [--check] [--cached|--index] [--3way] <patch>  Apply a unified patch.");
+    println!("  format-patch [-o <dir>] [--stdout] [-<n>] [<since>[..<until>]]  Export commits as patch mails.");
+    println!("  am [--3way] <mbox>...               Apply patch mails as commits.");
+    println!("  blame [-L <start>,<end>] [--porcelain] [<rev>] <file>  Show the commit that last changed each line.");
+    println!("  show [<rev>|<rev>:<path>]...          Show a commit with its changes, a tree, a blob or a tag.");
+    println!("  stash [push [-m <message>] [-u]|list|show [-p]|apply [--index]|pop [--index]|drop] [<stash>]  Save and restore local changes.");
     println!();
 }
 
@@ -63,7 +108,7 @@ fn main() {
         "--version" => {
             println!("rgit v0.0");
         }
-        "init" => init(),
+        "init" => init(&parse_init_options(&args[2..])),
         "hash-object" => {
             if args.len() != 3 {
                 eprintln!("Usage: rgit hash-object <file>");
@@ -124,64 +169,100 @@ fn main() {
             println!("{}", tree_hash);
         }
         "commit-tree" => {
-            if args.len() < 5 || args.len() > 6 {
-                println!("{}", args.len());
-                eprintln!(
-                    "Usage: rgit commit-tree <commit_message> <author> <tree_hash> [parent_hash]"
-                );
+            let options: CommitTreeOptions = parse_commit_tree_options(&args[2..]);
+            commit_tree_command(&options);
+        }
+        "checkout" if args[2..].iter().any(|arg| arg == "--") => {
+            let separator: usize = args.iter().position(|arg| arg == "--").unwrap();
+            let source: Option<String> = match &args[2..separator] {
+                [] => None,
+                [source] => Some(source.clone()),
+                _ => {
+                    eprintln!("Usage: rgit checkout [<rev>] -- <paths>...");
+                    std::process::exit(1);
+                }
+            };
+            // files come from the index, or from the commit into both the index and the worktree
+            let options: RestoreOptions = RestoreOptions {
+                staged: source.is_some(),
+                worktree: true,
+                overlay: true,
+                source,
+                paths: args[separator + 1..].to_vec(),
+            };
+            if options.paths.is_empty() {
+                eprintln!("Usage: rgit checkout [<rev>] -- <paths>...");
                 std::process::exit(1);
             }
-
-            let commit_name: &str = &args[2];
-            let author: &str = &args[3];
-            let tree_hash: &str = &args[4];
-
-            let parent: Option<&str> = if args.len() == 6 && args[5].to_lowercase() != "none" {
-                Some(&args[5])
-            } else {
-                None
-            };
-
-            let commit_hash: String = commit_tree(
-                commit_name,
-                author,
-                tree_hash.to_string(),
-                parent.map(|x| x),
-            );
-            println!("{}", commit_hash);
+            restore(&options);
         }
+        "branch" => branch(&parse_branch_options(&args[2..])),
+        "tag" => tag(&parse_tag_options(&args[2..])),
+        "stash" => stash(&args[2..]),
+        "reset" => reset(&parse_reset_options(&args[2..])),
+        "restore" => restore(&parse_restore_options(&args[2..])),
         "checkout" => {
-            if args.len() != 3 {
-                eprintln!("Usage: rgit checkout <commit_hash>");
-                std::process::exit(1);
+            let mut mode: CheckoutMode = CheckoutMode::Safe;
+            let mut target: Option<&str> = None;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "-f" | "--force" => mode = CheckoutMode::Force,
+                    "-m" | "--merge" => mode = CheckoutMode::Merge,
+                    arg if target.is_none() && (arg == "-" || !arg.starts_with('-')) => {
+                        target = Some(arg)
+                    }
+                    _ => {
+                        eprintln!(
+                            "Usage: rgit checkout [-f|--force] [-m|--merge] <commit_hash|branch>"
+                        );
+                        std::process::exit(1);
+                    }
+                }
             }
-            checkout(&args[2]);
-        }
-        "log" => {
-            if args.len() != 3 {
-                eprintln!("Usage: rgit log <commit_hash>");
-                std::process::exit(1);
+            match target {
+                Some(target) => checkout(target, mode),
+                None => {
+                    eprintln!(
+                        "Usage: rgit checkout [-f|--force] [-m|--merge] <commit_hash|branch>"
+                    );
+                    std::process::exit(1);
+                }
             }
-            log(&args[2]);
         }
-        "update-ref" => {
-            if args.len() != 4 {
-                eprintln!("Usage: rgit update-ref <ref_name> <commit_hash>");
-                std::process::exit(1);
+        "log" => {
+            let mut target: Option<&str> = None;
+            let mut follow_path: Option<&str> = None;
+            let mut index: usize = 2;
+            while index < args.len() {
+                match args[index].as_str() {
+                    "--follow" if index + 1 < args.len() => {
+                        follow_path = Some(&args[index + 1]);
+                        index += 1;
+                    }
+                    arg if target.is_none() && !arg.starts_with('-') => target = Some(arg),
+                    _ => {
+                        eprintln!("Usage: rgit log [<commit_hash>] [--follow <path>]");
+                        std::process::exit(1);
+                    }
+                }
+                index += 1;
             }
-            let ref_name: &str = &args[2];
-            let commit_hash: &str = &args[3];
-            update_ref(ref_name, commit_hash);
+            log(target.unwrap_or("HEAD"), follow_path);
         }
+        "update-ref" => update_ref_command(&parse_update_ref_options(&args[2..])),
         "symbolic-ref" => {
-            if args.len() != 4 {
-                eprintln!("Usage: rgit symbolic-ref <ref_name> <target_ref>");
+            let (reason, rest): (&str, &[String]) = match &args[2..] {
+                [flag, reason, rest @ ..] if flag == "-m" => (reason.as_str(), rest),
+                rest => ("symbolic-ref", rest),
+            };
+            if rest.len() != 2 {
+                eprintln!("Usage: rgit symbolic-ref [-m <reason>] <ref_name> <target_ref>");
                 std::process::exit(1);
             }
-            let ref_name: &str = &args[2];
-            let target_ref: &str = &args[3];
-            symbolic_ref(ref_name, target_ref);
+            symbolic_ref(&rest[0], &rest[1], reason);
         }
+        "reflog" => reflog(&args[2..]),
+        "gc" => gc(&args[2..]),
         "push" => {
             if args.len() != 4 {
                 eprintln!("Usage: rgit push <remote_path> <branch>");
@@ -192,13 +273,17 @@ fn main() {
             push(remote_path, branch);
         }
         "fetch" => {
-            if args.len() != 4 {
-                eprintln!("Usage: rgit fetch <remote_path> <branch>");
+            let (remote_name, rest): (&str, &[String]) = match &args[2..] {
+                [flag, name, rest @ ..] if flag == "--name" => (name.as_str(), rest),
+                rest => (DEFAULT_REMOTE_NAME, rest),
+            };
+            if rest.len() != 2 {
+                eprintln!("Usage: rgit fetch [--name <remote>] <remote_path> <branch>");
                 std::process::exit(1);
             }
-            let remote_path: &str = &args[2];
-            let branch: &str = &args[3];
-            fetch(remote_path, branch);
+            let remote_path: &str = &rest[0];
+            let branch: &str = &rest[1];
+            fetch(remote_name, remote_path, branch);
         }
         "get-head-hash" => {
             if args.len() != 2 {
@@ -248,16 +333,48 @@ fn main() {
             }
         }
         "commit" => {
-            if args.len() != 4 {
-                println!("{}", args.len());
-                eprintln!("Usage: rgit commit <commit_message> <author>");
-                std::process::exit(1);
-            }
-
-            let commit_message: &str = &args[2];
-            let author: &str = &args[3];
-
-            commit(commit_message, author);
+            let options: CommitOptions = parse_commit_options(&args[2..]);
+            commit_command(&options);
+        }
+        "diff" => {
+            let options: DiffOptions = parse_diff_options(&args[2..]);
+            diff(&options);
+        }
+        "apply" => {
+            let options: ApplyOptions = parse_apply_options(&args[2..]);
+            apply(&options);
+        }
+        "format-patch" => {
+            let options: FormatPatchOptions = parse_format_patch_options(&args[2..]);
+            format_patch(&options);
+        }
+        "am" => {
+            let three_way: bool = args[2..].iter().any(|arg| arg == "--3way" || arg == "-3");
+            let mbox_paths: Vec<String> = args[2..]
+                .iter()
+                .filter(|arg| *arg != "--3way" && *arg != "-3")
+                .cloned()
+                .collect();
+            am(&mbox_paths, three_way);
+        }
+        "blame" => {
+            let options: BlameOptions = parse_blame_options(&args[2..]);
+            blame(&options);
+        }
+        "show" => {
+            let options: ShowOptions = parse_show_options(&args[2..]);
+            show(&options);
+        }
+        "pack-refs" => pack_refs(&args[2..]),
+        "rev-parse" => {
+            let (output, revisions): (RevParseOutput, Vec<String>) =
+                parse_rev_parse_options(&args[2..]);
+            rev_parse_command(output, &revisions);
+        }
+        "show-ref" => show_ref(&parse_show_ref_options(&args[2..])),
+        "for-each-ref" => for_each_ref(&parse_for_each_ref_options(&args[2..])),
+        "check-ref-format" => {
+            check_ref_format_command(&parse_check_ref_format_options(&args[2..]));
         }
         _ => eprintln!("Unknown command: {}", args[1]),
     }
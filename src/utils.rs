@@ -1,18 +1,33 @@
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 use sha1::{Digest, Sha1};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::commands::cat_file::cat_file;
+use crate::commands::cat_file::{cat_file, read_object};
+use crate::commands::get_head_hash::get_head_hash;
+use crate::config::get_config;
 
 pub enum RepoPath {
     Local,
     Remote(String),
 }
 
+impl RepoPath {
+    /// Returns the repository directory: `.rgit`, or the path of a remote repository.
+    pub fn repo_dir(&self) -> PathBuf {
+        match self {
+            RepoPath::Local => PathBuf::from(".rgit"),
+            RepoPath::Remote(remote_repo_path) => PathBuf::from(remote_repo_path),
+        }
+    }
+}
+
 pub fn create_object_path(repo_path: &RepoPath, hash: &str) -> PathBuf {
     match repo_path {
         RepoPath::Local => {
@@ -63,6 +78,762 @@ pub fn hash_and_store(object_type: &str, content: &[u8]) -> String {
     hash_str
 }
 
+/// Computes the hash an object would have in the database, without storing it.
+pub fn compute_hash(object_type: &str, content: &[u8]) -> String {
+    let header: String = format!("{} {}\0", object_type, content.len());
+    let mut hash_data: Vec<u8> = Vec::new();
+    hash_data.extend(header.as_bytes());
+    hash_data.extend(content);
+
+    let hash: sha1::digest::Output<Sha1> = Sha1::digest(&hash_data);
+    format!("{:x}", hash)
+}
+
+/// Checks whether an object is in the local database.
+pub fn object_exists(hash: &str) -> bool {
+    hash.len() > 2
+        && Path::new(".rgit")
+            .join("objects")
+            .join(&hash[..2])
+            .join(&hash[2..])
+            .is_file()
+}
+
+/// Returns the full hash of the local object starting with `prefix`, if exactly one matches.
+pub fn find_object_by_prefix(prefix: &str) -> Option<String> {
+    if prefix.len() < 4 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let (dir, file_prefix): (&str, &str) = prefix.split_at(2);
+    let object_dir: PathBuf = Path::new(".rgit").join("objects").join(dir);
+    let entries: fs::ReadDir = fs::read_dir(object_dir).ok()?;
+
+    let matches: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|file_name| file_name.starts_with(file_prefix))
+        .map(|file_name| format!("{}{}", dir, file_name))
+        .collect();
+
+    if matches.len() == 1 {
+        matches.into_iter().next()
+    } else {
+        None
+    }
+}
+
+/// Resolves a revision expression (see `rev_parse`) to a commit hash, following annotated
+/// tags. HEAD is empty before the first commit.
+pub fn resolve_revision(target: &str) -> String {
+    if target == "HEAD" {
+        return get_head_hash();
+    }
+
+    match crate::commands::rev_parse::rev_parse(target) {
+        Ok(hash) => peel_to_commit(&hash),
+        // an unknown revision is left as is, to fail like an unknown commit
+        Err(_) => target.to_string(),
+    }
+}
+
+/// Finds the full name of a ref from a short name, in git's search order: `<name>` (if it
+/// starts with `refs/`), `refs/<name>`, `refs/tags/<name>`, `refs/heads/<name>`,
+/// `refs/remotes/<name>` and `refs/remotes/<name>/HEAD`.
+pub fn resolve_ref(name: &str) -> Option<String> {
+    if name.is_empty() || verify_path(name).is_err() {
+        return None;
+    }
+
+    let mut candidates: Vec<String> = Vec::new();
+    if name.starts_with("refs/") {
+        candidates.push(name.to_string());
+    }
+    for prefix in ["refs/", "refs/tags/", "refs/heads/", "refs/remotes/"] {
+        candidates.push(format!("{}{}", prefix, name));
+    }
+    candidates.push(format!("refs/remotes/{}/HEAD", name));
+
+    let packed_refs: BTreeMap<String, String> = read_packed_refs();
+    candidates.into_iter().find(|ref_name| {
+        Path::new(".rgit").join(ref_name).is_file() || packed_refs.contains_key(ref_name)
+    })
+}
+
+/// Reads the commit hash stored in a ref, given by its full name. Symbolic refs (e.g. an
+/// attached HEAD) are followed, and a ref without file is looked up in `packed-refs`.
+pub fn read_ref(ref_name: &str) -> Option<String> {
+    read_repo_ref(&RepoPath::Local, ref_name)
+}
+
+/// Reads a ref of a local or remote repository, like `read_ref`.
+pub fn read_repo_ref(repo_path: &RepoPath, ref_name: &str) -> Option<String> {
+    let ref_path: PathBuf = repo_path.repo_dir().join(ref_name);
+    if !ref_path.is_file() {
+        return read_repo_packed_refs(repo_path).remove(ref_name);
+    }
+    let content: String = fs::read_to_string(ref_path).ok()?;
+    match content.trim().strip_prefix("ref: ") {
+        Some(target_ref) => read_repo_ref(repo_path, target_ref),
+        None if content.trim().is_empty() => None,
+        None => Some(content.trim().to_string()),
+    }
+}
+
+/// Reads `.rgit/packed-refs`, which stores many refs in one file:
+///
+/// ```text
+/// # pack-refs with: peeled fully-peeled sorted
+/// <hash> <ref>
+/// ^<hash of the commit an annotated tag points to>
+/// ```
+///
+/// Returns the hash of each ref.
+pub fn read_packed_refs() -> BTreeMap<String, String> {
+    read_repo_packed_refs(&RepoPath::Local)
+}
+
+/// Reads the `packed-refs` file of a local or remote repository, like `read_packed_refs`.
+pub fn read_repo_packed_refs(repo_path: &RepoPath) -> BTreeMap<String, String> {
+    let content: String =
+        fs::read_to_string(repo_path.repo_dir().join("packed-refs")).unwrap_or_default();
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .filter_map(|line| line.split_once(' '))
+        .map(|(hash, ref_name)| (ref_name.trim().to_string(), hash.to_string()))
+        .collect()
+}
+
+/// Rewrites `.rgit/packed-refs` with the given refs, through a lock file. Annotated tags are
+/// followed by the commit they point to.
+pub fn write_packed_refs(packed_refs: &BTreeMap<String, String>) -> Result<(), String> {
+    let lock_path: PathBuf = crate::commands::update_ref::lock_ref("packed-refs")?;
+    let mut content: String = "# pack-refs with: peeled fully-peeled sorted \n".to_string();
+    for (ref_name, hash) in packed_refs {
+        content.push_str(&format!("{} {}\n", hash, ref_name));
+        let peeled_hash: String = peel_to_commit(hash);
+        if &peeled_hash != hash {
+            content.push_str(&format!("^{}\n", peeled_hash));
+        }
+    }
+    fs::write(&lock_path, content).expect("Failed to write packed-refs");
+    fs::rename(&lock_path, Path::new(".rgit").join("packed-refs"))
+        .expect("Failed to write packed-refs");
+    Ok(())
+}
+
+/// Lists the full names of the refs under a namespace (e.g. `refs/heads/`), loose or packed,
+/// sorted. Lock files are skipped.
+pub fn list_refs(namespace: &str) -> Vec<String> {
+    let mut ref_names: Vec<String> = read_packed_refs()
+        .into_keys()
+        .filter(|ref_name| ref_name.starts_with(namespace))
+        .collect();
+    let mut directories: Vec<String> = vec![namespace.trim_end_matches('/').to_string()];
+    while let Some(directory) = directories.pop() {
+        let Ok(dir_entries) = fs::read_dir(Path::new(".rgit").join(&directory)) else {
+            continue;
+        };
+        for dir_entry in dir_entries {
+            let dir_entry: fs::DirEntry = dir_entry.expect("Failed to read directory entry");
+            let name: String = format!("{}/{}", directory, dir_entry.file_name().to_string_lossy());
+            if dir_entry.path().is_dir() {
+                directories.push(name);
+            } else if !name.ends_with(".lock") && !ref_names.contains(&name) {
+                ref_names.push(name);
+            }
+        }
+    }
+    ref_names.sort();
+    ref_names
+}
+
+/// Removes a ref, loose and packed, then the directories it leaves empty (keeping
+/// `refs/heads`, `refs/tags`...).
+pub fn delete_ref(ref_name: &str) {
+    let mut packed_refs: BTreeMap<String, String> = read_packed_refs();
+    if packed_refs.remove(ref_name).is_some() {
+        if let Err(error) = write_packed_refs(&packed_refs) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+    let ref_path: PathBuf = Path::new(".rgit").join(ref_name);
+    if ref_path.is_file() {
+        fs::remove_file(&ref_path).expect("Failed to remove reference");
+    }
+    crate::commands::reflog::remove_reflog(ref_name);
+
+    let mut parent_dir: Option<&Path> = ref_path.parent();
+    while let Some(dir) = parent_dir {
+        // ".rgit/refs/<namespace>" is kept
+        if dir.components().count() <= 3 || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        parent_dir = dir.parent();
+    }
+}
+
+/// Checks a ref name against git's rules (`git check-ref-format`). With `allow_onelevel`,
+/// names without `/` (e.g. a branch name without `refs/heads/`) are accepted.
+pub fn check_ref_format(name: &str, allow_onelevel: bool) -> Result<(), String> {
+    let invalid = |reason: &str| -> Result<(), String> {
+        Err(format!("'{}' is not a valid ref name: {}", name, reason))
+    };
+
+    if name.is_empty() || name == "@" {
+        return invalid("it is empty or '@'");
+    }
+    if name.starts_with('/') || name.ends_with('/') || name.contains("//") {
+        return invalid("components cannot be empty");
+    }
+    if !allow_onelevel && !name.contains('/') {
+        return invalid("it needs at least one '/'");
+    }
+    if name.ends_with('.') {
+        return invalid("it cannot end with '.'");
+    }
+    if name.contains("..") || name.contains("@{") {
+        return invalid("it cannot contain '..' or '@{'");
+    }
+    if let Some(c) = name.chars().find(|c| {
+        c.is_ascii_control() || matches!(c, ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+    }) {
+        return invalid(&format!("it cannot contain {:?}", c));
+    }
+    for component in name.split('/') {
+        if component.starts_with('.') || component.ends_with(".lock") {
+            return invalid("components cannot start with '.' or end with '.lock'");
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a branch or tag name can be stored as a ref.
+pub fn verify_ref_name(name: &str) -> Result<(), String> {
+    if name == "HEAD" || name.starts_with('-') {
+        return Err(format!("'{}' is not a valid ref name", name));
+    }
+    check_ref_format(name, true)
+}
+
+/// Checks the full name of a ref before writing it: `HEAD`, or a valid name under `refs/`.
+pub fn verify_full_ref_name(ref_name: &str) -> Result<(), String> {
+    if ref_name == "HEAD" {
+        return Ok(());
+    }
+    if !ref_name.starts_with("refs/") {
+        return Err(format!(
+            "'{}' is not a valid ref name: it must be HEAD or start with 'refs/'",
+            ref_name
+        ));
+    }
+    check_ref_format(ref_name, false)
+}
+
+/// Follows annotated tags until reaching an object that is not a tag, usually a commit.
+pub fn peel_to_commit(hash: &str) -> String {
+    let mut hash: String = hash.to_string();
+    while object_exists(&hash) {
+        let (object_type, content): (String, Vec<u8>) = read_object(&RepoPath::Local, &hash);
+        if object_type != "tag" {
+            break;
+        }
+        let content: String = String::from_utf8_lossy(&content).into_owned();
+        match content
+            .lines()
+            .find_map(|line| line.strip_prefix("object "))
+        {
+            Some(target) => hash = target.trim().to_string(),
+            None => break,
+        }
+    }
+    hash
+}
+
+/// Matches a text against a shell pattern, where `*` matches any characters and `?` one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // matches[j]: the pattern read so far matches text[..j]
+    let mut matches: Vec<bool> = vec![false; text.len() + 1];
+    matches[0] = true;
+    for pattern_char in pattern {
+        let mut next_matches: Vec<bool> = vec![false; text.len() + 1];
+        for j in 0..=text.len() {
+            next_matches[j] = match pattern_char {
+                '*' => matches[j] || (j > 0 && next_matches[j - 1]),
+                '?' => j > 0 && matches[j - 1],
+                _ => j > 0 && matches[j - 1] && text[j - 1] == pattern_char,
+            };
+        }
+        matches = next_matches;
+    }
+    matches[text.len()]
+}
+
+/// The identity recorded by commands that write logs or objects on their own (reflogs,
+/// stash): the committer identity (see `committer_signature`), named `rgit` when no name is
+/// set.
+pub fn current_identity() -> String {
+    committer_signature(None)
+        .or_else(|_| signature_line("committer", Some("rgit")))
+        .unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        })
+}
+
+/// The committer identity, like commits record it: without a committer name, the name and
+/// email of `author_line` are used (or of the default author when not given), with the
+/// committer date. Fails when neither has a name.
+pub fn committer_signature(author_line: Option<&str>) -> Result<String, String> {
+    signature_line("committer", None).or_else(|error| {
+        let author_line: String = match author_line {
+            Some(author_line) => author_line.to_string(),
+            None => signature_line("author", None).map_err(|_| error)?,
+        };
+        signature_line("committer", Some(&parse_signature(&author_line).identity()))
+    })
+}
+
+/// Builds the `Name <email> <timestamp> <timezone>` line recording the `author` or the
+/// `committer` of a commit. Each part comes from `identity` when given (`Name <email>`,
+/// possibly followed by a date), else from the `RGIT_<ROLE>_NAME`, `RGIT_<ROLE>_EMAIL` and
+/// `RGIT_<ROLE>_DATE` (`<timestamp> [<timezone>]`) environment variables, else from the
+/// `user.name` and `user.email` config. The date defaults to now, in the local timezone.
+pub fn signature_line(role: &str, identity: Option<&str>) -> Result<String, String> {
+    build_signature_line(role, identity, &identity_variable)
+}
+
+/// Reads an identity variable from the environment. Tests never see the variables of the
+/// user running them: they pass their own to `build_signature_line`.
+fn identity_variable(name: &str) -> Option<String> {
+    if cfg!(test) {
+        None
+    } else {
+        env::var(name).ok()
+    }
+}
+
+/// Builds a signature line like `signature_line`, reading the variables with `lookup`.
+fn build_signature_line(
+    role: &str,
+    identity: Option<&str>,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let variable = |part: &str| -> Option<String> {
+        lookup(&format!("RGIT_{}_{}", role.to_uppercase(), part)).filter(|value| !value.is_empty())
+    };
+    let given: Option<Signature> = identity.map(parse_signature);
+
+    let (name, email): (String, String) = match &given {
+        Some(signature) if !signature.name.is_empty() => {
+            (signature.name.clone(), signature.email.clone())
+        }
+        _ => {
+            let name: String =
+                variable("NAME")
+                    .or_else(|| get_config("user.name"))
+                    .ok_or(format!(
+                        "fatal: unable to determine the {} identity: set user.name or RGIT_{}_NAME",
+                        role,
+                        role.to_uppercase()
+                    ))?;
+            let email: String = variable("EMAIL")
+                .or_else(|| get_config("user.email"))
+                .unwrap_or_default();
+            (name, email)
+        }
+    };
+
+    let date: Option<(i64, String)> = match given.and_then(|signature| signature.date) {
+        Some(date) => Some(date),
+        None => match variable("DATE") {
+            Some(value) => {
+                let mut parts: std::str::SplitWhitespace = value.split_whitespace();
+                let timestamp: Option<i64> = parts.next().and_then(|part| part.parse().ok());
+                let timezone: String = parts.next().unwrap_or("+0000").to_string();
+                let valid_timezone: bool = timezone.len() == 5
+                    && timezone.starts_with(['+', '-'])
+                    && timezone[1..].chars().all(|c| c.is_ascii_digit());
+                match timestamp {
+                    Some(timestamp) if valid_timezone => Some((timestamp, timezone)),
+                    _ => {
+                        return Err(format!(
+                            "fatal: invalid date in RGIT_{}_DATE: '{}'",
+                            role.to_uppercase(),
+                            value
+                        ))
+                    }
+                }
+            }
+            None => None,
+        },
+    };
+    let (timestamp, timezone): (i64, String) = date.unwrap_or_else(|| {
+        let now: i64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Failed to read the system time")
+            .as_secs() as i64;
+        (now, local_timezone())
+    });
+
+    Ok(format!("{} <{}> {} {}", name, email, timestamp, timezone))
+}
+
+/// Returns the current offset of the local timezone from UTC as `+hhmm`, as printed by
+/// `date +%z`, or `+0000` when it can not be read. It is only read once per process, which
+/// is short-lived.
+pub fn local_timezone() -> String {
+    static LOCAL_TIMEZONE: OnceLock<String> = OnceLock::new();
+    LOCAL_TIMEZONE.get_or_init(read_local_timezone).clone()
+}
+
+fn read_local_timezone() -> String {
+    let output: Option<String> = Command::new("date")
+        .arg("+%z")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    match output {
+        Some(timezone)
+            if timezone.len() == 5
+                && timezone.starts_with(['+', '-'])
+                && timezone[1..].chars().all(|c| c.is_ascii_digit()) =>
+        {
+            timezone
+        }
+        _ => "+0000".to_string(),
+    }
+}
+
+/// Returns the hash of the tree recorded in a commit.
+pub fn get_commit_tree(repo_path: &RepoPath, commit_hash: &str) -> String {
+    let commit_content: String = cat_file(repo_path, commit_hash);
+    match commit_content
+        .lines()
+        .find(|line| line.starts_with("tree "))
+        .and_then(|line| line.split_whitespace().nth(1))
+    {
+        Some(tree_hash) => tree_hash.to_string(),
+        None => {
+            eprintln!("Error: object {} is not a commit.", commit_hash);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads a tree recursively and returns a map from file path to blob hash.
+pub fn read_tree(repo_path: &RepoPath, tree_hash: &str) -> BTreeMap<String, String> {
+    let mut files: BTreeMap<String, String> = BTreeMap::new();
+    read_tree_into(repo_path, tree_hash, "", &mut files);
+    files
+}
+
+fn read_tree_into(
+    repo_path: &RepoPath,
+    tree_hash: &str,
+    prefix: &str,
+    files: &mut BTreeMap<String, String>,
+) {
+    for entry in read_tree_entries(repo_path, tree_hash) {
+        let path: String = if prefix.is_empty() {
+            entry.name
+        } else {
+            format!("{}/{}", prefix, entry.name)
+        };
+
+        match entry.object_type.as_str() {
+            "blob" => {
+                files.insert(path, entry.hash);
+            }
+            "tree" => read_tree_into(repo_path, &entry.hash, &path, files),
+            _ => {}
+        }
+    }
+}
+
+/// An entry of a tree object: `<mode> <type> <hash> <name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: String,
+    pub object_type: String,
+    pub hash: String,
+    pub name: String,
+}
+
+/// Parses the content of a tree object, rejecting entry names that could escape the worktree
+/// or reach the repository once checked out. Blob names may hold a whole relative path, as
+/// in the flat trees written by older versions (`100644 blob <hash> src/main.rs`): each of
+/// its components is checked.
+pub fn parse_tree_entries(tree_content: &str) -> Result<Vec<TreeEntry>, String> {
+    let mut entries: Vec<TreeEntry> = Vec::new();
+    for line in tree_content.lines() {
+        // the name is everything after the third space, and may itself contain spaces
+        let parts: Vec<&str> = line.splitn(4, ' ').collect();
+        if parts.len() < 4 {
+            continue;
+        }
+
+        let name: &str = parts[3];
+        if parts[1] == "blob" && name.contains('/') {
+            verify_path(name)
+        } else {
+            verify_path_component(name)
+        }
+        .map_err(|error| format!("invalid tree entry '{}': {}", name, error))?;
+        entries.push(TreeEntry {
+            mode: parts[0].to_string(),
+            object_type: parts[1].to_string(),
+            hash: parts[2].to_string(),
+            name: name.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Reads and parses a tree object, exiting if it contains an unsafe entry name.
+pub fn read_tree_entries(repo_path: &RepoPath, tree_hash: &str) -> Vec<TreeEntry> {
+    let tree_content: String = cat_file(repo_path, tree_hash);
+    parse_tree_entries(&tree_content).unwrap_or_else(|error| {
+        eprintln!("Error: tree {}: {}", tree_hash, error);
+        std::process::exit(1);
+    })
+}
+
+/// Checks that a name can be used as one component of a path in the worktree: it must not
+/// be empty, `.` or `..`, contain a separator or a NUL byte, or name the repository
+/// directory, including variants that some filesystems treat as `.rgit` (other case,
+/// trailing dots or spaces, 8.3 short name).
+pub fn verify_path_component(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("empty name".to_string());
+    }
+    if name == "." || name == ".." {
+        return Err("relative path component".to_string());
+    }
+    if name.contains(['/', '\\']) {
+        return Err("contains a path separator".to_string());
+    }
+    if name.contains('\0') {
+        return Err("contains a NUL byte".to_string());
+    }
+
+    let folded: String = name.trim_end_matches(['.', ' ']).to_lowercase();
+    if folded == ".rgit" || folded == "rgit~1" {
+        return Err("reserved repository name".to_string());
+    }
+    Ok(())
+}
+
+/// Checks every component of a relative path with `verify_path_component`, so that it can
+/// be written in the worktree safely.
+pub fn verify_path(path: &str) -> Result<(), String> {
+    if path.starts_with('/') {
+        return Err(format!("'{}': absolute path", path));
+    }
+    for component in path.split('/') {
+        verify_path_component(component).map_err(|error| format!("'{}': {}", path, error))?;
+    }
+    Ok(())
+}
+
+/// An identity recorded in a commit, written `Name <email> <timestamp> <timezone>`. The email
+/// and the date are optional, older commits only record a free-form name.
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// unix timestamp and `+hhmm` timezone
+    pub date: Option<(i64, String)>,
+}
+
+impl Signature {
+    /// Returns the identity without the date, e.g. `Jane Doe <jane@example.com>`.
+    pub fn identity(&self) -> String {
+        if self.email.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} <{}>", self.name, self.email)
+        }
+    }
+}
+
+pub fn parse_signature(line: &str) -> Signature {
+    let line: &str = line.trim();
+
+    // the date is the last two words: "<timestamp> <timezone>"
+    let parts: Vec<&str> = line.rsplitn(3, ' ').collect();
+    let (identity, date): (&str, Option<(i64, String)>) = match parts.as_slice() {
+        [timezone, timestamp, identity]
+            if timezone.starts_with(['+', '-']) && timestamp.parse::<i64>().is_ok() =>
+        {
+            (
+                identity,
+                Some((timestamp.parse().unwrap(), timezone.to_string())),
+            )
+        }
+        _ => (line, None),
+    };
+
+    match (identity.find('<'), identity.rfind('>')) {
+        (Some(start), Some(end)) if start < end => Signature {
+            name: identity[..start].trim().to_string(),
+            email: identity[start + 1..end].to_string(),
+            date,
+        },
+        _ => Signature {
+            name: identity.to_string(),
+            email: String::new(),
+            date,
+        },
+    }
+}
+
+/// Formats a unix timestamp in a `+hhmm` timezone as an RFC 2822 date, as used in mail
+/// headers (e.g. "Thu, 13 Apr 2023 14:30:00 +0200").
+pub fn format_rfc2822_date(timestamp: i64, timezone: &str) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    // "+hhmm" or "-hhmm", anything else is treated as UTC
+    let offset_minutes: i64 = match (timezone.get(1..3), timezone.get(3..5)) {
+        (Some(hours), Some(minutes)) if timezone.len() == 5 => {
+            let offset: i64 =
+                hours.parse::<i64>().unwrap_or(0) * 60 + minutes.parse::<i64>().unwrap_or(0);
+            if timezone.starts_with('-') {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => 0,
+    };
+    let local_time: i64 = timestamp + offset_minutes * 60;
+    let days: i64 = local_time.div_euclid(86400);
+    let seconds_of_day: i64 = local_time.rem_euclid(86400);
+
+    // civil date from the number of days since 1970-01-01 (Howard Hinnant's algorithm)
+    let shifted_days: i64 = days + 719468;
+    let era: i64 = shifted_days.div_euclid(146097);
+    let day_of_era: i64 = shifted_days.rem_euclid(146097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: i64 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year: i64 = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} {}",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        if offset_minutes == 0 && timezone.len() != 5 {
+            "+0000"
+        } else {
+            timezone
+        }
+    )
+}
+
+/// Parses an RFC 2822 date as found in mail headers (e.g. "Thu, 13 Apr 2023 14:30:00 +0200")
+/// into a unix timestamp and its `+hhmm` timezone. The weekday and the seconds are optional.
+pub fn parse_rfc2822_date(date: &str) -> Option<(i64, String)> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    // skip the weekday, if any
+    let date: &str = match date.split_once(',') {
+        Some((_, rest)) => rest,
+        None => date,
+    };
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    let [day, month, year, time, timezone] = parts.as_slice() else {
+        return None;
+    };
+
+    let day: i64 = day.parse().ok().filter(|day| (1..=31).contains(day))?;
+    let month: i64 = MONTHS
+        .iter()
+        .position(|name| month.eq_ignore_ascii_case(name))? as i64
+        + 1;
+    let year: i64 = year.parse().ok()?;
+    let time_parts: Vec<i64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<i64>>>()?;
+    let (hours, minutes, seconds): (i64, i64, i64) = match time_parts.as_slice() {
+        [hours, minutes] => (*hours, *minutes, 0),
+        [hours, minutes, seconds] => (*hours, *minutes, *seconds),
+        _ => return None,
+    };
+    let valid_timezone: bool = timezone.len() == 5
+        && timezone.starts_with(['+', '-'])
+        && timezone[1..].chars().all(|c| c.is_ascii_digit());
+    if !valid_timezone || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let offset_minutes: i64 =
+        timezone[1..3].parse::<i64>().ok()? * 60 + timezone[3..5].parse::<i64>().ok()?;
+    let offset_minutes: i64 = if timezone.starts_with('-') {
+        -offset_minutes
+    } else {
+        offset_minutes
+    };
+
+    // days since 1970-01-01 from the civil date (Howard Hinnant's algorithm)
+    let shifted_year: i64 = if month <= 2 { year - 1 } else { year };
+    let era: i64 = shifted_year.div_euclid(400);
+    let year_of_era: i64 = shifted_year.rem_euclid(400);
+    let month_index: i64 = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year: i64 = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era: i64 = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days: i64 = era * 146097 + day_of_era - 719468;
+
+    let local_time: i64 = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some((local_time - offset_minutes * 60, timezone.to_string()))
+}
+
+/// Looks up a path in a tree and returns the type and hash of the entry.
+pub fn find_tree_entry(
+    repo_path: &RepoPath,
+    tree_hash: &str,
+    path: &str,
+) -> Option<(String, String)> {
+    let path: &str = path.trim_matches('/');
+    if path.is_empty() {
+        return Some(("tree".to_string(), tree_hash.to_string()));
+    }
+
+    for entry in read_tree_entries(repo_path, tree_hash) {
+        if entry.name == path {
+            return Some((entry.object_type, entry.hash));
+        }
+        if let Some(rest) = path.strip_prefix(&format!("{}/", entry.name)) {
+            if entry.object_type == "tree" {
+                return find_tree_entry(repo_path, &entry.hash, rest);
+            }
+        }
+    }
+    None
+}
+
 pub fn collect_objects(repo_path: &RepoPath, commit_hash: &str) -> HashSet<String> {
     println!("collect_objects");
     let mut visited: HashSet<String> = HashSet::new();
@@ -75,10 +846,11 @@ pub fn collect_objects(repo_path: &RepoPath, commit_hash: &str) -> HashSet<Strin
 
         visited.insert(current_hash.clone());
 
-        // read the content of the current commit or tree
-        let content: String = cat_file(repo_path, &current_hash);
+        // read the current object: commits and trees point to other objects
+        let (object_type, content): (String, Vec<u8>) = read_object(repo_path, &current_hash);
+        let content: String = String::from_utf8_lossy(&content).into_owned();
 
-        if content.contains("author") {
+        if object_type == "commit" {
             // for commits, add the parent(s) and tree
             if let Some(tree_line) = content.lines().find(|line| line.starts_with("tree ")) {
                 let tree_hash: String = tree_line.split_whitespace().nth(1).unwrap().to_string();
@@ -91,18 +863,19 @@ pub fn collect_objects(repo_path: &RepoPath, commit_hash: &str) -> HashSet<Strin
                     parent_line.split_whitespace().nth(1).unwrap().to_string();
                 to_visit.push(parent_hash);
             }
-        } else if content.contains("tree") {
-            // for trees, add blobs and subtrees
-            for line in content.lines() {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() < 4 {
-                    continue;
-                }
-
-                let object_hash: String = parts[2].to_string();
-
-                // add blobs and subtrees to visit
-                to_visit.push(object_hash);
+        } else if object_type == "tag" {
+            // for annotated tags, add the tagged object
+            if let Some(object_line) = content.lines().find(|line| line.starts_with("object ")) {
+                to_visit.push(object_line.trim_start_matches("object ").trim().to_string());
+            }
+        } else if object_type == "tree" {
+            // for trees, add blobs and subtrees, refusing unsafe entry names
+            let entries: Vec<TreeEntry> = parse_tree_entries(&content).unwrap_or_else(|error| {
+                eprintln!("Error: tree {}: {}", current_hash, error);
+                std::process::exit(1);
+            });
+            for entry in entries {
+                to_visit.push(entry.hash);
             }
         }
     }
@@ -156,15 +929,54 @@ pub fn get_missing_objects(repo_path: &RepoPath, objects: &HashSet<String>) -> H
 
 #[cfg(test)]
 pub mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
     use std::env;
     use std::fs;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Mutex, MutexGuard};
+
+    /// tests share the current directory of the process: only one of them may use
+    /// `test-repo` at a time
+    static TEST_REPO_LOCK: Mutex<()> = Mutex::new(());
+
+    /// holds the lock while a test uses `test-repo`, and goes back to the directory the test
+    /// started from when dropped, even if the test panicked
+    struct TestRepoGuard {
+        original_dir: PathBuf,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Drop for TestRepoGuard {
+        fn drop(&mut self) {
+            let _ = env::set_current_dir(&self.original_dir);
+        }
+    }
+
+    thread_local! {
+        static TEST_REPO_GUARD: RefCell<Option<TestRepoGuard>> = const { RefCell::new(None) };
+    }
 
     /// removes the test directory `test-repo` if it exists, creates a new one,
-    /// and initializes a `.rgit` repository in this directory
+    /// and initializes a `.rgit` repository in this directory. The directory is locked until
+    /// `remove_test_repo` (or the end of the test thread)
     pub fn setup_test_repo() {
         let repo_dir: &str = "test-repo";
 
+        // release a guard left by a test that panicked on this thread, then wait for the
+        // other tests to be done with the directory
+        TEST_REPO_GUARD.with(|guard| guard.borrow_mut().take());
+        let lock: MutexGuard<'static, ()> = TEST_REPO_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let original_dir: PathBuf = env::current_dir().expect("failed to read current directory");
+        TEST_REPO_GUARD.with(|guard| {
+            *guard.borrow_mut() = Some(TestRepoGuard {
+                original_dir,
+                _lock: lock,
+            })
+        });
+
         // if the directory already exists, remove it
         if Path::new(repo_dir).exists() {
             fs::remove_dir_all(repo_dir).expect("failed to remove existing test-repo directory");
@@ -177,7 +989,244 @@ pub mod tests {
         env::set_current_dir(repo_dir).expect("failed to change directory to test-repo");
 
         // initialize the `.rgit` repository
-        crate::commands::init::init();
+        let options: crate::commands::init::InitOptions =
+            crate::commands::init::parse_init_options(&[]);
+        crate::commands::init::init(&options);
+    }
+
+    #[test]
+    fn test_signature_line_reads_variables_before_config() {
+        setup_test_repo();
+
+        let variables = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let build = |identity: Option<&str>, variables: &HashMap<String, String>| {
+            super::build_signature_line("author", identity, &|name: &str| {
+                variables.get(name).cloned()
+            })
+        };
+
+        // without any name, there is no identity
+        assert!(build(None, &variables(&[("RGIT_AUTHOR_EMAIL", "a@example.com")])).is_err());
+        set_test_identity();
+
+        // the config, dated now in the local timezone
+        let line: String = build(None, &variables(&[])).unwrap();
+        assert!(line.starts_with("Config User <config@example.com> "));
+        assert!(line.ends_with(&format!(" {}", super::local_timezone())));
+
+        // the variables override the config, and an explicit author both
+        let env: HashMap<String, String> = variables(&[
+            ("RGIT_AUTHOR_NAME", "Env Author"),
+            ("RGIT_AUTHOR_DATE", "1681396200 +0200"),
+            ("RGIT_COMMITTER_NAME", "Env Committer"),
+        ]);
+        assert_eq!(
+            build(None, &env),
+            Ok("Env Author <config@example.com> 1681396200 +0200".to_string())
+        );
+        assert_eq!(
+            build(Some("Jane Doe <jane@example.com>"), &env),
+            Ok("Jane Doe <jane@example.com> 1681396200 +0200".to_string())
+        );
+        assert_eq!(
+            build(Some("Jane Doe <jane@example.com> 1000 -0100"), &env),
+            Ok("Jane Doe <jane@example.com> 1000 -0100".to_string())
+        );
+        assert!(build(None, &variables(&[("RGIT_AUTHOR_DATE", "yesterday")])).is_err());
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_parse_rfc2822_date_reverses_format() {
+        assert_eq!(
+            super::parse_rfc2822_date("Thu, 13 Apr 2023 16:30:00 +0200"),
+            Some((1681396200, "+0200".to_string()))
+        );
+        assert_eq!(
+            super::parse_rfc2822_date("29 Feb 2024 23:59 -0130"),
+            Some((1709256540, "-0130".to_string()))
+        );
+        for (timestamp, timezone) in [(0, "+0000"), (951782400, "-0800"), (1681396200, "+0530")] {
+            let date: String = super::format_rfc2822_date(timestamp, timezone);
+            assert_eq!(
+                super::parse_rfc2822_date(&date),
+                Some((timestamp, timezone.to_string()))
+            );
+        }
+        for date in [
+            "",
+            "Thu, 13 Foo 2023 16:30:00 +0200",
+            "13 Apr 2023 16:30:00 CEST",
+        ] {
+            assert_eq!(super::parse_rfc2822_date(date), None);
+        }
+    }
+
+    #[test]
+    fn test_parse_tree_entries_rejects_unsafe_names() {
+        let hash: &str = "0123456789abcdef0123456789abcdef01234567";
+        let tree = |name: &str| -> String { format!("100644 blob {} {}\n", hash, name) };
+
+        let entries: Vec<super::TreeEntry> =
+            super::parse_tree_entries(&tree("file with spaces.txt")).unwrap();
+        assert_eq!(entries[0].name, "file with spaces.txt");
+
+        for name in [
+            "..",
+            ".",
+            "../../.bashrc",
+            "/etc/passwd",
+            "dir\\file",
+            "a\0b",
+            ".rgit",
+            ".RGIT",
+            ".Rgit.",
+            ".rgit ",
+            "RGIT~1",
+        ] {
+            assert!(
+                super::parse_tree_entries(&tree(name)).is_err(),
+                "'{}' should be rejected.",
+                name
+            );
+        }
+        assert!(super::parse_tree_entries(&tree(".rgitignore")).is_ok());
+        for name in ["src/../../x", "src//x", "sub/.rgit/config", "dir/"] {
+            assert!(
+                super::parse_tree_entries(&tree(name)).is_err(),
+                "'{}' should be rejected.",
+                name
+            );
+        }
+        let subtree: String = format!("040000 tree {} src/lib\n", hash);
+        assert!(super::parse_tree_entries(&subtree).is_err());
+    }
+
+    #[test]
+    fn test_read_tree_accepts_flat_legacy_trees() {
+        setup_test_repo();
+
+        // older versions wrote one blob entry per file, with its whole path as the name
+        let blob_hash: String = super::hash_and_store("blob", b"fn main() {}\n");
+        let tree_hash: String = super::hash_and_store(
+            "tree",
+            format!(
+                "100644 blob {0} README\n100644 blob {0} src/main.rs\n100644 blob {0} src/commands/mod.rs\n",
+                blob_hash
+            )
+            .as_bytes(),
+        );
+
+        let files: Vec<(String, String)> = super::read_tree(&super::RepoPath::Local, &tree_hash)
+            .into_iter()
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("README".to_string(), blob_hash.clone()),
+                ("src/commands/mod.rs".to_string(), blob_hash.clone()),
+                ("src/main.rs".to_string(), blob_hash.clone()),
+            ]
+        );
+        assert_eq!(
+            super::find_tree_entry(&super::RepoPath::Local, &tree_hash, "src/main.rs"),
+            Some(("blob".to_string(), blob_hash))
+        );
+
+        remove_test_repo();
+    }
+
+    #[test]
+    fn test_verify_path_checks_every_component() {
+        assert!(super::verify_path("src/main.rs").is_ok());
+        assert!(super::verify_path("/etc/passwd").is_err());
+        assert!(super::verify_path("src/../../x").is_err());
+        assert!(super::verify_path("src//x").is_err());
+        assert!(super::verify_path("sub/.rgit/hooks/post-commit").is_err());
+    }
+
+    #[test]
+    fn test_check_ref_format_follows_git_rules() {
+        for name in [
+            "refs/heads/main",
+            "refs/tags/v1.0",
+            "refs/heads/feature/x-1",
+            "refs/stash",
+        ] {
+            assert!(super::check_ref_format(name, false).is_ok(), "{}", name);
+        }
+        for name in [
+            "main",
+            "refs/heads/../../etc/passwd",
+            "refs/heads/with space",
+            "refs/heads/HEAD.lock",
+            "refs/heads/.hidden",
+            "refs/heads/x.",
+            "refs//heads",
+            "/refs/heads/x",
+            "refs/heads/a~1",
+            "refs/heads/a^",
+            "refs/heads/a:b",
+            "refs/heads/a*",
+            "refs/heads/a@{1}",
+            "refs/heads/a\\b",
+            "refs/heads/tab\tx",
+            "@",
+        ] {
+            assert!(super::check_ref_format(name, false).is_err(), "{}", name);
+        }
+        assert!(super::check_ref_format("main", true).is_ok());
+
+        assert!(super::verify_full_ref_name("HEAD").is_ok());
+        assert!(super::verify_full_ref_name("../HEAD").is_err());
+        assert!(super::verify_full_ref_name("objects/ab").is_err());
+        assert!(super::verify_ref_name("-main").is_err());
+    }
+
+    /// sets `user.name` and `user.email` in the repository config, for the commands that
+    /// need an identity of their own (e.g. annotated tags)
+    pub fn set_test_identity() {
+        let mut config: String = fs::read_to_string(".rgit/config").unwrap_or_default();
+        config.push_str("[user]\n    name = Config User\n    email = config@example.com\n");
+        fs::write(".rgit/config", config).expect("failed to write test config");
+    }
+
+    /// commits `files` (path and content pairs, written to the worktree and the index) on
+    /// top of `parents`, and returns the new commit hash: builds merge histories in tests
+    pub fn commit_files(message: &str, files: &[(&str, &str)], parents: &[&str]) -> String {
+        for (path, content) in files {
+            fs::write(path, content).expect("failed to write test file");
+            crate::commands::update_index::add_index(
+                path,
+                &crate::commands::hash_object::hash_object(path),
+            );
+        }
+        crate::commands::commit_tree::commit_tree(
+            message,
+            Some("John Doe <john@example.com>"),
+            crate::commands::write_tree::write_tree(),
+            parents,
+        )
+    }
+
+    /// commits `files` like `commit_files`, on top of HEAD, and moves HEAD (or its branch) to
+    /// the new commit, whose hash is returned
+    pub fn commit_files_on_head(message: &str, files: &[(&str, &str)]) -> String {
+        for (path, content) in files {
+            fs::write(path, content).expect("failed to write test file");
+            crate::commands::update_index::add_index(
+                path,
+                &crate::commands::hash_object::hash_object(path),
+            );
+        }
+        crate::commands::commit::commit(message, Some("John Doe"));
+        crate::commands::get_head_hash::get_head_hash()
     }
 
     /// returns to the parent directory and removes the `test-repo` directory
@@ -191,5 +1240,8 @@ pub mod tests {
         if Path::new(repo_dir).exists() {
             fs::remove_dir_all(repo_dir).expect("failed to remove test-repo directory");
         }
+
+        // let the next test use the directory
+        TEST_REPO_GUARD.with(|guard| guard.borrow_mut().take());
     }
 }